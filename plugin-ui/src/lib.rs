@@ -1,16 +1,169 @@
 wit_bindgen::generate!({
-    world: "plugin",
+    world: "extended-plugin",
     generate_all
 });
 
-use exports::test::Guest as TestGuest;
+use exports::pato::plugin_ui::bytes::Guest as BytesGuest;
+use exports::pato::plugin_ui::filter::Guest as FilterGuest;
+use exports::pato::plugin_ui::health::{Guest as HealthGuest, HealthStatus};
+use exports::pato::plugin_ui::inbox::Guest as InboxGuest;
+use exports::pato::plugin_ui::lifecycle::Guest as LifecycleGuest;
+use exports::pato::plugin_ui::pipeline::Guest as PipelineGuest;
+use exports::pato::plugin_ui::settings::{ConfigField, ConfigType, Guest as SettingsGuest};
+use exports::pato::plugin_ui::streamer::Guest as StreamerGuest;
+use exports::pato::plugin_ui::subscriber::Guest as SubscriberGuest;
+use exports::pato::plugin_ui::timer_callback::Guest as TimerGuest;
+use exports::pato::plugin_ui::ui::{ContributionKind, Guest as UiGuest, UiContribution};
+use exports::test::{Guest as TestGuest, Stats, TransformInput};
+use pato::plugin_ui::config::get_config;
+use pato::plugin_ui::db;
+use pato::plugin_ui::events::emit_json;
+use pato::plugin_ui::filter_helper;
+use pato::plugin_ui::host_info;
+use pato::plugin_ui::log::{log, LogLevel};
+use pato::plugin_ui::chunks::emit_chunk;
 
 struct Component;
 
 impl TestGuest for Component {
     fn get_number() -> u32 {
-        42
+        log(LogLevel::Info, "get-number called");
+        match get_config("greeting") {
+            Some(greeting) => log(LogLevel::Info, &format!("greeting from config: {}", greeting)),
+            None => log(LogLevel::Debug, "no greeting configured"),
+        }
+        // Remember how many times we've been asked, across reloads
+        let calls = db::get("calls")
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u32::from_le_bytes)
+            .unwrap_or(0)
+            + 1;
+        if let Err(e) = db::set("calls", &calls.to_le_bytes()) {
+            log(LogLevel::Warn, &format!("failed to save call count: {}", e));
+        }
+        log(LogLevel::Debug, &format!("get-number call #{}", calls));
+        emit_json("progress", "{\"percent\":0}");
+        let number = 42;
+        emit_json("progress", "{\"percent\":100}");
+        number
+    }
+
+    fn get_stats() -> Stats {
+        let count = db::get("calls")
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u32::from_le_bytes)
+            .unwrap_or(0);
+        let label = get_config("greeting").unwrap_or_else(|| "plugin-ui".to_string());
+        Stats { count, label }
+    }
+
+    fn transform(input: TransformInput) -> String {
+        log(LogLevel::Debug, &format!("transform called with {:?}", input));
+        format!("{} x{}", input.name, input.count)
+    }
+}
+
+impl InboxGuest for Component {
+    fn receive(sender: String, message: Vec<u8>) {
+        log(
+            LogLevel::Info,
+            &format!("message from {}: {}", sender, String::from_utf8_lossy(&message)),
+        );
+    }
+}
+
+impl SubscriberGuest for Component {
+    fn on_event(topic: String, payload: Vec<u8>) {
+        log(
+            LogLevel::Info,
+            &format!("event on {}: {}", topic, String::from_utf8_lossy(&payload)),
+        );
+    }
+}
+
+impl PipelineGuest for Component {
+    fn process(input: String) -> String {
+        input.to_uppercase()
+    }
+}
+
+impl BytesGuest for Component {
+    fn hash(data: Vec<u8>) -> Vec<u8> {
+        // FNV-1a: tiny, and good enough to show the bytes arrived intact
+        let hash = data.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+        hash.to_be_bytes().to_vec()
+    }
+}
+
+impl FilterGuest for Component {
+    fn filter(items: Vec<String>, pattern: String) -> Vec<String> {
+        // Calls back into the host for each item while the host waits on this call
+        items
+            .into_iter()
+            .filter(|item| filter_helper::matches(item, &pattern))
+            .collect()
+    }
+}
+
+impl StreamerGuest for Component {
+    fn run(count: u32) -> u32 {
+        for i in 0..count {
+            emit_chunk(format!("chunk {} of {}", i + 1, count).as_bytes());
+        }
+        count
+    }
+}
+
+impl TimerGuest for Component {
+    fn on_timer(id: u64) {
+        log(LogLevel::Debug, &format!("timer {} fired", id));
+    }
+}
+
+impl LifecycleGuest for Component {
+    fn init(params: Vec<(String, String)>) {
+        log(LogLevel::Info, &format!("plugin-ui initialised with {} param(s)", params.len()));
+        log(
+            LogLevel::Info,
+            &format!("running as {} in pato {} on {}", host_info::plugin_id(), host_info::version(), host_info::platform()),
+        );
+        for (key, value) in params {
+            log(LogLevel::Debug, &format!("init param {} = {}", key, value));
+        }
+    }
+
+    fn shutdown() {
+        log(LogLevel::Info, "plugin-ui shutting down");
+    }
+}
+
+impl SettingsGuest for Component {
+    fn config_schema() -> Vec<ConfigField> {
+        vec![ConfigField {
+            key: "greeting".to_string(),
+            kind: ConfigType::Text,
+            description: "Logged by get-number, and the label in get-stats".to_string(),
+        }]
+    }
+}
+
+impl UiGuest for Component {
+    fn contributions() -> Vec<UiContribution> {
+        vec![UiContribution {
+            id: "get-number".to_string(),
+            kind: ContributionKind::MenuItem,
+            label: "Get number".to_string(),
+            target_function: "test#get-number".to_string(),
+        }]
+    }
+}
+
+impl HealthGuest for Component {
+    fn health() -> HealthStatus {
+        HealthStatus::Healthy
     }
 }
 
-export!(Component);
\ No newline at end of file
+export!(Component);
@@ -0,0 +1,31 @@
+//! Where the host's background tasks run: Tauri's async runtime in the app, or
+//! one of our own, set up the same way, when the runtime is embedded without Tauri.
+
+#[cfg(feature = "tauri")]
+pub use tauri::async_runtime::{block_on, spawn, JoinHandle};
+
+#[cfg(not(feature = "tauri"))]
+pub use standalone::{block_on, spawn, JoinHandle};
+
+#[cfg(not(feature = "tauri"))]
+mod standalone {
+    use std::future::Future;
+    use std::sync::LazyLock;
+
+    pub use tokio::task::JoinHandle;
+
+    static RUNTIME: LazyLock<tokio::runtime::Runtime> =
+        LazyLock::new(|| tokio::runtime::Runtime::new().expect("failed to start the async runtime"));
+
+    pub fn spawn<F>(task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        RUNTIME.spawn(task)
+    }
+
+    pub fn block_on<F: Future>(task: F) -> F::Output {
+        RUNTIME.block_on(task)
+    }
+}
@@ -0,0 +1,9 @@
+//! Prints the plugin manifest JSON Schema. Regenerate the copy at the repo root with
+//!
+//! ```sh
+//! cargo run --bin manifest-schema > ../plugin-manifest.schema.json
+//! ```
+
+fn main() {
+    print!("{}", pato_lib::manifest::PluginManifest::schema_json());
+}
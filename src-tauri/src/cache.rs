@@ -0,0 +1,226 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use wasmtime::component::Component;
+use wasmtime::Engine;
+
+use crate::error::PluginError;
+
+/// Name of the directory, next to the plugins, that holds compiled artifacts.
+const CACHE_DIR: &str = ".cache";
+
+/// How much the compilation cache is holding.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheStats {
+    /// Compiled artifacts, one per plugin build and engine configuration
+    pub entries: u64,
+    /// Bytes on disk, including each artifact's timing file
+    pub total_bytes: u64,
+}
+
+/// Compiles `bytes` into a component, reusing a previously compiled artifact
+/// from disk when one exists for the same bytes and engine configuration.
+pub fn load_component(engine: &Engine, plugin_path: &Path, bytes: &[u8]) -> Result<Component, PluginError> {
+    let artifact = artifact_path(engine, plugin_path, bytes);
+
+    if let Some(artifact) = &artifact {
+        if artifact.exists() {
+            let started = Instant::now();
+            // SAFETY: the artifact was written by `Engine::precompile_component` below and is
+            // keyed by both the source hash and the hash of the engine that wrote it. Wasmtime
+            // still validates the header and rejects artifacts from other versions.
+            match unsafe { Component::deserialize_file(engine, artifact) } {
+                Ok(component) => {
+                    let cached_ms = started.elapsed().as_millis();
+                    match read_compile_ms(artifact) {
+                        Some(compile_ms) => println!(
+                            "⚡ Loaded {:?} from cache in {}ms (compiling took {}ms)",
+                            plugin_path.file_name().unwrap_or_default(),
+                            cached_ms,
+                            compile_ms
+                        ),
+                        None => println!(
+                            "⚡ Loaded {:?} from cache in {}ms",
+                            plugin_path.file_name().unwrap_or_default(),
+                            cached_ms
+                        ),
+                    }
+                    return Ok(component);
+                }
+                Err(e) => println!("⚠️ Ignoring unusable cached artifact {:?}: {}", artifact, e),
+            }
+        }
+    }
+
+    let started = Instant::now();
+    let serialized = engine
+        .precompile_component(bytes)
+        .map_err(|e| PluginError::compile(bytes, e))?;
+    let compile_ms = started.elapsed().as_millis();
+    println!(
+        "🛠️ Compiled {:?} in {}ms",
+        plugin_path.file_name().unwrap_or_default(),
+        compile_ms
+    );
+
+    // A failure to write the cache only costs us speed next time
+    if let Some(artifact) = &artifact {
+        if let Err(e) = write_artifact(artifact, &serialized, compile_ms) {
+            println!("⚠️ Failed to cache compiled plugin {:?}: {}", artifact, e);
+        }
+    }
+
+    // SAFETY: `serialized` was just produced by this engine.
+    unsafe { Component::deserialize(engine, &serialized) }.map_err(|e| PluginError::CompileFailed(e.to_string()))
+}
+
+/// Where the artifact for these bytes lives, e.g.
+/// `plugins/.cache/plugin_ui-<engine hash>-<source hash>.cwasm`.
+fn artifact_path(engine: &Engine, plugin_path: &Path, bytes: &[u8]) -> Option<PathBuf> {
+    let dir = plugin_path.parent()?.join(CACHE_DIR);
+    let stem = plugin_path.file_stem()?.to_str()?;
+    let digest = Sha256::digest(bytes);
+    let source = hex(&digest[..16]);
+    Some(dir.join(format!("{}-{}-{}.cwasm", stem, engine_hash(engine)?, source)))
+}
+
+/// The smallest component there is: the component preamble and nothing else.
+const EMPTY_COMPONENT: &[u8] = b"\0asm\x0d\0\x01\0";
+
+/// Identifies the wasmtime version and settings artifacts are only valid for.
+/// Wasmtime writes both into every artifact it produces, so the SHA-256 of an
+/// empty component's artifact changes exactly when they do, and stays the same
+/// from one build to the next otherwise. `None` if this engine can't compile.
+fn engine_hash(engine: &Engine) -> Option<String> {
+    let artifact = engine.precompile_component(EMPTY_COMPONENT).ok()?;
+    Some(hex(&Sha256::digest(artifact)[..8]))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Deletes the cached artifacts for `plugins_dir` that `engine` can't use,
+/// because an older wasmtime or engine configuration compiled them, returning
+/// the bytes freed. Nothing would ever load them again.
+pub fn prune(engine: &Engine, plugins_dir: &Path) -> u64 {
+    // Without a hash of our own there's no telling which artifacts are stale
+    let Some(current) = engine_hash(engine) else {
+        return 0;
+    };
+    let mut freed = 0;
+    for (path, size) in cache_files(plugins_dir) {
+        // Timing files go with their artifact, whose name they share
+        let engine_part = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.rsplit('-').nth(1));
+        if engine_part == Some(current.as_str()) {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(()) => freed += size,
+            Err(e) => println!("⚠️ Couldn't remove stale cached artifact {:?}: {}", path, e),
+        }
+    }
+    freed
+}
+
+fn write_artifact(artifact: &Path, serialized: &[u8], compile_ms: u128) -> std::io::Result<()> {
+    if let Some(dir) = artifact.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(artifact, serialized)?;
+    std::fs::write(artifact.with_extension("ms"), compile_ms.to_string())
+}
+
+fn read_compile_ms(artifact: &Path) -> Option<u128> {
+    std::fs::read_to_string(artifact.with_extension("ms"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Counts the artifacts in the cache next to the plugins in `plugins_dir`.
+pub fn stats(plugins_dir: &Path) -> CacheStats {
+    let mut stats = CacheStats::default();
+    for (path, size) in cache_files(plugins_dir) {
+        if path.extension().and_then(|s| s.to_str()) == Some("cwasm") {
+            stats.entries += 1;
+        }
+        stats.total_bytes += size;
+    }
+    stats
+}
+
+/// Deletes every cached artifact for `plugins_dir`, returning the bytes freed.
+///
+/// Loaded plugins already hold their compiled code in memory, so this only
+/// means their next load compiles from scratch. A file that can't be removed
+/// (Windows won't delete a mapped one) is skipped and left for next time.
+pub fn clear(plugins_dir: &Path) -> u64 {
+    let mut freed = 0;
+    for (path, size) in cache_files(plugins_dir) {
+        match std::fs::remove_file(&path) {
+            Ok(()) => freed += size,
+            Err(e) => println!("⚠️ Couldn't remove cached artifact {:?}: {}", path, e),
+        }
+    }
+    freed
+}
+
+fn cache_files(plugins_dir: &Path) -> Vec<(PathBuf, u64)> {
+    let Ok(entries) = std::fs::read_dir(plugins_dir.join(CACHE_DIR)) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(|metadata| metadata.is_file())?;
+            Some((entry.path(), metadata.len()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{component, engine, ANSWER};
+
+    #[test]
+    fn artifacts_are_reused_and_named_for_the_engine() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = dir.path().join("cached.wasm");
+        let bytes = component(ANSWER);
+
+        load_component(engine(), &plugin, &bytes).unwrap();
+        let artifact = artifact_path(engine(), &plugin, &bytes).unwrap();
+        assert!(artifact.exists());
+        // The same engine always gets the same hash
+        let hash = engine_hash(engine()).unwrap();
+        assert_eq!(engine_hash(engine()).as_ref(), Some(&hash));
+        let name = artifact.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(&format!("cached-{}-", hash)), "{}", name);
+
+        // A second load comes from the cache
+        load_component(engine(), &plugin, &bytes).unwrap();
+        assert_eq!(stats(dir.path()).entries, 1);
+    }
+
+    #[test]
+    fn pruning_keeps_only_what_this_engine_can_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let plugin = dir.path().join("pruned.wasm");
+        load_component(engine(), &plugin, &component(ANSWER)).unwrap();
+        let cache = dir.path().join(CACHE_DIR);
+        std::fs::write(cache.join("pruned-0000000000000000-00.cwasm"), "stale").unwrap();
+        std::fs::write(cache.join("pruned-0000000000000000-00.ms"), "1").unwrap();
+        std::fs::write(cache.join("old-format.cwasm"), "stale").unwrap();
+
+        assert_eq!(prune(engine(), dir.path()), 11);
+        let stats = stats(dir.path());
+        assert_eq!(stats.entries, 1);
+    }
+}
@@ -0,0 +1,190 @@
+use serde_json::{json, Value};
+use std::io::Write;
+
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::{epoch, messaging, stream, PluginRuntime};
+
+const USAGE: &str = "usage: pato run <plugin-id> <function> [args-json] [--quiet] [--plugins-dir <path>]";
+
+/// `pato run <plugin-id> <function> [args-json]`: loads the plugins directory,
+/// calls one export, prints its result as JSON, and exits.
+pub struct RunCommand {
+    pub plugin: String,
+    pub function: String,
+    /// Positional arguments, as `invoke_export` takes them
+    pub args: Value,
+    /// Send the host's own messages to stderr, leaving only the result on stdout
+    pub quiet: bool,
+}
+
+/// Picks `pato run ...` out of the command line. `None` means start the app as
+/// usual; `Some(Err(_))` is a `run` with bad arguments.
+pub fn parse(args: impl Iterator<Item = String>) -> Option<Result<RunCommand, String>> {
+    let mut args = args.skip(1).peekable();
+    if args.peek().map(String::as_str) != Some("run") {
+        return None;
+    }
+    args.next();
+
+    let mut positional = Vec::new();
+    let mut quiet = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--quiet" | "-q" => quiet = true,
+            // Read again by `dev_mode`
+            "--dev" => {}
+            // Read again by `find_plugins_dir`
+            "--plugins-dir" => {
+                args.next();
+            }
+            _ if arg.starts_with("--plugins-dir=") => {}
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let (Some(plugin), Some(function)) = (positional.next(), positional.next()) else {
+        return Some(Err(USAGE.to_string()));
+    };
+    let args = match positional.next() {
+        Some(json) => match serde_json::from_str(&json) {
+            Ok(args) => args,
+            Err(e) => return Some(Err(format!("args-json isn't valid JSON: {}", e))),
+        },
+        None => Value::Null,
+    };
+    if positional.next().is_some() {
+        return Some(Err(USAGE.to_string()));
+    }
+
+    Some(Ok(RunCommand {
+        plugin,
+        function,
+        args,
+        quiet,
+    }))
+}
+
+/// Runs `command` without opening a window, returning the process exit code:
+/// the plugin's own code if it exited, 1 for any other failure, 0 otherwise.
+/// The result goes to stdout as JSON; errors go to stderr as `{ kind, message }`.
+pub fn run(command: RunCommand, mut context: tauri::Context) -> i32 {
+    let mut stdout: Box<dyn Write> = Box::new(std::io::stdout());
+    if command.quiet {
+        match quiet_stdout() {
+            Ok(real) => stdout = real,
+            Err(e) => eprintln!("⚠️ Can't quieten the host's output: {}", e),
+        }
+    }
+
+    // A headless run needs the app handle for host interfaces, but not its windows
+    context.config_mut().app.windows.clear();
+    let app = match tauri::Builder::default().build(context) {
+        Ok(app) => app,
+        Err(e) => return fail(&PluginError::InstantiationFailed(e.to_string())),
+    };
+    let handle = AppHandle::from(app.handle().clone());
+
+    let result = tauri::async_runtime::block_on(async {
+        let runtime = PluginRuntime::builder().build(&handle)?;
+        messaging::start();
+        stream::start(handle.clone());
+        runtime.load_all().await?;
+        let result = runtime.call(&command.plugin, &command.function, command.args, None).await;
+        runtime.shutdown().await;
+        result
+    });
+    epoch::stop();
+
+    match result {
+        Ok(value) => {
+            // There's nowhere better to report a closed stdout, and the call itself worked
+            let _ = writeln!(stdout, "{}", value);
+            let _ = stdout.flush();
+            0
+        }
+        Err(e) => fail(&e),
+    }
+}
+
+fn fail(error: &PluginError) -> i32 {
+    let report = serde_json::to_value(error).unwrap_or_else(|_| json!({ "kind": "Unknown" }));
+    eprintln!("{}", report);
+    match error {
+        PluginError::Exited(code) => *code,
+        _ => 1,
+    }
+}
+
+/// Points the process's stdout at stderr, so the host's `println!` chatter stays
+/// out of the result, and hands back a writer for the real stdout.
+#[cfg(unix)]
+fn quiet_stdout() -> std::io::Result<Box<dyn Write>> {
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    std::io::stdout().flush()?;
+    let stdout = std::io::stdout().as_raw_fd();
+    let stderr = std::io::stderr().as_raw_fd();
+    // SAFETY: both descriptors stay open for the life of the process, and the
+    // duplicate is owned by nothing but the `File` made from it
+    unsafe {
+        let real = libc::dup(stdout);
+        if real < 0 || libc::dup2(stderr, stdout) < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Box::new(std::fs::File::from_raw_fd(real)))
+    }
+}
+
+#[cfg(not(unix))]
+fn quiet_stdout() -> std::io::Result<Box<dyn Write>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "--quiet only redirects the host's output on Unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Option<Result<RunCommand, String>> {
+        parse(line.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn only_run_is_a_command() {
+        assert!(parse_line("pato").is_none());
+        assert!(parse_line("pato --dev").is_none());
+        assert!(parse_line("pato open run").is_none());
+    }
+
+    #[test]
+    fn runs_take_a_plugin_a_function_and_optional_args() {
+        let command = parse_line("pato run plugin-ui get-number").unwrap().unwrap();
+        assert_eq!((command.plugin.as_str(), command.function.as_str()), ("plugin-ui", "get-number"));
+        assert_eq!(command.args, Value::Null);
+        assert!(!command.quiet);
+
+        let command = parse_line(r#"pato run -q plugin-ui transform [{"name":"pato","count":2}]"#).unwrap().unwrap();
+        assert_eq!(command.args, json!([{ "name": "pato", "count": 2 }]));
+        assert!(command.quiet);
+    }
+
+    #[test]
+    fn flags_read_elsewhere_arent_positional() {
+        let command = parse_line("pato run --dev --plugins-dir /tmp/plugins plugin-ui --plugins-dir=/tmp get-number")
+            .unwrap()
+            .unwrap();
+        assert_eq!((command.plugin.as_str(), command.function.as_str()), ("plugin-ui", "get-number"));
+    }
+
+    #[test]
+    fn bad_runs_are_errors() {
+        assert_eq!(parse_line("pato run plugin-ui").unwrap().err().as_deref(), Some(USAGE));
+        assert_eq!(parse_line("pato run a b [] extra").unwrap().err().as_deref(), Some(USAGE));
+        let error = parse_line("pato run a b {oops").unwrap().err().unwrap();
+        assert!(error.starts_with("args-json isn't valid JSON"), "{}", error);
+    }
+}
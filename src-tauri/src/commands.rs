@@ -0,0 +1,549 @@
+use serde::Serialize;
+use std::time::Duration;
+use tauri::Emitter;
+
+use crate::cache::CacheStats;
+use crate::config::ConfigField;
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::introspect::ExportInfo;
+use crate::manifest::{Capability, LogThreshold, PluginManifest};
+use crate::metrics::Metrics;
+use crate::output::PluginOutput;
+use crate::permissions::PermissionReport;
+use crate::registry::{PluginInfo, ReloadSummary};
+use crate::runtime::PluginRuntime;
+use crate::ui::UiContribution;
+use crate::usage::ResourceUsage;
+use crate::validate::PluginValidation;
+use crate::{call_plugin_function, registry, PLUGINS_READY, PLUGINS_UNAVAILABLE};
+
+#[tauri::command]
+pub async fn handle_button_click(app: tauri::AppHandle) -> Result<(), String> {
+    println!("Button clicked in Rust! Calling plugin...");
+
+    // The button always targets the first plugin in id order. The registry guard is a
+    // temporary here, so it's released before the call and listing stays responsive.
+    let first_id = registry().list_ids().into_iter().next();
+    let Some(id) = first_id else {
+        let error_msg = "Plugin call failed: No plugins loaded";
+        println!("❌ {}", error_msg);
+        return app.emit("button-clicked", error_msg).map_err(|e| e.to_string());
+    };
+
+    // Call the plugin function
+    match call_plugin_function(&id, None).await {
+        Ok(result) => {
+            let message = format!("Plugin returned: {}", result);
+            println!("✅ {}", message);
+            app.emit("button-clicked", message).map_err(|e| e.to_string())?;
+        }
+        Err(e) => {
+            let error_msg = format!("Plugin call failed: {}", e);
+            println!("❌ {}", error_msg);
+            app.emit("button-clicked", error_msg).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_plugins(runtime: tauri::State<'_, PluginRuntime>) -> Vec<PluginInfo> {
+    runtime.list()
+}
+
+/// Just the plugins whose last load failed, with why.
+#[tauri::command]
+pub fn list_failed_plugins() -> Vec<PluginInfo> {
+    registry()
+        .list_info()
+        .into_iter()
+        .filter(|info| info.error.is_some())
+        .collect()
+}
+
+/// Tries loading a plugin that failed to load again, e.g. after fixing its manifest.
+#[tauri::command]
+pub async fn retry_failed(app: tauri::AppHandle, path: String) -> Result<PluginInfo, PluginError> {
+    let path = std::path::PathBuf::from(path);
+    let failed_id = {
+        let registry = registry();
+        registry.id_for_path(&path).filter(|id| registry.is_failed(id))
+    };
+    let Some(failed_id) = failed_id else {
+        return Err(PluginError::NotLoaded(format!("{:?} (no failed load to retry)", path)));
+    };
+
+    let engine = crate::shared_engine()?;
+    println!("🔁 Retrying plugin {} from {:?}", failed_id, path);
+    match crate::load_plugin(&AppHandle::from(app.clone()), engine, &path).await {
+        Ok(id) => {
+            println!("✅ Successfully loaded plugin: {}", id);
+            let _ = app.emit("plugin-loaded", &id);
+            registry()
+                .list_info()
+                .into_iter()
+                .find(|info| info.id == id)
+                .ok_or(PluginError::NotLoaded(id))
+        }
+        Err(e) => {
+            println!("❌ Retry of plugin {} failed: {}", failed_id, e);
+            // The UI shows the new error in place of the old one
+            let _ = app.emit("plugin-load-failed", &failed_id);
+            Err(e)
+        }
+    }
+}
+
+/// The order the startup load resolved from plugins' declared dependencies.
+#[tauri::command]
+pub fn plugin_load_order() -> Vec<String> {
+    registry().load_order().to_vec()
+}
+
+/// Whether the startup plugin load has finished.
+#[tauri::command]
+pub fn plugins_ready() -> bool {
+    PLUGINS_READY.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+/// Resolves once plugin `id` has loaded and run its `init`, after all of its
+/// dependencies did. Fails if it failed to load, or the startup load finished without it.
+#[tauri::command]
+pub async fn wait_for_plugin_ready(id: String) -> Result<(), PluginError> {
+    crate::ready::wait_for(&id).await
+}
+
+/// Why plugins can't run in this build, or `None` when they can.
+#[tauri::command]
+pub fn plugins_unavailable() -> Option<String> {
+    PLUGINS_UNAVAILABLE.get().cloned()
+}
+
+/// Calls `function` on the plugin with id `name`, without arguments. `get-number`
+/// takes the typed path; anything else is looked up like `invoke_export` does.
+#[tauri::command]
+pub async fn invoke_plugin(
+    name: String,
+    function: String,
+    timeout_ms: Option<u64>,
+) -> Result<serde_json::Value, PluginError> {
+    let timeout = timeout_ms.map(Duration::from_millis);
+    match function.as_str() {
+        "get-number" | "get_number" => call_plugin_function(&name, timeout).await.map(serde_json::Value::from),
+        _ => crate::dynamic::invoke(&name, &function, serde_json::Value::Null, timeout).await,
+    }
+}
+
+/// Calls the plugin's `transform` with a `{ name, count }` record.
+#[tauri::command]
+pub async fn transform(id: String, name: String, count: u32, timeout_ms: Option<u64>) -> Result<String, PluginError> {
+    let input = crate::exports::test::TransformInput { name, count };
+    crate::call_plugin_transform(&id, &input, timeout_ms.map(Duration::from_millis)).await
+}
+
+/// Calls any export by name, e.g. `test#get-number`, with JSON arguments.
+#[tauri::command]
+pub async fn invoke_export(
+    runtime: tauri::State<'_, PluginRuntime>,
+    id: String,
+    function: String,
+    args: Option<serde_json::Value>,
+    timeout_ms: Option<u64>,
+) -> Result<serde_json::Value, PluginError> {
+    let timeout = timeout_ms.map(Duration::from_millis);
+    runtime.call(&id, &function, args.unwrap_or_default(), timeout).await
+}
+
+/// Calls an export that takes no arguments, e.g. `test#get-number`, skipping
+/// argument handling entirely. Fails if the function does take arguments.
+#[tauri::command]
+pub async fn invoke_simple(
+    id: String,
+    function: String,
+    timeout_ms: Option<u64>,
+) -> Result<serde_json::Value, PluginError> {
+    crate::dynamic::invoke_simple(&id, &function, timeout_ms.map(Duration::from_millis)).await
+}
+
+/// Calls a `list<u8> -> list<u8>` export with raw bytes, so nothing is JSON- or
+/// base64-encoded on the way. The plugin and function go in the `Pato-Plugin` and
+/// `Pato-Function` headers, e.g. from JS:
+///
+/// ```js
+/// invoke('invoke_bytes', bytes, { headers: { 'Pato-Plugin': id, 'Pato-Function': 'pato:plugin-ui/bytes@0.1.0#hash' } })
+/// ```
+///
+/// A JSON array of numbers works as the body too. The result comes back as an `ArrayBuffer`.
+#[tauri::command]
+pub async fn invoke_bytes(request: tauri::ipc::Request<'_>) -> Result<tauri::ipc::Response, PluginError> {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| PluginError::InvalidArguments(format!("missing the {} header", name)))
+    };
+    let (id, function) = (header("Pato-Plugin")?, header("Pato-Function")?);
+    let input = match request.body() {
+        tauri::ipc::InvokeBody::Raw(bytes) => bytes.clone(),
+        tauri::ipc::InvokeBody::Json(value) => serde_json::from_value(value.clone())
+            .map_err(|e| PluginError::InvalidArguments(format!("expected bytes: {}", e)))?,
+    };
+
+    let output = crate::dynamic::invoke_bytes(&id, &function, input, None).await?;
+    Ok(tauri::ipc::Response::new(output))
+}
+
+/// Calls any export like `invoke_export`, with the default timeout. The call
+/// can be stopped early with `cancel_plugin_call`.
+#[tauri::command]
+pub async fn invoke_async(
+    id: String,
+    function: String,
+    args: Option<serde_json::Value>,
+) -> Result<serde_json::Value, PluginError> {
+    crate::dynamic::invoke(&id, &function, args.unwrap_or_default(), None).await
+}
+
+/// Stops plugin `id`'s running call within one epoch tick; it fails with
+/// `Cancelled`. Calls to other plugins carry on as normal.
+#[tauri::command]
+pub fn cancel_plugin_call(id: String) -> Result<(), PluginError> {
+    let registry = registry();
+    let loaded = registry.get(&id).ok_or_else(|| PluginError::NotLoaded(id.clone()))?;
+    loaded.cancel.store(true, std::sync::atomic::Ordering::SeqCst);
+    println!("🛑 Cancelling the running call to plugin {}", id);
+    Ok(())
+}
+
+/// One plugin's share of a `sum_all_numbers` total.
+#[derive(Debug, Clone, Serialize)]
+pub struct Contribution {
+    pub id: String,
+    pub value: Option<u32>,
+    pub error: Option<PluginError>,
+}
+
+/// The result of `sum_all_numbers`, also emitted as `numbers-summed`.
+#[derive(Debug, Clone, Serialize)]
+pub struct NumberTotal {
+    pub total: u64,
+    pub contributions: Vec<Contribution>,
+}
+
+/// Calls `get-number` on every loaded plugin, in priority order, and adds up the
+/// results. A plugin that fails is reported in the breakdown but doesn't stop the others.
+#[tauri::command]
+pub async fn sum_all_numbers(app: tauri::AppHandle) -> Result<NumberTotal, String> {
+    let ids = registry().ids_by_priority();
+
+    let mut total = 0u64;
+    let mut contributions = Vec::with_capacity(ids.len());
+    for id in ids {
+        match call_plugin_function(&id, None).await {
+            Ok(value) => {
+                total += u64::from(value);
+                contributions.push(Contribution { id, value: Some(value), error: None });
+            }
+            Err(e) => {
+                println!("❌ {} didn't contribute: {}", id, e);
+                contributions.push(Contribution { id, value: None, error: Some(e) });
+            }
+        }
+    }
+
+    let result = NumberTotal { total, contributions };
+    println!("🧮 Sum of all plugins: {}", result.total);
+    app.emit("numbers-summed", &result).map_err(|e| e.to_string())?;
+    Ok(result)
+}
+
+/// Reloads every plugin from disk, picking up added and removed files too.
+#[tauri::command]
+pub async fn reload_all_plugins(app: tauri::AppHandle) -> Result<ReloadSummary, PluginError> {
+    let summary = crate::reload_all_plugins(&AppHandle::from(app.clone())).await?;
+    println!(
+        "🔄 Reloaded {}, loaded {}, unloaded {}, {} failed",
+        summary.reloaded.len(),
+        summary.loaded.len(),
+        summary.unloaded.len(),
+        summary.failed.len()
+    );
+    let _ = app.emit("plugins-reloaded", &summary);
+    Ok(summary)
+}
+
+/// The JSON Schema plugin manifests are checked against.
+#[tauri::command]
+pub fn manifest_schema() -> String {
+    PluginManifest::schema_json()
+}
+
+/// Passes `input` through each plugin's `process` in priority order.
+#[tauri::command]
+pub async fn run_pipeline(input: String) -> Result<String, PluginError> {
+    let output = crate::pipeline::run(input).await;
+    match &output {
+        Ok(output) => println!("🧪 Pipeline produced: {}", output),
+        Err(e) => println!("❌ Pipeline failed: {}", e),
+    }
+    output
+}
+
+/// Keeps the `items` plugin `id`'s `filter` says match `pattern`.
+#[tauri::command]
+pub async fn filter_items(id: String, items: Vec<String>, pattern: String) -> Result<Vec<String>, PluginError> {
+    let kept = crate::filter::run(&id, items, pattern).await;
+    match &kept {
+        Ok(kept) => println!("🧪 {} kept {} item(s)", id, kept.len()),
+        Err(e) => println!("❌ {} failed to filter: {}", id, e),
+    }
+    kept
+}
+
+#[tauri::command]
+pub async fn reload_plugin(app: tauri::AppHandle, id: String) -> Result<(), PluginError> {
+    match crate::reload_plugin(&AppHandle::from(app.clone()), &id).await {
+        Ok(()) => {
+            println!("✅ Reloaded plugin {}", id);
+            let _ = app.emit("plugin-reloaded", id);
+            Ok(())
+        }
+        Err(e) => {
+            println!("❌ Failed to reload plugin {}: {}", id, e);
+            Err(e)
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn unload_plugin(
+    app: tauri::AppHandle,
+    runtime: tauri::State<'_, PluginRuntime>,
+    id: String,
+) -> Result<bool, String> {
+    let removed = runtime.unload(&id).await;
+    if removed {
+        app.emit("plugin-unloaded", id).map_err(|e| e.to_string())?;
+    }
+
+    Ok(removed)
+}
+
+/// Gets plugin `id` ready for its next call, re-instantiating it now if it's dirty.
+#[tauri::command]
+pub async fn warmup_plugin(id: String) -> Result<(), PluginError> {
+    crate::warmup::warmup(&id).await.inspect_err(|e| {
+        println!("❌ Failed to warm up plugin {}: {}", id, e);
+    })
+}
+
+/// Checks that the `.wasm` at `path` would load, without loading it.
+#[tauri::command]
+pub async fn validate_plugin(path: String) -> Result<PluginValidation, PluginError> {
+    crate::validate::validate_plugin(path.into()).await
+}
+
+/// Downloads, validates, and loads the plugin at `url`.
+#[tauri::command]
+pub async fn install_plugin_from_url(app: tauri::AppHandle, url: String) -> Result<PluginInfo, PluginError> {
+    crate::install::install_from_url(&AppHandle::from(app.clone()), &url).await.inspect_err(|e| {
+        println!("❌ Failed to install plugin from {}: {}", url, e);
+    })
+}
+
+/// Deletes the compiled component cache, returning the bytes freed. Loaded
+/// plugins keep running; only their next load recompiles.
+#[tauri::command]
+pub fn clear_component_cache() -> u64 {
+    let Some(plugins_dir) = crate::PLUGINS_DIR.get() else {
+        return 0;
+    };
+    let freed = crate::cache::clear(plugins_dir);
+    println!("🧹 Cleared component cache, freed {} bytes", freed);
+    freed
+}
+
+/// How many compiled artifacts are cached and how much space they take.
+#[tauri::command]
+pub fn cache_stats() -> CacheStats {
+    crate::PLUGINS_DIR
+        .get()
+        .map(|plugins_dir| crate::cache::stats(plugins_dir))
+        .unwrap_or_default()
+}
+
+/// Lists the functions plugin `id` exports, with their parameter and result types.
+#[tauri::command]
+pub fn list_exports(id: String) -> Result<Vec<ExportInfo>, PluginError> {
+    let engine = crate::shared_engine()?;
+    let registry = registry();
+    let loaded = registry.get(&id).ok_or(PluginError::NotLoaded(id))?;
+    Ok(crate::introspect::describe_exports(engine, &loaded.component))
+}
+
+/// A WIT-like description of what plugin `id` imports and exports, for its API reference.
+#[tauri::command]
+pub fn plugin_interface(id: String) -> Result<String, PluginError> {
+    let engine = crate::shared_engine()?;
+    let registry = registry();
+    let loaded = registry.get(&id).ok_or(PluginError::NotLoaded(id))?;
+    Ok(crate::introspect::describe_interface(engine, &loaded.component))
+}
+
+/// Turns plugin `id` on or off without unloading it. The choice is remembered
+/// across restarts and reloads.
+#[tauri::command]
+pub fn set_plugin_enabled(app: tauri::AppHandle, id: String, enabled: bool) -> Result<(), PluginError> {
+    {
+        let mut registry = registry();
+        let loaded = registry.get_mut(&id).ok_or_else(|| PluginError::NotLoaded(id.clone()))?;
+        loaded.enabled = enabled;
+    }
+    crate::disabled::save(&AppHandle::from(app.clone()), &id, enabled).map_err(PluginError::Io)?;
+
+    let state = if enabled { "enabled" } else { "disabled" };
+    println!("⏯️ Plugin {} {}", id, state);
+    let _ = app.emit("plugin-enabled-changed", (&id, enabled));
+    Ok(())
+}
+
+/// Plugin `id`'s stored data, as a portable dump for backing up.
+#[tauri::command]
+pub fn export_plugin_data(id: String) -> Result<Vec<u8>, PluginError> {
+    let data_dir = {
+        let registry = registry();
+        let loaded = registry.get(&id).ok_or_else(|| PluginError::NotLoaded(id.clone()))?;
+        loaded.data_dir.clone()
+    };
+    crate::database::export(&id, &data_dir)
+}
+
+/// Replaces plugin `id`'s stored data with an `export_plugin_data` dump. Dumps
+/// from other plugins are refused unless `force` is set.
+#[tauri::command]
+pub fn import_plugin_data(id: String, data: Vec<u8>, force: Option<bool>) -> Result<usize, PluginError> {
+    let data_dir = {
+        let registry = registry();
+        let loaded = registry.get(&id).ok_or_else(|| PluginError::NotLoaded(id.clone()))?;
+        loaded.data_dir.clone()
+    };
+    let imported = crate::database::import(&id, &data_dir, &data, force.unwrap_or(false))?;
+    println!("📥 Imported {} key(s) into plugin {}", imported, id);
+    Ok(imported)
+}
+
+/// Menu items and panels the enabled plugins want shown, in priority order.
+#[tauri::command]
+pub fn list_ui_contributions() -> Vec<UiContribution> {
+    registry().ui_contributions()
+}
+
+/// The config keys plugin `id` accepts and their types, for rendering a settings
+/// form; `None` when the plugin doesn't declare any.
+#[tauri::command]
+pub fn plugin_config_schema(id: String) -> Result<Option<Vec<ConfigField>>, PluginError> {
+    let registry = registry();
+    let loaded = registry.get(&id).ok_or(PluginError::NotLoaded(id))?;
+    Ok(loaded.config_schema.clone())
+}
+
+/// Changes `key` in plugin `id`'s config without reloading it; see `config::update`.
+#[tauri::command]
+pub async fn update_plugin_config(
+    app: tauri::AppHandle,
+    id: String,
+    key: String,
+    value: String,
+) -> Result<(), PluginError> {
+    crate::config::update(&AppHandle::from(app), &id, &key, &value).await
+}
+
+/// Drops plugin `id`'s `log` messages below `level` from now on, overriding the
+/// manifest's `log-level` until the plugin is next loaded. Waits for a call in
+/// progress to finish first.
+#[tauri::command]
+pub async fn set_plugin_log_level(
+    app: tauri::AppHandle,
+    id: String,
+    level: LogThreshold,
+) -> Result<(), PluginError> {
+    let (instance, snapshot) = {
+        let registry = registry();
+        let loaded = registry.get(&id).ok_or_else(|| PluginError::NotLoaded(id.clone()))?;
+        (loaded.instance.clone(), loaded.snapshot.clone())
+    };
+    snapshot.update(|host| host.log_level = level);
+    instance.lock().await.store.data_mut().log_level = level;
+
+    println!("🔈 Plugin {} log level = {:?}", id, level);
+    let _ = app.emit("plugin-log-level-changed", (&id, level));
+    Ok(())
+}
+
+/// The capabilities plugin `id` requests, and what the user decided about each.
+#[tauri::command]
+pub fn plugin_permissions(app: tauri::AppHandle, id: String) -> Result<PermissionReport, PluginError> {
+    let registry = registry();
+    let loaded = registry.get(&id).ok_or_else(|| PluginError::NotLoaded(id.clone()))?;
+    Ok(crate::permissions::report(&AppHandle::from(app.clone()), &id, loaded.manifest.as_ref()))
+}
+
+/// Grants or denies `capability` to plugin `id`. Takes effect when the plugin is next reloaded.
+#[tauri::command]
+pub fn set_permission(
+    app: tauri::AppHandle,
+    id: String,
+    capability: Capability,
+    granted: bool,
+) -> Result<(), PluginError> {
+    crate::permissions::save(&AppHandle::from(app.clone()), &id, capability, granted).map_err(PluginError::Io)?;
+
+    let decision = if granted { "granted" } else { "denied" };
+    println!("🔐 {:?} {} for plugin {}; reload it to apply", capability, decision, id);
+    let _ = app.emit("plugin-permission-changed", (&id, capability, granted));
+    Ok(())
+}
+
+/// Call counts, timings, and fuel use for plugin `id` since it was last loaded.
+#[tauri::command]
+pub fn plugin_metrics(id: String) -> Result<Metrics, PluginError> {
+    let registry = registry();
+    let loaded = registry.get(&id).ok_or_else(|| PluginError::NotLoaded(id.clone()))?;
+    Ok(Metrics {
+        functions: loaded.metrics.clone(),
+        throttled: loaded.throttled.clone(),
+        id,
+    })
+}
+
+/// Plugin `id`'s memory, table, and handle usage, read from its store without
+/// running any plugin code. Waits for a call in progress to finish first.
+#[tauri::command]
+pub async fn plugin_resource_usage(id: String) -> Result<ResourceUsage, PluginError> {
+    let instance = {
+        let registry = registry();
+        let loaded = registry.get(&id).ok_or_else(|| PluginError::NotLoaded(id.clone()))?;
+        loaded.instance.clone()
+    };
+    let guard = instance.lock().await;
+    let host = guard.store.data();
+    Ok(ResourceUsage {
+        memory_bytes: host.limits.memory_bytes,
+        memory_limit_bytes: crate::memory_limit_bytes(),
+        table_elements: host.limits.table_elements,
+        open_handles: host.open_handles,
+        id,
+    })
+}
+
+/// The most recent lines plugin `id` wrote to stdout and stderr.
+#[tauri::command]
+pub fn plugin_output(id: String) -> Result<PluginOutput, PluginError> {
+    let registry = registry();
+    let loaded = registry.get(&id).ok_or(PluginError::NotLoaded(id))?;
+    Ok(loaded.output.snapshot())
+}
@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use wasmtime::component::Val;
+
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::manifest::PluginManifest;
+use crate::registry::PluginInstance;
+use crate::{arm_call_limits, dynamic, registry};
+
+/// Optional export from the `settings` interface.
+const CONFIG_SCHEMA_EXPORT: &str = "pato:plugin-ui/settings@0.1.0#config-schema";
+
+/// The user's overrides file in the app config dir, with one table per plugin id:
+///
+/// ```toml
+/// [plugin-ui]
+/// greeting = "Hello from the user"
+/// ```
+const OVERRIDES_FILE: &str = "plugin-config.toml";
+
+/// Builds the config a plugin sees through `get-config`: its manifest's
+/// `[config]` table, with the user's overrides for `id` layered on top.
+pub fn load(app: &AppHandle, id: &str, manifest: Option<&PluginManifest>) -> HashMap<String, String> {
+    let mut config = manifest.map(|m| m.config.clone()).unwrap_or_default();
+
+    // A broken overrides file shouldn't stop the plugin from loading
+    match read_overrides(app) {
+        Ok(mut overrides) => {
+            if let Some(values) = overrides.remove(id) {
+                config.extend(values);
+            }
+        }
+        Err(e) => println!("⚠️ Ignoring plugin config overrides: {}", e),
+    }
+
+    config
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigType {
+    Text,
+    Number,
+    Boolean,
+}
+
+/// A config key a plugin accepts, as returned by its `config-schema`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigField {
+    pub key: String,
+    pub kind: ConfigType,
+    pub description: String,
+}
+
+/// Asks a freshly initialised plugin which config keys it accepts. `None` for
+/// plugins without the `settings` export, whose config isn't checked.
+pub async fn collect_schema(
+    plugin: &mut PluginInstance,
+    fuel_budget: u64,
+) -> Result<Option<Vec<ConfigField>>, PluginError> {
+    let PluginInstance { store, instance } = plugin;
+
+    let func = match dynamic::find_func(store, instance, CONFIG_SCHEMA_EXPORT) {
+        Ok(func) => func,
+        Err(PluginError::NoSuchFunction(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut results = vec![Val::Bool(false)];
+    arm_call_limits(store, fuel_budget, CONFIG_SCHEMA_EXPORT, None)?;
+    func.call_async(&mut *store, &[], &mut results)
+        .await
+        .map_err(PluginError::trap)?;
+    func.post_return_async(&mut *store)
+        .await
+        .map_err(PluginError::trap)?;
+
+    serde_json::from_value(dynamic::val_to_json(&results[0]))
+        .map(Some)
+        .map_err(|e| PluginError::LinkError(format!("unexpected `config-schema` result: {}", e)))
+}
+
+/// Checks `value` for `key` against a plugin's schema: the key must be declared,
+/// and the value must parse as its type. Numbers are anything `f64` parses, and
+/// booleans are `true` or `false`.
+pub fn check(schema: &[ConfigField], key: &str, value: &str) -> Result<(), PluginError> {
+    let field = schema
+        .iter()
+        .find(|field| field.key == key)
+        .ok_or_else(|| PluginError::InvalidConfig(format!("unknown key {}", key)))?;
+    let fits = match field.kind {
+        ConfigType::Text => true,
+        ConfigType::Number => value.trim().parse::<f64>().is_ok(),
+        ConfigType::Boolean => matches!(value, "true" | "false"),
+    };
+    if !fits {
+        return Err(PluginError::InvalidConfig(format!(
+            "{} must be {:?}, got {:?}",
+            key, field.kind, value
+        )));
+    }
+    Ok(())
+}
+
+/// Changes `key` in plugin `id`'s config. The running instance sees the new value
+/// on its next `get-config`, and it's saved as a user override for later loads.
+/// Plugins that declare a config schema have the value checked against it first;
+/// those that don't accept any key, as their manifest's `[config]` table does.
+/// Waits for a call in progress to finish first.
+pub async fn update(app: &AppHandle, id: &str, key: &str, value: &str) -> Result<(), PluginError> {
+    let (instance, snapshot) = {
+        let registry = registry();
+        let loaded = registry.get(id).ok_or_else(|| PluginError::NotLoaded(id.to_string()))?;
+        if let Some(schema) = &loaded.config_schema {
+            check(schema, key, value)?;
+        }
+        (loaded.instance.clone(), loaded.snapshot.clone())
+    };
+    save_override(app, id, key, value).map_err(PluginError::Io)?;
+    snapshot.update(|host| {
+        host.config.insert(key.to_string(), value.to_string());
+    });
+    instance
+        .lock()
+        .await
+        .store
+        .data_mut()
+        .config
+        .insert(key.to_string(), value.to_string());
+
+    println!("⚙️ Plugin {} config {} = {}", id, key, value);
+    let _ = app.emit("plugin-config-changed", (id, key, value));
+    Ok(())
+}
+
+/// Saves the user's override of `key` for plugin `id`, so it outlasts the running instance.
+pub fn save_override(app: &AppHandle, id: &str, key: &str, value: &str) -> Result<(), String> {
+    let path = overrides_path(app).ok_or("no app config directory")?;
+    // Don't overwrite a file we couldn't parse; the user would lose what's in it
+    let mut overrides = read_overrides(app)?;
+    overrides
+        .entry(id.to_string())
+        .or_default()
+        .insert(key.to_string(), value.to_string());
+
+    let text = toml::to_string(&overrides).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{:?}: {}", dir, e))?;
+    }
+    std::fs::write(&path, text).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+fn overrides_path(app: &AppHandle) -> Option<PathBuf> {
+    app.app_config_dir().map(|dir| dir.join(OVERRIDES_FILE))
+}
+
+fn read_overrides(app: &AppHandle) -> Result<HashMap<String, HashMap<String, String>>, String> {
+    let Some(path) = overrides_path(app).filter(|path| path.exists()) else {
+        return Ok(HashMap::new());
+    };
+
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{:?}: {}", path, e))?;
+    toml::from_str(&text).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pato::plugin_ui::config::Host as _;
+    use crate::testing::{with_host, TestApp, ANSWER};
+
+    #[tokio::test]
+    async fn updates_reach_the_running_instance_and_later_loads() {
+        let app = TestApp::new();
+        let id = app.load("config-update", ANSWER).await.unwrap();
+        let before = registry().get(&id).unwrap().instance.clone();
+
+        update(&app.handle, &id, "greeting", "hi").await.unwrap();
+
+        // Same instance, no reload, new value
+        assert!(std::sync::Arc::ptr_eq(&before, &registry().get(&id).unwrap().instance));
+        let seen = with_host(&id, |host| host.get_config("greeting".to_string())).await;
+        assert_eq!(seen.as_deref(), Some("hi"));
+        assert_eq!(load(&app.handle, &id, None).get("greeting").map(String::as_str), Some("hi"));
+        assert!(std::fs::read_to_string(app.config_dir().join(OVERRIDES_FILE)).unwrap().contains("greeting"));
+        assert_eq!(app.emitted("plugin-config-changed").len(), 1);
+    }
+
+    fn field(key: &str, kind: ConfigType) -> ConfigField {
+        ConfigField {
+            key: key.to_string(),
+            kind,
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn values_are_checked_against_their_type() {
+        let schema = [field("name", ConfigType::Text), field("size", ConfigType::Number), field("on", ConfigType::Boolean)];
+        assert!(check(&schema, "name", "anything").is_ok());
+        assert!(check(&schema, "size", " 2.5 ").is_ok());
+        assert!(check(&schema, "on", "false").is_ok());
+        assert!(matches!(check(&schema, "size", "big"), Err(PluginError::InvalidConfig(_))));
+        assert!(matches!(check(&schema, "on", "yes"), Err(PluginError::InvalidConfig(_))));
+        assert!(matches!(check(&schema, "colour", "red"), Err(PluginError::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn updates_that_dont_fit_the_schema_change_nothing() {
+        let app = TestApp::new();
+        let id = app.load("config-checked", ANSWER).await.unwrap();
+        registry().get_mut(&id).unwrap().config_schema = Some(vec![field("size", ConfigType::Number)]);
+
+        let mismatch = update(&app.handle, &id, "size", "big").await;
+        assert!(matches!(&mismatch, Err(PluginError::InvalidConfig(e)) if e.starts_with("size must be")), "{:?}", mismatch);
+        let unknown = update(&app.handle, &id, "colour", "red").await;
+        assert!(matches!(&unknown, Err(PluginError::InvalidConfig(e)) if e == "unknown key colour"), "{:?}", unknown);
+
+        assert_eq!(with_host(&id, |host| host.get_config("size".to_string())).await, None);
+        assert!(load(&app.handle, &id, None).is_empty());
+        assert!(app.emitted("plugin-config-changed").is_empty());
+
+        update(&app.handle, &id, "size", "3").await.unwrap();
+        assert_eq!(with_host(&id, |host| host.get_config("size".to_string())).await.as_deref(), Some("3"));
+    }
+
+    #[tokio::test]
+    async fn plugins_without_a_schema_take_any_key() {
+        let app = TestApp::new();
+        let id = app.load("config-unchecked", ANSWER).await.unwrap();
+        assert!(registry().get(&id).unwrap().config_schema.is_none());
+        update(&app.handle, &id, "anything", "goes").await.unwrap();
+    }
+}
@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
+
+use crate::error::PluginError;
+
+/// Where a plugin's database lives inside its data directory.
+const DB_DIR: &str = "pato-db";
+
+// Default cap on a single stored value, overridable with PATO_DB_MAX_VALUE_BYTES
+const DEFAULT_MAX_VALUE_BYTES: usize = 1024 * 1024;
+
+// sled locks its directory, so a database is opened once and shared by every
+// instance of the plugin, e.g. the old and new instance during a reload
+static OPEN: LazyLock<Mutex<HashMap<PathBuf, sled::Db>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn open_databases() -> MutexGuard<'static, HashMap<PathBuf, sled::Db>> {
+    OPEN.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Opens (or reuses) the key-value store in a plugin's data directory.
+pub fn open(data_dir: &Path) -> Result<sled::Db, PluginError> {
+    let path = data_dir.join(DB_DIR);
+    let mut open = open_databases();
+    if let Some(db) = open.get(&path) {
+        return Ok(db.clone());
+    }
+
+    let db = sled::open(&path).map_err(|e| PluginError::Io(format!("failed to open {:?}: {}", path, e)))?;
+    open.insert(path, db.clone());
+    Ok(db)
+}
+
+/// Forgets the database in `data_dir` so its files can be removed. It closes
+/// once the last store holding it is dropped.
+pub fn close(data_dir: &Path) {
+    if let Some(db) = open_databases().remove(&data_dir.join(DB_DIR)) {
+        if let Err(e) = db.flush() {
+            println!("⚠️ Failed to flush database in {:?}: {}", data_dir, e);
+        }
+    }
+}
+
+/// Writes every open database's pending changes to disk, e.g. before the app exits.
+pub fn flush_all() {
+    for (path, db) in open_databases().iter() {
+        if let Err(e) = db.flush() {
+            println!("⚠️ Failed to flush database in {:?}: {}", path, e);
+        }
+    }
+}
+
+/// A plugin's whole database, as written by `export` and read by `import`.
+/// Keys are sorted, so two exports of the same data are byte-for-byte equal.
+#[derive(Serialize, Deserialize)]
+struct Dump {
+    /// The plugin the data came from
+    plugin: String,
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+/// Dumps plugin `id`'s database as JSON, for backing up.
+pub fn export(id: &str, data_dir: &Path) -> Result<Vec<u8>, PluginError> {
+    let db = open(data_dir)?;
+    let entries = db
+        .iter()
+        .map(|entry| entry.map_err(|e| PluginError::Io(e.to_string())))
+        // Keys are only ever written from strings
+        .filter_map(|entry| match entry {
+            Ok((key, value)) => String::from_utf8(key.to_vec()).ok().map(|key| Ok((key, value.to_vec()))),
+            Err(e) => Some(Err(e)),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let dump = Dump {
+        plugin: id.to_string(),
+        entries,
+    };
+    serde_json::to_vec(&dump).map_err(|e| PluginError::Io(e.to_string()))
+}
+
+/// Replaces plugin `id`'s database with the contents of an `export`. A dump from
+/// a different plugin is refused unless `force` is set.
+pub fn import(id: &str, data_dir: &Path, bytes: &[u8], force: bool) -> Result<usize, PluginError> {
+    let dump: Dump = serde_json::from_slice(bytes)
+        .map_err(|e| PluginError::InvalidArguments(format!("not a plugin data export: {}", e)))?;
+    if dump.plugin != id && !force {
+        return Err(PluginError::InvalidArguments(format!(
+            "this export is from plugin {}, not {}",
+            dump.plugin, id
+        )));
+    }
+
+    let db = open(data_dir)?;
+    let io = |e: sled::Error| PluginError::Io(e.to_string());
+    let mut batch = sled::Batch::default();
+    for key in db.iter().keys() {
+        batch.remove(key.map_err(io)?);
+    }
+    for (key, value) in &dump.entries {
+        batch.insert(key.as_bytes(), value.as_slice());
+    }
+    // One batch, so a failed import leaves the old data in place
+    db.apply_batch(batch).map_err(io)?;
+    db.flush().map_err(io)?;
+    Ok(dump.entries.len())
+}
+
+pub fn max_value_bytes() -> usize {
+    std::env::var("PATO_DB_MAX_VALUE_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_VALUE_BYTES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exports_restore_into_a_fresh_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let (from, to) = (dir.path().join("from"), dir.path().join("to"));
+        let db = open(&from).unwrap();
+        db.insert("greeting", "hello").unwrap();
+        db.insert("count", &[1u8, 2, 3][..]).unwrap();
+        let dump = export("backed-up", &from).unwrap();
+
+        // Whatever was there before is replaced
+        open(&to).unwrap().insert("stale", "gone").unwrap();
+        assert_eq!(import("backed-up", &to, &dump, false).unwrap(), 2);
+        let restored = open(&to).unwrap();
+        assert_eq!(restored.get("greeting").unwrap().as_deref(), Some(&b"hello"[..]));
+        assert_eq!(restored.get("count").unwrap().as_deref(), Some(&[1, 2, 3][..]));
+        assert!(restored.get("stale").unwrap().is_none());
+
+        // Exports are stable, so the same data exports the same bytes
+        assert_eq!(export("backed-up", &to).unwrap(), dump);
+        close(&from);
+        close(&to);
+    }
+
+    #[test]
+    fn exports_from_another_plugin_need_force() {
+        let dir = tempfile::tempdir().unwrap();
+        open(dir.path()).unwrap().insert("key", "value").unwrap();
+        let dump = export("someone-else", dir.path()).unwrap();
+
+        assert!(matches!(import("me", dir.path(), &dump, false), Err(PluginError::InvalidArguments(_))));
+        assert_eq!(import("me", dir.path(), &dump, true).unwrap(), 1);
+        assert!(import("me", dir.path(), b"not an export", true).is_err());
+        close(dir.path());
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+use crate::error::PluginError;
+use crate::manifest::PluginManifest;
+use crate::registry;
+
+/// A plugin that has been read from disk but not yet instantiated.
+pub struct Candidate {
+    pub id: String,
+    pub path: PathBuf,
+    pub manifest: Option<PluginManifest>,
+    pub bytes: Vec<u8>,
+}
+
+impl Candidate {
+    fn dependencies(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.manifest.iter().flat_map(|m| m.dependencies.iter())
+    }
+
+    /// Where the candidate goes among plugins that could load at the same time:
+    /// by manifest priority, then id, then file name, so every machine agrees.
+    fn order_key(&self) -> (i32, &str, Option<&OsStr>) {
+        let priority = self.manifest.as_ref().map(|m| m.priority).unwrap_or(0);
+        (priority, self.id.as_str(), self.path.file_name())
+    }
+
+    fn version(&self) -> Option<&str> {
+        self.manifest.as_ref().map(|m| m.version.as_str())
+    }
+}
+
+/// The outcome of ordering a set of candidates by their dependencies.
+pub struct Resolution {
+    /// Each level only depends on earlier levels, so a level's plugins can load concurrently
+    pub levels: Vec<Vec<Candidate>>,
+    /// Candidates that can't be loaded at all: missing or incompatible deps, or cycles
+    pub failed: Vec<(Candidate, PluginError)>,
+}
+
+/// Sorts candidates so every plugin comes after the plugins it depends on, and
+/// plugins in the same level are in `order_key` order.
+pub fn resolve(mut candidates: Vec<Candidate>) -> Resolution {
+    candidates.sort_by(|a, b| a.order_key().cmp(&b.order_key()));
+
+    let versions: HashMap<String, Option<String>> = candidates
+        .iter()
+        .map(|c| (c.id.clone(), c.version().map(str::to_string)))
+        .collect();
+
+    let mut failed = Vec::new();
+    let mut failed_ids = HashSet::new();
+    let mut remaining = Vec::new();
+
+    // Dependencies that aren't there at all, or are the wrong version, fail straight away
+    for candidate in candidates {
+        let problem = candidate.dependencies().find_map(|(dep, range)| match versions.get(dep) {
+            None => Some(format!("missing dependency {}", dep)),
+            Some(version) => check_version(dep, range, version.as_deref()).err(),
+        });
+        match problem {
+            Some(message) => {
+                failed_ids.insert(candidate.id.clone());
+                failed.push((candidate, PluginError::Dependency(message)));
+            }
+            None => remaining.push(candidate),
+        }
+    }
+
+    // Peel off a level at a time: everything whose dependencies are all placed already
+    let mut placed = HashSet::new();
+    let mut levels = Vec::new();
+    while !remaining.is_empty() {
+        // Failures cascade, so keep going until nothing else depends on a failed plugin
+        loop {
+            let (blocked, rest): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|c: &Candidate| {
+                c.dependencies().any(|(dep, _)| failed_ids.contains(dep))
+            });
+            remaining = rest;
+            if blocked.is_empty() {
+                break;
+            }
+            for candidate in blocked {
+                let dep = candidate
+                    .dependencies()
+                    .map(|(dep, _)| dep.clone())
+                    .find(|dep| failed_ids.contains(dep))
+                    .unwrap_or_default();
+                failed_ids.insert(candidate.id.clone());
+                failed.push((candidate, PluginError::Dependency(format!("dependency {} can't be loaded", dep))));
+            }
+        }
+
+        let (ready, waiting): (Vec<_>, Vec<_>) = remaining
+            .into_iter()
+            .partition(|c: &Candidate| c.dependencies().all(|(dep, _)| placed.contains(dep)));
+
+        if ready.is_empty() {
+            // Nothing can make progress, so whatever is left is in, or stuck behind, a cycle
+            if !waiting.is_empty() {
+                let mut ids: Vec<String> = waiting.iter().map(|c| c.id.clone()).collect();
+                ids.sort();
+                let message = format!("dependency cycle among {}", ids.join(", "));
+                for candidate in waiting {
+                    failed.push((candidate, PluginError::Dependency(message.clone())));
+                }
+            }
+            break;
+        }
+
+        placed.extend(ready.iter().map(|c| c.id.clone()));
+        levels.push(ready);
+        remaining = waiting;
+    }
+
+    Resolution { levels, failed }
+}
+
+/// Checks that every dependency of `manifest` is loaded right now at a suitable
+/// version, for plugins loaded one at a time (e.g. by the watcher).
+pub fn check_loaded(manifest: Option<&PluginManifest>) -> Result<(), PluginError> {
+    let Some(manifest) = manifest else {
+        return Ok(());
+    };
+
+    let registry = registry();
+    for (dep, range) in &manifest.dependencies {
+        let loaded = registry
+            .get(dep)
+            .ok_or_else(|| PluginError::Dependency(format!("dependency {} isn't loaded", dep)))?;
+        let version = loaded.manifest.as_ref().map(|m| m.version.as_str());
+        check_version(dep, range, version).map_err(PluginError::Dependency)?;
+    }
+    Ok(())
+}
+
+fn check_version(dep: &str, range: &str, version: Option<&str>) -> Result<(), String> {
+    let requirement = semver::VersionReq::parse(range)
+        .map_err(|e| format!("invalid version range {:?} for {}: {}", range, dep, e))?;
+    let version = version
+        .and_then(|version| semver::Version::parse(version).ok())
+        .ok_or_else(|| format!("dependency {} has no semver version to check against {}", dep, range))?;
+
+    if requirement.matches(&version) {
+        Ok(())
+    } else {
+        Err(format!("dependency {} is {}, but {} is required", dep, version, range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn candidate(id: &str, version: &str, dependencies: &[(&str, &str)]) -> Candidate {
+        let mut text = format!("id = \"{}\"\nname = \"{}\"\nversion = \"{}\"\n[dependencies]\n", id, id, version);
+        for (dep, range) in dependencies {
+            text.push_str(&format!("{} = \"{}\"\n", dep, range));
+        }
+        Candidate {
+            id: id.to_string(),
+            path: PathBuf::from(format!("{}.wasm", id)),
+            manifest: Some(PluginManifest::parse(&text, Path::new("test.toml")).unwrap()),
+            bytes: Vec::new(),
+        }
+    }
+
+    fn levels(resolution: &Resolution) -> Vec<Vec<&str>> {
+        resolution
+            .levels
+            .iter()
+            .map(|level| level.iter().map(|c| c.id.as_str()).collect())
+            .collect()
+    }
+
+    fn failures(resolution: &Resolution) -> Vec<(&str, String)> {
+        let mut failed: Vec<_> = resolution
+            .failed
+            .iter()
+            .map(|(c, e)| (c.id.as_str(), e.to_string()))
+            .collect();
+        failed.sort();
+        failed
+    }
+
+    #[test]
+    fn dependencies_load_a_level_before_their_dependents() {
+        let resolution = resolve(vec![
+            candidate("app", "1.0.0", &[("ui", "^1"), ("core", "^2")]),
+            candidate("ui", "1.2.0", &[("core", ">=2.0")]),
+            candidate("core", "2.1.0", &[]),
+            candidate("extra", "0.1.0", &[]),
+        ]);
+        assert_eq!(levels(&resolution), [vec!["core", "extra"], vec!["ui"], vec!["app"]]);
+        assert!(resolution.failed.is_empty());
+    }
+
+    #[test]
+    fn missing_and_mismatched_dependencies_fail() {
+        let resolution = resolve(vec![
+            candidate("needs-ghost", "1.0.0", &[("ghost", "*")]),
+            candidate("needs-new-core", "1.0.0", &[("core", "^3")]),
+            candidate("core", "2.1.0", &[]),
+        ]);
+        assert_eq!(levels(&resolution), [vec!["core"]]);
+        let failed = failures(&resolution);
+        assert_eq!(failed[0].0, "needs-ghost");
+        assert!(failed[0].1.contains("missing dependency ghost"), "{}", failed[0].1);
+        assert_eq!(failed[1].0, "needs-new-core");
+        assert!(failed[1].1.contains("core is 2.1.0, but ^3 is required"), "{}", failed[1].1);
+    }
+
+    #[test]
+    fn failures_cascade_to_dependents() {
+        let resolution = resolve(vec![
+            candidate("broken", "1.0.0", &[("ghost", "*")]),
+            candidate("middle", "1.0.0", &[("broken", "*")]),
+            candidate("top", "1.0.0", &[("middle", "*")]),
+        ]);
+        assert!(resolution.levels.is_empty());
+        let failed: Vec<_> = failures(&resolution).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(failed, ["broken", "middle", "top"]);
+    }
+
+    #[test]
+    fn cycles_fail_without_holding_up_everyone_else() {
+        let resolution = resolve(vec![
+            candidate("a", "1.0.0", &[("b", "*")]),
+            candidate("b", "1.0.0", &[("a", "*")]),
+            candidate("c", "1.0.0", &[]),
+        ]);
+        assert_eq!(levels(&resolution), [vec!["c"]]);
+        let failed = failures(&resolution);
+        assert_eq!(failed.len(), 2);
+        assert!(failed.iter().all(|(_, e)| e.contains("dependency cycle among a, b")));
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use crate::embedder::AppHandle;
+
+/// Ids of the plugins the user has switched off, kept in the app config dir so
+/// they stay off across restarts:
+///
+/// ```toml
+/// disabled = ["plugin-ui"]
+/// ```
+const DISABLED_FILE: &str = "disabled-plugins.toml";
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct DisabledPlugins {
+    #[serde(default)]
+    disabled: BTreeSet<String>,
+}
+
+/// Whether the user has disabled plugin `id`.
+pub fn is_disabled(app: &AppHandle, id: &str) -> bool {
+    // A broken file shouldn't stop anything from running
+    match read(app) {
+        Ok(file) => file.disabled.contains(id),
+        Err(e) => {
+            println!("⚠️ Ignoring disabled plugins list: {}", e);
+            false
+        }
+    }
+}
+
+/// Records whether plugin `id` is enabled, for the next time it loads.
+pub fn save(app: &AppHandle, id: &str, enabled: bool) -> Result<(), String> {
+    let path = disabled_path(app).ok_or("no app config directory")?;
+    // Don't overwrite a file we couldn't parse; the user would lose what's in it
+    let mut file = read(app)?;
+    if enabled {
+        file.disabled.remove(id);
+    } else {
+        file.disabled.insert(id.to_string());
+    }
+
+    let text = toml::to_string(&file).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{:?}: {}", dir, e))?;
+    }
+    std::fs::write(&path, text).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+fn disabled_path(app: &AppHandle) -> Option<PathBuf> {
+    app.app_config_dir().map(|dir| dir.join(DISABLED_FILE))
+}
+
+fn read(app: &AppHandle) -> Result<DisabledPlugins, String> {
+    let Some(path) = disabled_path(app).filter(|path| path.exists()) else {
+        return Ok(DisabledPlugins::default());
+    };
+
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{:?}: {}", path, e))?;
+    toml::from_str(&text).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn disabling_lasts_until_reenabled() {
+        let app = TestApp::new();
+        assert!(!is_disabled(&app.handle, "switched-off"));
+        save(&app.handle, "switched-off", false).unwrap();
+        assert!(is_disabled(&app.handle, "switched-off"));
+        save(&app.handle, "switched-off", true).unwrap();
+        assert!(!is_disabled(&app.handle, "switched-off"));
+    }
+
+    #[test]
+    fn a_broken_disabled_file_isnt_overwritten() {
+        let app = TestApp::new();
+        std::fs::create_dir_all(app.config_dir()).unwrap();
+        let path = app.config_dir().join(DISABLED_FILE);
+        std::fs::write(&path, "disabled = [").unwrap();
+
+        assert!(save(&app.handle, "switched-off", false).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "disabled = [");
+    }
+}
@@ -0,0 +1,520 @@
+use serde_json::{json, Map, Value};
+use std::time::{Duration, Instant};
+use wasmtime::component::{ComponentNamedList, Func, Instance, Lift, Lower, Type, Val};
+use wasmtime::Store;
+
+use crate::error::PluginError;
+use crate::host::PluginHost;
+use crate::metrics;
+use crate::recovery;
+use crate::registry::PluginInstance;
+use crate::{arm_call_limits, handle_exit, plugin_instance};
+
+/// Calls `function` on plugin `id` with JSON-encoded positional `args`.
+///
+/// `function` is either a top-level export (`get-number`) or an interface
+/// export (`test#get-number`, or `test/get-number`). Multiple results come back
+/// as a JSON array, a single result as itself, and no results as `null`. Records
+/// become objects keyed by field name, so `test#get-stats` returns `{ count, label }`.
+pub async fn invoke(
+    id: &str,
+    function: &str,
+    args: Value,
+    timeout: Option<Duration>,
+) -> Result<Value, PluginError> {
+    let called = recovery::with_trap_retries(id, || call_export(id, function, args.clone(), timeout)).await;
+    settle_exit(id, called).await
+}
+
+/// Calls `function`, which must take no arguments, on plugin `id` and returns its
+/// result as JSON the way `invoke` does, without building any arguments first.
+pub async fn invoke_simple(id: &str, function: &str, timeout: Option<Duration>) -> Result<Value, PluginError> {
+    let called = recovery::with_trap_retries(id, || call_simple(id, function, timeout)).await;
+    settle_exit(id, called).await
+}
+
+// Unloads a plugin that exited, if configured to, then treats a clean exit as a
+// successful call that just didn't return anything
+async fn settle_exit(id: &str, called: Result<Value, PluginError>) -> Result<Value, PluginError> {
+    match called {
+        Err(PluginError::Exited(code)) => {
+            handle_exit(id, code).await;
+            if code == 0 {
+                Ok(Value::Null)
+            } else {
+                Err(PluginError::Exited(code))
+            }
+        }
+        result => result,
+    }
+}
+
+/// Calls `function` on plugin `id`, which must take and return a single `list<u8>`,
+/// passing the bytes straight through without a JSON round-trip.
+pub async fn invoke_bytes(
+    id: &str,
+    function: &str,
+    input: Vec<u8>,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>, PluginError> {
+    let called = recovery::with_trap_retries(id, || call_bytes(id, function, input.clone(), timeout)).await;
+    if let Err(PluginError::Exited(code)) = &called {
+        handle_exit(id, *code).await;
+    }
+    called
+}
+
+async fn call_bytes(
+    id: &str,
+    function: &str,
+    input: Vec<u8>,
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>, PluginError> {
+    let (instance, fuel_budget) = plugin_instance(id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let func = find_func(store, instance, function)?
+        .typed::<(Vec<u8>,), (Vec<u8>,)>(&*store)
+        .map_err(|e| PluginError::InvalidArguments(format!("{} doesn't take and return list<u8>: {}", function, e)))?;
+
+    arm_call_limits(store, fuel_budget, function, timeout)?;
+    let started = Instant::now();
+    let called = match func.call_async(&mut *store, (input,)).await {
+        Ok((output,)) => func.post_return_async(&mut *store).await.map(|()| output),
+        Err(e) => Err(e),
+    };
+    let fuel_used = metrics::fuel_used(store, fuel_budget);
+    metrics::record(id, function, started.elapsed(), fuel_used, called.is_err());
+    called.map_err(PluginError::trap)
+}
+
+async fn call_export(
+    id: &str,
+    function: &str,
+    args: Value,
+    timeout: Option<Duration>,
+) -> Result<Value, PluginError> {
+    let (instance, fuel_budget) = plugin_instance(id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let func = find_func(store, instance, function)?;
+    let params = json_to_params(&args, &func.params(&*store))?;
+    let mut results = vec![Val::Bool(false); func.results(&*store).len()];
+
+    arm_call_limits(store, fuel_budget, function, timeout)?;
+    let started = Instant::now();
+    let called = match func.call_async(&mut *store, &params, &mut results).await {
+        Ok(()) => func.post_return_async(&mut *store).await,
+        Err(e) => Err(e),
+    };
+    let fuel_used = metrics::fuel_used(store, fuel_budget);
+    metrics::record(id, function, started.elapsed(), fuel_used, called.is_err());
+    called.map_err(PluginError::trap)?;
+
+    Ok(results_to_json(&results))
+}
+
+async fn call_simple(id: &str, function: &str, timeout: Option<Duration>) -> Result<Value, PluginError> {
+    let (instance, fuel_budget) = plugin_instance(id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let func = find_func(store, instance, function)?;
+    let params = func.params(&*store);
+    if !params.is_empty() {
+        return Err(PluginError::InvalidArguments(format!(
+            "{} takes {} argument(s); call it with invoke_export instead",
+            function,
+            params.len()
+        )));
+    }
+    let mut results = vec![Val::Bool(false); func.results(&*store).len()];
+
+    arm_call_limits(store, fuel_budget, function, timeout)?;
+    let started = Instant::now();
+    let called = match func.call_async(&mut *store, &[], &mut results).await {
+        Ok(()) => func.post_return_async(&mut *store).await,
+        Err(e) => Err(e),
+    };
+    let fuel_used = metrics::fuel_used(store, fuel_budget);
+    metrics::record(id, function, started.elapsed(), fuel_used, called.is_err());
+    called.map_err(PluginError::trap)?;
+
+    Ok(results_to_json(&results))
+}
+
+/// Multiple results come back as a JSON array, a single result as itself, and none as `null`.
+fn results_to_json(results: &[Val]) -> Value {
+    match results {
+        [] => Value::Null,
+        [single] => val_to_json(single),
+        many => Value::Array(many.iter().map(val_to_json).collect()),
+    }
+}
+
+/// Calls `function` with Rust-typed params and results, looking it up by name
+/// rather than through the generated bindings. For plugins whose exports don't
+/// match the host's `plugin` world exactly; the caller arms the call limits.
+pub async fn call_typed<Params, Results>(
+    store: &mut Store<PluginHost>,
+    instance: &Instance,
+    function: &str,
+    params: Params,
+) -> Result<Results, PluginError>
+where
+    Params: ComponentNamedList + Lower + Send + Sync,
+    Results: ComponentNamedList + Lift + Send + Sync,
+{
+    let func = find_func(store, instance, function)?
+        .typed::<Params, Results>(&*store)
+        .map_err(|e| PluginError::LinkError(format!("{} has an unexpected signature: {}", function, e)))?;
+
+    let results = func.call_async(&mut *store, params).await.map_err(PluginError::trap)?;
+    func.post_return_async(&mut *store).await.map_err(PluginError::trap)?;
+    Ok(results)
+}
+
+/// Resolves an export name like `get-number` or `test#get-number` to a function.
+pub fn find_func(store: &mut Store<PluginHost>, instance: &Instance, name: &str) -> Result<Func, PluginError> {
+    let missing = || PluginError::NoSuchFunction(name.to_string());
+
+    let index = match name.rsplit_once('#').or_else(|| name.rsplit_once('/')) {
+        Some((interface, func)) => {
+            let interface = instance
+                .get_export(&mut *store, None, interface)
+                .ok_or_else(missing)?;
+            instance
+                .get_export(&mut *store, Some(&interface), func)
+                .ok_or_else(missing)?
+        }
+        None => instance.get_export(&mut *store, None, name).ok_or_else(missing)?,
+    };
+
+    instance.get_func(&mut *store, index).ok_or_else(missing)
+}
+
+fn json_to_params(args: &Value, types: &[Type]) -> Result<Vec<Val>, PluginError> {
+    // `null` is shorthand for "no arguments"
+    let args: &[Value] = match args {
+        Value::Null => &[],
+        Value::Array(args) => args,
+        other => {
+            return Err(PluginError::InvalidArguments(format!(
+                "expected an array of arguments, got {}",
+                other
+            )))
+        }
+    };
+
+    if args.len() != types.len() {
+        return Err(PluginError::InvalidArguments(format!(
+            "expected {} argument(s), got {}",
+            types.len(),
+            args.len()
+        )));
+    }
+
+    args.iter().zip(types).map(|(arg, ty)| json_to_val(arg, ty)).collect()
+}
+
+/// Converts a JSON value into a component value of type `ty`.
+pub fn json_to_val(value: &Value, ty: &Type) -> Result<Val, PluginError> {
+    let mismatch = || PluginError::InvalidArguments(format!("expected {:?}, got {}", ty, value));
+
+    let val = match ty {
+        Type::Bool => Val::Bool(value.as_bool().ok_or_else(mismatch)?),
+        Type::S8 => Val::S8(signed(value).ok_or_else(mismatch)?),
+        Type::S16 => Val::S16(signed(value).ok_or_else(mismatch)?),
+        Type::S32 => Val::S32(signed(value).ok_or_else(mismatch)?),
+        Type::S64 => Val::S64(signed(value).ok_or_else(mismatch)?),
+        Type::U8 => Val::U8(unsigned(value).ok_or_else(mismatch)?),
+        Type::U16 => Val::U16(unsigned(value).ok_or_else(mismatch)?),
+        Type::U32 => Val::U32(unsigned(value).ok_or_else(mismatch)?),
+        Type::U64 => Val::U64(unsigned(value).ok_or_else(mismatch)?),
+        Type::Float32 => Val::Float32(value.as_f64().ok_or_else(mismatch)? as f32),
+        Type::Float64 => Val::Float64(value.as_f64().ok_or_else(mismatch)?),
+        Type::Char => {
+            let text = value.as_str().ok_or_else(mismatch)?;
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Val::Char(c),
+                _ => return Err(mismatch()),
+            }
+        }
+        Type::String => Val::String(value.as_str().ok_or_else(mismatch)?.to_string()),
+        Type::List(list) => {
+            let items = value.as_array().ok_or_else(mismatch)?;
+            let item_ty = list.ty();
+            Val::List(items.iter().map(|item| json_to_val(item, &item_ty)).collect::<Result<_, _>>()?)
+        }
+        Type::Record(record) => {
+            let object = value.as_object().ok_or_else(mismatch)?;
+            let fields = record
+                .fields()
+                .map(|field| {
+                    // Leaving out an optional field is the same as passing `null`
+                    let field_value = object.get(field.name).unwrap_or(&Value::Null);
+                    Ok((field.name.to_string(), json_to_val(field_value, &field.ty)?))
+                })
+                .collect::<Result<_, PluginError>>()?;
+            Val::Record(fields)
+        }
+        Type::Tuple(tuple) => {
+            let items = value.as_array().ok_or_else(mismatch)?;
+            let types: Vec<Type> = tuple.types().collect();
+            if items.len() != types.len() {
+                return Err(mismatch());
+            }
+            Val::Tuple(items.iter().zip(&types).map(|(item, ty)| json_to_val(item, ty)).collect::<Result<_, _>>()?)
+        }
+        Type::Option(option) => match value {
+            Value::Null => Val::Option(None),
+            value => Val::Option(Some(Box::new(json_to_val(value, &option.ty())?))),
+        },
+        Type::Enum(enum_ty) => {
+            let name = value.as_str().ok_or_else(mismatch)?;
+            if !enum_ty.names().any(|case| case == name) {
+                return Err(mismatch());
+            }
+            Val::Enum(name.to_string())
+        }
+        Type::Variant(variant) => {
+            // A payload-less case is just its name; otherwise `{ "case": payload }`
+            let (name, payload) = match value {
+                Value::String(name) => (name.as_str(), None),
+                Value::Object(object) if object.len() == 1 => {
+                    let (name, payload) = object.iter().next().unwrap();
+                    (name.as_str(), Some(payload))
+                }
+                _ => return Err(mismatch()),
+            };
+            let case = variant.cases().find(|case| case.name == name).ok_or_else(mismatch)?;
+            let payload = match (case.ty, payload) {
+                (Some(ty), Some(payload)) => Some(Box::new(json_to_val(payload, &ty)?)),
+                (None, None) => None,
+                _ => return Err(mismatch()),
+            };
+            Val::Variant(name.to_string(), payload)
+        }
+        Type::Result(result) => {
+            let object = value.as_object().ok_or_else(mismatch)?;
+            let convert = |payload: Option<&Value>, ty: Option<Type>| -> Result<Option<Box<Val>>, PluginError> {
+                match ty {
+                    Some(ty) => Ok(Some(Box::new(json_to_val(payload.unwrap_or(&Value::Null), &ty)?))),
+                    None => Ok(None),
+                }
+            };
+            if object.contains_key("ok") {
+                Val::Result(Ok(convert(object.get("ok"), result.ok())?))
+            } else if object.contains_key("err") {
+                Val::Result(Err(convert(object.get("err"), result.err())?))
+            } else {
+                return Err(mismatch());
+            }
+        }
+        Type::Flags(flags) => {
+            let names = value.as_array().ok_or_else(mismatch)?;
+            let names = names
+                .iter()
+                .map(|name| {
+                    let name = name.as_str().ok_or_else(mismatch)?;
+                    if flags.names().any(|flag| flag == name) {
+                        Ok(name.to_string())
+                    } else {
+                        Err(mismatch())
+                    }
+                })
+                .collect::<Result<_, _>>()?;
+            Val::Flags(names)
+        }
+        Type::Own(_) | Type::Borrow(_) => {
+            return Err(PluginError::InvalidArguments(
+                "resources can't be passed as JSON".to_string(),
+            ))
+        }
+    };
+
+    Ok(val)
+}
+
+/// Converts a component value into JSON, mirroring `json_to_val`.
+pub fn val_to_json(val: &Val) -> Value {
+    match val {
+        Val::Bool(b) => Value::Bool(*b),
+        Val::S8(n) => json!(n),
+        Val::S16(n) => json!(n),
+        Val::S32(n) => json!(n),
+        Val::S64(n) => json!(n),
+        Val::U8(n) => json!(n),
+        Val::U16(n) => json!(n),
+        Val::U32(n) => json!(n),
+        Val::U64(n) => json!(n),
+        Val::Float32(n) => json!(n),
+        Val::Float64(n) => json!(n),
+        Val::Char(c) => Value::String(c.to_string()),
+        Val::String(s) => Value::String(s.clone()),
+        Val::List(items) | Val::Tuple(items) => Value::Array(items.iter().map(val_to_json).collect()),
+        Val::Record(fields) => {
+            let object: Map<String, Value> = fields
+                .iter()
+                .map(|(name, value)| (name.clone(), val_to_json(value)))
+                .collect();
+            Value::Object(object)
+        }
+        Val::Variant(name, None) | Val::Enum(name) => Value::String(name.clone()),
+        Val::Variant(name, Some(payload)) => json!({ name.clone(): val_to_json(payload) }),
+        Val::Option(value) => value.as_deref().map(val_to_json).unwrap_or(Value::Null),
+        Val::Result(Ok(value)) => json!({ "ok": value.as_deref().map(val_to_json) }),
+        Val::Result(Err(value)) => json!({ "err": value.as_deref().map(val_to_json) }),
+        Val::Flags(names) => Value::Array(names.iter().cloned().map(Value::String).collect()),
+        Val::Resource(_) => Value::Null,
+    }
+}
+
+fn signed<T: TryFrom<i64>>(value: &Value) -> Option<T> {
+    value.as_i64().and_then(|n| T::try_from(n).ok())
+}
+
+fn unsigned<T: TryFrom<u64>>(value: &Value) -> Option<T> {
+    value.as_u64().and_then(|n| T::try_from(n).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    // `echo` hands back the bytes it was given; `get-number` answers 42
+    const ECHOES: &str = r#"
+    (component
+      (core module $m
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+          (local $ptr i32)
+          (local.set $ptr (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+          (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+          (local.get $ptr))
+        (func (export "echo") (param $ptr i32) (param $len i32) (result i32)
+          (i32.store (i32.const 0) (local.get $ptr))
+          (i32.store (i32.const 4) (local.get $len))
+          (i32.const 0))
+        (func (export "get-number") (result i32) i32.const 42))
+      (core instance $i (instantiate $m))
+      (func $echo (param "data" (list u8)) (result (list u8))
+        (canon lift (core func $i "echo") (memory $i "memory") (realloc (func $i "realloc"))))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (export "echo" (func $echo))
+      (export "get-number" (func $get-number)))
+    "#;
+
+    #[tokio::test]
+    async fn bytes_go_through_untouched() {
+        let app = TestApp::new();
+        let id = app.load("dynamic-bytes", ECHOES).await.unwrap();
+        let input: Vec<u8> = (0..=255).collect();
+        assert_eq!(invoke_bytes(&id, "echo", input.clone(), None).await.unwrap(), input);
+        assert_eq!(invoke_bytes(&id, "echo", Vec::new(), None).await.unwrap(), Vec::<u8>::new());
+    }
+
+    #[tokio::test]
+    async fn bytes_need_a_list_u8_function() {
+        let app = TestApp::new();
+        let id = app.load("dynamic-bytes-mismatch", ECHOES).await.unwrap();
+        let result = invoke_bytes(&id, "get-number", vec![1, 2, 3], None).await;
+        assert!(matches!(result, Err(PluginError::InvalidArguments(_))), "{:?}", result);
+    }
+
+    // Never called; only here for their parameter types
+    const TAKES_EVERYTHING: &str = r#"
+    (component
+      (core module $m
+        (memory (export "memory") 1)
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32) i32.const 1024)
+        (func (export "take") (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32 f64))
+        (func (export "take-named") (param i32 i32 i32 i32 i32 i32 i32)))
+      (core instance $i (instantiate $m))
+      (type $point-def (record (field "name" string) (field "count" u32)))
+      (export $point "point" (type $point-def))
+      (type $mode-def (enum "fast" "slow"))
+      (export $mode "mode" (type $mode-def))
+      (type $shape-def (variant (case "empty") (case "count" u32)))
+      (export $shape "shape" (type $shape-def))
+      (type $perms-def (flags "read" "write"))
+      (export $perms "perms" (type $perms-def))
+      (func $take
+        (param "a" (list (option u32)))
+        (param "b" (tuple s8 char string))
+        (param "c" (result u32 (error string)))
+        (param "d" (option float64))
+        (canon lift (core func $i "take") (memory $i "memory") (realloc (func $i "realloc"))))
+      (func $take-named (param "p" $point) (param "m" $mode) (param "s" $shape) (param "f" $perms)
+        (canon lift (core func $i "take-named") (memory $i "memory") (realloc (func $i "realloc"))))
+      (export "take" (func $take))
+      (export "take-named" (func $take-named)))
+    "#;
+
+    fn param_types(function: &str) -> Vec<Type> {
+        use wasmtime::component::types::ComponentItem;
+
+        let engine = crate::testing::engine();
+        let component = wasmtime::component::Component::new(engine, crate::testing::component(TAKES_EVERYTHING)).unwrap();
+        let item = component
+            .component_type()
+            .exports(engine)
+            .find(|(name, _)| *name == function)
+            .map(|(_, item)| item);
+        match item {
+            Some(ComponentItem::ComponentFunc(func)) => func.params().collect(),
+            _ => panic!("no function {}", function),
+        }
+    }
+
+    fn round_trip(value: Value, ty: &Type) {
+        let val = json_to_val(&value, ty).unwrap_or_else(|e| panic!("{} didn't convert: {}", value, e));
+        assert_eq!(val_to_json(&val), value);
+    }
+
+    #[test]
+    fn json_round_trips_through_component_values() {
+        let types = param_types("take");
+        round_trip(json!([1, null, 3]), &types[0]);
+        round_trip(json!([-5, "é", "text"]), &types[1]);
+        round_trip(json!({ "ok": 7 }), &types[2]);
+        round_trip(json!({ "err": "bad" }), &types[2]);
+        round_trip(json!(1.5), &types[3]);
+        round_trip(Value::Null, &types[3]);
+
+        let types = param_types("take-named");
+        round_trip(json!({ "name": "pato", "count": 2 }), &types[0]);
+        round_trip(json!("slow"), &types[1]);
+        round_trip(json!("empty"), &types[2]);
+        round_trip(json!({ "count": 3 }), &types[2]);
+        round_trip(json!(["read", "write"]), &types[3]);
+    }
+
+    #[test]
+    fn json_that_doesnt_fit_the_type_is_rejected() {
+        let types = param_types("take");
+        let named = param_types("take-named");
+        let rejected = [
+            (json!(["1"]), &types[0]),
+            // Out of range for the s8
+            (json!([200, "a", "text"]), &types[1]),
+            (json!([1, "ab", "text"]), &types[1]),
+            (json!([1, "a"]), &types[1]),
+            (json!({ "maybe": 7 }), &types[2]),
+            (json!({ "name": "pato" }), &named[0]),
+            (json!("medium"), &named[1]),
+            (json!({ "empty": 1 }), &named[2]),
+            (json!("count"), &named[2]),
+            (json!(["execute"]), &named[3]),
+        ];
+        for (value, ty) in rejected {
+            let result = json_to_val(&value, ty);
+            assert!(matches!(result, Err(PluginError::InvalidArguments(_))), "{} was accepted", value);
+        }
+    }
+}
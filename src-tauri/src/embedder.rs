@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+#[cfg(feature = "tauri")]
+use tauri::{Emitter, Manager};
+
+/// What the plugin runtime needs from the app hosting it: somewhere to send
+/// events, and the directories plugins' config, data, and downloads live in.
+/// The Tauri app is one embedder; tests and other hosts can bring their own.
+pub trait Embedder: Send + Sync + 'static {
+    /// Sends `payload` to whoever is listening for `event`.
+    fn emit_value(&self, event: &str, payload: serde_json::Value) -> Result<(), String>;
+
+    /// Holds plugins' data directories and, by default, the plugins directory itself.
+    fn app_data_dir(&self) -> Option<PathBuf>;
+
+    /// Holds permission, config, signing, and enabled-state files.
+    fn app_config_dir(&self) -> Option<PathBuf>;
+
+    /// Holds downloads waiting to be validated.
+    fn app_cache_dir(&self) -> Option<PathBuf>;
+
+    /// Where plugins bundled with the app are looked for, if anywhere.
+    fn resource_dir(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// The app's version, answered by `host-info.version`.
+    fn version(&self) -> String;
+}
+
+/// A cheaply cloned handle on the embedder, passed wherever plugins are loaded or called.
+#[derive(Clone)]
+pub struct AppHandle(Arc<dyn Embedder>);
+
+impl AppHandle {
+    pub fn new(embedder: impl Embedder) -> Self {
+        AppHandle(Arc::new(embedder))
+    }
+
+    /// Serializes `payload` and sends it as `event`.
+    pub fn emit<S: Serialize>(&self, event: &str, payload: S) -> Result<(), String> {
+        let payload = serde_json::to_value(payload).map_err(|e| e.to_string())?;
+        self.0.emit_value(event, payload)
+    }
+
+    pub fn app_data_dir(&self) -> Option<PathBuf> {
+        self.0.app_data_dir()
+    }
+
+    pub fn app_config_dir(&self) -> Option<PathBuf> {
+        self.0.app_config_dir()
+    }
+
+    pub fn app_cache_dir(&self) -> Option<PathBuf> {
+        self.0.app_cache_dir()
+    }
+
+    pub fn resource_dir(&self) -> Option<PathBuf> {
+        self.0.resource_dir()
+    }
+
+    pub fn version(&self) -> String {
+        self.0.version()
+    }
+}
+
+#[cfg(feature = "tauri")]
+impl Embedder for tauri::AppHandle {
+    fn emit_value(&self, event: &str, payload: serde_json::Value) -> Result<(), String> {
+        self.emit(event, payload).map_err(|e| e.to_string())
+    }
+
+    fn app_data_dir(&self) -> Option<PathBuf> {
+        self.path().app_data_dir().ok()
+    }
+
+    fn app_config_dir(&self) -> Option<PathBuf> {
+        self.path().app_config_dir().ok()
+    }
+
+    fn app_cache_dir(&self) -> Option<PathBuf> {
+        self.path().app_cache_dir().ok()
+    }
+
+    fn resource_dir(&self) -> Option<PathBuf> {
+        self.path().resource_dir().ok()
+    }
+
+    fn version(&self) -> String {
+        self.package_info().version.to_string()
+    }
+}
+
+#[cfg(feature = "tauri")]
+impl From<tauri::AppHandle> for AppHandle {
+    fn from(app: tauri::AppHandle) -> Self {
+        AppHandle::new(app)
+    }
+}
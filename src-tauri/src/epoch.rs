@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use wasmtime::{Engine, StoreContextMut, UpdateDeadline};
+
+use crate::error::{CallCancelled, FunctionTimedOut};
+use crate::host::PluginHost;
+
+/// How often the engine epoch advances. Deadlines are measured in these ticks.
+pub const TICK: Duration = Duration::from_millis(10);
+
+struct Ticker {
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+// The running ticker thread, if any
+static TICKER: Mutex<Option<Ticker>> = Mutex::new(None);
+
+/// Starts a background thread that advances `engine`'s epoch every `TICK`.
+pub fn start(engine: Engine) {
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let handle = std::thread::spawn(move || {
+        while !thread_stop.load(Ordering::Relaxed) {
+            std::thread::sleep(TICK);
+            engine.increment_epoch();
+        }
+    });
+
+    // Replacing an old ticker would leave it running, so stop it first
+    stop_ticker(TICKER.lock().unwrap().replace(Ticker { stop, handle }));
+}
+
+/// Stops the ticker thread and waits for it to exit.
+pub fn stop() {
+    stop_ticker(TICKER.lock().unwrap().take());
+}
+
+fn stop_ticker(ticker: Option<Ticker>) {
+    if let Some(ticker) = ticker {
+        ticker.stop.store(true, Ordering::Relaxed);
+        let _ = ticker.handle.join();
+    }
+}
+
+/// Converts a timeout into the number of epoch ticks to pass to `Store::set_epoch_deadline`.
+pub fn ticks_for(timeout: Duration) -> u64 {
+    let ticks = timeout.as_millis() / TICK.as_millis();
+    (ticks as u64).max(1)
+}
+
+/// Runs every tick while a plugin call is in progress, since stores keep a deadline
+/// of one tick. That lets a single call be cancelled without bumping the shared
+/// epoch, which would eat into every other plugin's timeout too.
+pub fn on_deadline(mut store: StoreContextMut<'_, PluginHost>) -> wasmtime::Result<UpdateDeadline> {
+    let host = store.data_mut();
+    if host.cancel.swap(false, Ordering::SeqCst) {
+        return Err(CallCancelled.into());
+    }
+    if host.ticks_left <= 1 {
+        if let Some(function) = host.timed_function.clone() {
+            return Err(FunctionTimedOut { function }.into());
+        }
+        return Err(wasmtime::Trap::Interrupt.into());
+    }
+    host.ticks_left -= 1;
+    Ok(UpdateDeadline::Continue(1))
+}
+
+/// Gives the store's next call `timeout` before `on_deadline` interrupts it.
+pub fn arm(store: &mut wasmtime::Store<PluginHost>, timeout: Duration) {
+    let host = store.data_mut();
+    // A cancel that arrived between calls was meant for a call that's already over
+    host.cancel.store(false, Ordering::SeqCst);
+    host.ticks_left = ticks_for(timeout);
+    store.set_epoch_deadline(1);
+}
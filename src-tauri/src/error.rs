@@ -0,0 +1,331 @@
+use serde::Serialize;
+use std::fmt;
+
+use crate::manifest::Capability;
+
+/// Everything that can go wrong loading or calling a plugin. Serialized to the
+/// frontend as `{ kind, message }` so it can tell a missing plugin from a crashed one.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", content = "message")]
+pub enum PluginError {
+    /// No plugin with this id is loaded.
+    NotLoaded(String),
+    /// The plugin is loaded, but the user has disabled it.
+    Disabled(String),
+    /// The plugin doesn't export the requested function.
+    NoSuchFunction(String),
+    /// The arguments don't match the function's parameters.
+    InvalidArguments(String),
+    /// The plugin file couldn't be read.
+    Io(String),
+    /// A plugin couldn't be downloaded, or didn't match its published checksum.
+    Download(String),
+    /// A config value doesn't fit the plugin's declared config schema.
+    InvalidConfig(String),
+    /// The plugin's manifest is malformed or missing required fields.
+    InvalidManifest(String),
+    /// A `.patopkg` isn't a readable archive or is missing its manifest or component.
+    InvalidPackage(String),
+    /// The plugin's signature is missing (in strict mode), malformed, or not from a trusted key.
+    SignatureInvalid(String),
+    /// The bytes aren't a component this engine can compile.
+    CompileFailed(String),
+    /// The file is a core wasm module rather than a component.
+    NotAComponent(String),
+    /// The component uses a wasm feature this engine doesn't enable.
+    UnsupportedFeature(String),
+    /// The file isn't valid wasm at all, e.g. truncated or not wasm in the first place.
+    CorruptBinary(String),
+    /// The host interfaces couldn't be linked, or the plugin doesn't match the expected world.
+    LinkError(String),
+    /// The component compiled but couldn't be instantiated.
+    InstantiationFailed(String),
+    /// The plugin trapped while running.
+    Trap(String),
+    /// The call was stopped by one of the host's execution limits.
+    Limit(PluginTrap),
+    /// The call ran past the deadline the manifest's `timeouts` gives this function.
+    FunctionTimeout(String),
+    /// The call was cancelled with `cancel_plugin_call`.
+    Cancelled(String),
+    /// The plugin called `proc_exit` with this code.
+    Exited(i32),
+    /// The plugin is compiled into the host, so there's no file to reload it from.
+    Embedded(String),
+    /// A declared dependency is missing, incompatible, failed to load, or part of a cycle.
+    Dependency(String),
+    /// A runtime was already built in this process with different settings.
+    RuntimeConflict(String),
+    /// A plugin in a `run_pipeline` chain failed, stopping the pipeline there.
+    PipelineStage { stage: String, error: Box<PluginError> },
+    /// The plugin was built against a version of the host interfaces this host doesn't support.
+    VersionMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::NotLoaded(id) => write!(f, "no plugin named {}", id),
+            PluginError::Disabled(id) => write!(f, "plugin {} is disabled", id),
+            PluginError::NoSuchFunction(function) => write!(f, "plugin has no function {}", function),
+            PluginError::InvalidArguments(message) => write!(f, "invalid arguments: {}", message),
+            PluginError::Io(message) => write!(f, "failed to read plugin: {}", message),
+            PluginError::Download(message) => write!(f, "failed to download plugin: {}", message),
+            PluginError::InvalidConfig(message) => write!(f, "invalid plugin config: {}", message),
+            PluginError::InvalidManifest(message) => write!(f, "invalid plugin manifest: {}", message),
+            PluginError::InvalidPackage(message) => write!(f, "invalid plugin package: {}", message),
+            PluginError::SignatureInvalid(message) => write!(f, "invalid plugin signature: {}", message),
+            PluginError::CompileFailed(message) => write!(f, "failed to compile plugin: {}", message),
+            PluginError::NotAComponent(message) => write!(
+                f,
+                "plugin is a core wasm module, not a component ({}); wrap it with `wasm-tools component new`",
+                message
+            ),
+            PluginError::UnsupportedFeature(message) => write!(f, "plugin uses an unsupported wasm feature: {}", message),
+            PluginError::CorruptBinary(message) => write!(f, "plugin isn't a valid wasm binary: {}", message),
+            PluginError::LinkError(message) => write!(f, "failed to link plugin: {}", message),
+            PluginError::InstantiationFailed(message) => write!(f, "failed to instantiate plugin: {}", message),
+            PluginError::Trap(message) => write!(f, "plugin trapped: {}", message),
+            PluginError::Limit(trap) => write!(f, "{}", trap),
+            PluginError::FunctionTimeout(function) => write!(f, "plugin call to {} timed out", function),
+            PluginError::Cancelled(message) => write!(f, "plugin call cancelled: {}", message),
+            PluginError::Exited(code) => write!(f, "plugin exited with code {}", code),
+            PluginError::Embedded(id) => write!(f, "plugin {} is embedded in the host and can't be reloaded", id),
+            PluginError::Dependency(message) => write!(f, "unmet dependency: {}", message),
+            PluginError::RuntimeConflict(message) => write!(f, "the plugin runtime is already running with {}", message),
+            PluginError::PipelineStage { stage, error } => write!(f, "pipeline stage {} failed: {}", stage, error),
+            PluginError::VersionMismatch { expected, actual } => write!(
+                f,
+                "plugin targets {} {}, but this host supports {}",
+                crate::version::PACKAGE,
+                actual,
+                expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+impl From<PluginTrap> for PluginError {
+    fn from(trap: PluginTrap) -> Self {
+        PluginError::Limit(trap)
+    }
+}
+
+impl PluginError {
+    /// Whether the plugin trapped, including on an execution limit, rather than
+    /// failing some other way.
+    pub fn is_trap(&self) -> bool {
+        matches!(self, PluginError::Trap(_) | PluginError::Limit(_) | PluginError::FunctionTimeout(_))
+    }
+
+    /// Whether the host stopped the call partway through, by cancelling it or on an
+    /// execution limit, which can leave the store's resources half-updated.
+    pub fn is_interrupted(&self) -> bool {
+        matches!(
+            self,
+            PluginError::Cancelled(_) | PluginError::Limit(_) | PluginError::FunctionTimeout(_)
+        )
+    }
+
+    /// Wraps an error raised while running plugin code, keeping limit traps distinct.
+    pub fn trap(error: wasmtime::Error) -> Self {
+        if let Some(exit) = error.downcast_ref::<wasmtime_wasi::I32Exit>() {
+            return PluginError::Exited(exit.0);
+        }
+        if error.downcast_ref::<CallCancelled>().is_some() {
+            return PluginError::Cancelled(describe_trap(&error));
+        }
+        if let Some(timed_out) = error.downcast_ref::<FunctionTimedOut>() {
+            return PluginError::FunctionTimeout(timed_out.function.clone());
+        }
+        match PluginTrap::from_wasmtime(&error) {
+            Some(trap) => trap.into(),
+            None => PluginError::Trap(describe_trap(&error)),
+        }
+    }
+
+    /// Wraps a compilation error, telling apart the mistakes users can fix
+    /// themselves by looking at the binary's header and wasmtime's message.
+    pub fn compile(bytes: &[u8], error: wasmtime::Error) -> Self {
+        const MAGIC: &[u8] = b"\0asm";
+        // Core modules are version 1; components are version 0xd in the component layer
+        const CORE_MODULE_VERSION: &[u8] = &[1, 0, 0, 0];
+
+        let message = format!("{:#}", error);
+        if !bytes.starts_with(MAGIC) {
+            return PluginError::CorruptBinary("missing the \\0asm header".to_string());
+        }
+        if bytes.get(4..8) == Some(CORE_MODULE_VERSION) {
+            return PluginError::NotAComponent(message);
+        }
+
+        let lower = message.to_ascii_lowercase();
+        if lower.contains("feature") || lower.contains("not enabled") || lower.contains("not supported") {
+            PluginError::UnsupportedFeature(message)
+        } else if lower.contains("unexpected end") || lower.contains("malformed") || lower.contains("out of bounds") {
+            PluginError::CorruptBinary(message)
+        } else {
+            PluginError::CompileFailed(message)
+        }
+    }
+
+    /// Wraps an error raised while instantiating, keeping limit traps distinct.
+    pub fn instantiation(error: wasmtime::Error) -> Self {
+        if let Some(exit) = error.downcast_ref::<wasmtime_wasi::I32Exit>() {
+            return PluginError::Exited(exit.0);
+        }
+        if error.downcast_ref::<CallCancelled>().is_some() {
+            return PluginError::Cancelled(describe_trap(&error));
+        }
+        if let Some(timed_out) = error.downcast_ref::<FunctionTimedOut>() {
+            return PluginError::FunctionTimeout(timed_out.function.clone());
+        }
+        match PluginTrap::from_wasmtime(&error) {
+            Some(trap) => trap.into(),
+            None => PluginError::InstantiationFailed(error.to_string()),
+        }
+    }
+}
+
+/// A one-line summary of a trap for the UI, e.g. "wasm `unreachable` instruction
+/// executed (in get_number)". The full chain and wasm backtrace go to the debug log.
+fn describe_trap(error: &wasmtime::Error) -> String {
+    log::debug!("plugin trap: {:?}", error);
+
+    // The backtrace is attached as context, so the top-level message is the whole multi-line trace
+    let message = match error.downcast_ref::<wasmtime::Trap>() {
+        Some(trap) => trap.to_string(),
+        None => error.root_cause().to_string(),
+    };
+
+    let function = error
+        .downcast_ref::<wasmtime::WasmBacktrace>()
+        .and_then(|backtrace| backtrace.frames().iter().find_map(|frame| frame.func_name()));
+
+    match function {
+        Some(function) => format!("{} (in {})", message, function),
+        None => message,
+    }
+}
+
+/// A host function was called without the capability it needs. Its message,
+/// `capability-denied: <capability>`, is what plugins see in the `result` error,
+/// so they can match on the prefix.
+#[derive(Debug, Clone, Copy)]
+pub struct CapabilityDenied(pub Capability);
+
+impl fmt::Display for CapabilityDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "capability-denied: {}", self.0)
+    }
+}
+
+impl std::error::Error for CapabilityDenied {}
+
+/// Raised from the epoch callback to stop a call that was cancelled.
+#[derive(Debug, Clone, Copy)]
+pub struct CallCancelled;
+
+impl fmt::Display for CallCancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stopped by the host")
+    }
+}
+
+impl std::error::Error for CallCancelled {}
+
+/// Raised from the epoch callback when a call runs past its function's own deadline.
+#[derive(Debug, Clone)]
+pub struct FunctionTimedOut {
+    pub function: String,
+}
+
+impl fmt::Display for FunctionTimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ran past its deadline", self.function)
+    }
+}
+
+impl std::error::Error for FunctionTimedOut {}
+
+/// Ways a plugin call can be stopped by the host's execution limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PluginTrap {
+    /// The call used up its fuel budget, usually because it looped forever.
+    OutOfFuel,
+    /// The call ran past its epoch deadline.
+    Timeout,
+}
+
+impl fmt::Display for PluginTrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginTrap::OutOfFuel => write!(f, "plugin ran out of fuel"),
+            PluginTrap::Timeout => write!(f, "plugin call timed out"),
+        }
+    }
+}
+
+impl std::error::Error for PluginTrap {}
+
+impl PluginTrap {
+    /// Picks out the limit traps we know about from a wasmtime error.
+    pub fn from_wasmtime(error: &wasmtime::Error) -> Option<Self> {
+        match error.downcast_ref::<wasmtime::Trap>() {
+            Some(wasmtime::Trap::OutOfFuel) => Some(PluginTrap::OutOfFuel),
+            Some(wasmtime::Trap::Interrupt) => Some(PluginTrap::Timeout),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+    use serde_json::Value;
+
+    // `get-number` hits `unreachable` inside a function named `boom`
+    const TRAPS: &str = r#"
+    (component
+      (core module $m
+        (func $boom (export "get-number") (result i32) unreachable))
+      (core instance $i (instantiate $m))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "test" (instance $test)))
+    "#;
+
+    #[tokio::test]
+    async fn traps_say_what_happened_and_where() {
+        let app = TestApp::new();
+        let id = app.load("error-trap", TRAPS).await.unwrap();
+        let result = crate::dynamic::invoke(&id, "test#get-number", Value::Null, None).await;
+        let Err(PluginError::Trap(message)) = result else {
+            panic!("expected a trap, got {:?}", result);
+        };
+        assert!(message.contains("unreachable"), "{}", message);
+        assert!(message.ends_with("(in boom)"), "{}", message);
+    }
+
+    fn compile_error(bytes: &[u8]) -> PluginError {
+        let Err(error) = wasmtime::component::Component::new(crate::testing::engine(), bytes) else {
+            panic!("expected the bytes not to compile");
+        };
+        PluginError::compile(bytes, error)
+    }
+
+    #[test]
+    fn compile_failures_say_what_kind_of_file_it_was() {
+        let core_module = wat::parse_str("(module)").unwrap();
+        assert!(matches!(compile_error(&core_module), PluginError::NotAComponent(_)));
+
+        assert!(matches!(compile_error(b"definitely not wasm"), PluginError::CorruptBinary(_)));
+
+        let component = crate::testing::component(TRAPS);
+        let truncated = &component[..component.len() / 2];
+        assert!(matches!(compile_error(truncated), PluginError::CorruptBinary(_)));
+    }
+}
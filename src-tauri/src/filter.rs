@@ -0,0 +1,141 @@
+use std::time::Instant;
+
+use crate::error::PluginError;
+use crate::registry::PluginInstance;
+use crate::{arm_call_limits, dynamic, handle_exit, metrics, plugin_instance, recovery};
+
+/// Optional export from the `filter` interface.
+const FILTER_EXPORT: &str = "pato:plugin-ui/filter@0.1.0#filter";
+
+/// Asks plugin `id` which of `items` match `pattern`. The plugin may call
+/// `filter-helper` back while this runs; those calls get the store's host state
+/// directly, so they don't need (and mustn't take) the instance lock held here.
+pub async fn run(id: &str, items: Vec<String>, pattern: String) -> Result<Vec<String>, PluginError> {
+    let result = recovery::with_trap_retries(id, || call_filter(id, items.clone(), pattern.clone())).await;
+    if let Err(PluginError::Exited(code)) = &result {
+        handle_exit(id, *code).await;
+    }
+    result
+}
+
+async fn call_filter(id: &str, items: Vec<String>, pattern: String) -> Result<Vec<String>, PluginError> {
+    let (instance, fuel_budget) = plugin_instance(id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let func = dynamic::find_func(store, instance, FILTER_EXPORT)?;
+    let filter = func
+        .typed::<(Vec<String>, String), (Vec<String>,)>(&*store)
+        .map_err(|e| PluginError::LinkError(e.to_string()))?;
+
+    arm_call_limits(store, fuel_budget, FILTER_EXPORT, None)?;
+    let started = Instant::now();
+    let called = match filter.call_async(&mut *store, (items, pattern)).await {
+        Ok((kept,)) => filter.post_return_async(&mut *store).await.map(|()| kept),
+        Err(e) => Err(e),
+    };
+    let fuel_used = metrics::fuel_used(store, fuel_budget);
+    metrics::record(id, "filter#filter", started.elapsed(), fuel_used, called.is_err());
+
+    called.map_err(PluginError::trap)
+}
+
+/// `filter-helper.matches`: case-insensitive, `*` for any run of characters, `?` for one.
+pub fn matches(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+
+    // Greedy match, backtracking to the last `*` on a mismatch
+    let (mut t, mut p) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    // `filter` keeps the items the host's `filter-helper.matches` says match
+    const FILTERS_THROUGH_THE_HOST: &str = r#"
+    (component
+      (import "pato:plugin-ui/filter-helper@0.1.0" (instance $helper
+        (export "matches" (func (param "text" string) (param "pattern" string) (result bool)))))
+      (core module $memory (memory (export "memory") 1))
+      (core instance $memory (instantiate $memory))
+      (core func $matches (canon lower (func $helper "matches") (memory $memory "memory")))
+      (core instance $host (export "matches" (func $matches)))
+      (core module $m
+        (import "env" "memory" (memory 1))
+        (import "host" "matches" (func $matches (param i32 i32 i32 i32) (result i32)))
+        (global $next (mut i32) (i32.const 1024))
+        (func $realloc (export "realloc") (param i32 i32 i32 i32) (result i32)
+          (local $ptr i32)
+          (local.set $ptr (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+          (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+          (local.get $ptr))
+        (func (export "filter") (param $items i32) (param $count i32) (param $pattern i32) (param $pattern-len i32) (result i32)
+          (local $out i32) (local $kept i32) (local $item i32) (local $i i32)
+          (local.set $out (call $realloc (i32.const 0) (i32.const 0) (i32.const 4) (i32.mul (local.get $count) (i32.const 8))))
+          (block $done
+            (loop $next-item
+              (br_if $done (i32.ge_u (local.get $i) (local.get $count)))
+              (local.set $item (i32.add (local.get $items) (i32.mul (local.get $i) (i32.const 8))))
+              (if (call $matches (i32.load (local.get $item)) (i32.load offset=4 (local.get $item))
+                                 (local.get $pattern) (local.get $pattern-len))
+                (then
+                  (i64.store (i32.add (local.get $out) (i32.mul (local.get $kept) (i32.const 8)))
+                             (i64.load (local.get $item)))
+                  (local.set $kept (i32.add (local.get $kept) (i32.const 1)))))
+              (local.set $i (i32.add (local.get $i) (i32.const 1)))
+              (br $next-item)))
+          (i32.store (i32.const 0) (local.get $out))
+          (i32.store (i32.const 4) (local.get $kept))
+          (i32.const 0)))
+      (core instance $i (instantiate $m (with "env" (instance $memory)) (with "host" (instance $host))))
+      (func $filter (param "items" (list string)) (param "pattern" string) (result (list string))
+        (canon lift (core func $i "filter") (memory $memory "memory") (realloc (func $i "realloc"))))
+      (instance $filter (export "filter" (func $filter)))
+      (export "pato:plugin-ui/filter@0.1.0" (instance $filter)))
+    "#;
+
+    #[test]
+    fn wildcards_match_any_run_or_any_one_character() {
+        assert!(matches("Pato.wasm", "*.WASM"));
+        assert!(matches("pato", "p?to"));
+        assert!(matches("pato", "*"));
+        assert!(matches("", "*"));
+        assert!(matches("a-b-c", "a*c"));
+        assert!(!matches("pato", "p?t"));
+        assert!(!matches("pato.toml", "*.wasm"));
+    }
+
+    #[tokio::test]
+    async fn plugins_can_call_the_host_back_while_filtering() {
+        let app = TestApp::new();
+        let id = app.load("filter-helper", FILTERS_THROUGH_THE_HOST).await.unwrap();
+        let items = ["alpha.wasm", "beta.toml", "Gamma.WASM"].map(String::from).to_vec();
+        let kept = run(&id, items, "*.wasm".to_string()).await.unwrap();
+        assert_eq!(kept, ["alpha.wasm", "Gamma.WASM"]);
+    }
+}
@@ -0,0 +1,47 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Host side of a WIT `handle`: a file in the plugin's data directory. Lives in
+/// the store's resource table, so dropping the store closes it.
+pub struct OpenFile {
+    /// `None` once the plugin has called `close`
+    pub file: Option<File>,
+}
+
+impl OpenFile {
+    /// Opens (creating if needed) `name` directly inside `data_dir`.
+    pub fn open(data_dir: &Path, name: &str) -> Result<Self, String> {
+        // Handles are flat names in `/data`, never paths that could leave it
+        let is_safe = !name.is_empty()
+            && name != "."
+            && name != ".."
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !is_safe {
+            return Err(format!("{:?} isn't a valid handle name", name));
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(data_dir.join(name))
+            .map_err(|e| e.to_string())?;
+        Ok(OpenFile { file: Some(file) })
+    }
+
+    pub fn read(&mut self, max: u32) -> Result<Vec<u8>, String> {
+        let file = self.file.as_mut().ok_or("handle is closed")?;
+        let mut buffer = vec![0; max as usize];
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        buffer.truncate(read);
+        Ok(buffer)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> Result<u32, String> {
+        let file = self.file.as_mut().ok_or("handle is closed")?;
+        file.write_all(bytes).map_err(|e| e.to_string())?;
+        Ok(bytes.len() as u32)
+    }
+}
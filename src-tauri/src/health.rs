@@ -0,0 +1,157 @@
+use serde::Serialize;
+use std::time::{Duration, SystemTime};
+use wasmtime::component::Val;
+
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::registry::{millis_since_epoch, PluginInstance};
+use crate::{arm_call_limits, dynamic, plugin_instance, recovery, registry};
+
+/// The optional export from the `health` interface.
+const HEALTH_EXPORT: &str = "pato:plugin-ui/health@0.1.0#health";
+
+/// How often every plugin is checked.
+const INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a single health check may run before it counts as a failure.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Consecutive failed checks before a plugin is marked unhealthy.
+pub const MAX_FAILURES: u32 = 3;
+
+/// What a plugin's `health` export reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "healthy" => Some(HealthStatus::Healthy),
+            "degraded" => Some(HealthStatus::Degraded),
+            "unhealthy" => Some(HealthStatus::Unhealthy),
+            _ => None,
+        }
+    }
+}
+
+/// The last-known health of a plugin that exports `health`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PluginHealth {
+    /// What the last successful check reported
+    pub status: Option<HealthStatus>,
+    /// Checks in a row that trapped, timed out, or reported `unhealthy`
+    pub consecutive_failures: u32,
+    /// Set once `consecutive_failures` reaches `MAX_FAILURES`
+    pub unhealthy: bool,
+    /// Milliseconds since the Unix epoch
+    pub checked_at: u64,
+    pub last_error: Option<String>,
+}
+
+impl PluginHealth {
+    /// Records a check, returning true if it's the one that made the plugin unhealthy.
+    pub fn record(&mut self, outcome: Result<HealthStatus, PluginError>) -> bool {
+        self.checked_at = millis_since_epoch(SystemTime::now());
+        match outcome {
+            Ok(status) => {
+                self.status = Some(status);
+                self.last_error = None;
+            }
+            Err(e) => self.last_error = Some(e.to_string()),
+        }
+
+        if self.status == Some(HealthStatus::Unhealthy) || self.last_error.is_some() {
+            self.consecutive_failures += 1;
+        } else {
+            self.consecutive_failures = 0;
+            self.unhealthy = false;
+        }
+
+        let became_unhealthy = !self.unhealthy && self.consecutive_failures >= MAX_FAILURES;
+        self.unhealthy |= became_unhealthy;
+        became_unhealthy
+    }
+}
+
+/// Starts polling every loaded plugin's `health` export.
+pub fn start(app: AppHandle) {
+    crate::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(INTERVAL);
+        loop {
+            ticker.tick().await;
+            let ids = registry().list_ids();
+            for id in ids {
+                poll(&app, &id).await;
+            }
+        }
+    });
+}
+
+async fn poll(app: &AppHandle, id: &str) {
+    let outcome = match check(id).await {
+        Ok(Some(status)) => Ok(status),
+        Ok(None) => return,
+        Err(e) => Err(e),
+    };
+
+    let became_unhealthy = {
+        let mut registry = registry();
+        let Some(loaded) = registry.get_mut(id) else {
+            return;
+        };
+        loaded.health.record(outcome)
+    };
+
+    if became_unhealthy {
+        println!("🩺 Plugin {} failed {} health checks in a row", id, MAX_FAILURES);
+        let _ = app.emit("plugin-unhealthy", id);
+    }
+}
+
+/// Runs one health check, or `Ok(None)` if the plugin has no `health` export,
+/// is disabled, or is busy with another call. A check that traps or times out
+/// leaves the plugin to be re-instantiated, like any other call.
+async fn check(id: &str) -> Result<Option<HealthStatus>, PluginError> {
+    recovery::with_trap_retries(id, || check_once(id)).await
+}
+
+async fn check_once(id: &str) -> Result<Option<HealthStatus>, PluginError> {
+    let (instance, fuel_budget) = match plugin_instance(id) {
+        // Nobody is using a disabled plugin, so there's nothing to report
+        Err(PluginError::Disabled(_)) => return Ok(None),
+        result => result?,
+    };
+    // A plugin in the middle of a call is evidently alive; waiting for it would stall the poller
+    let Ok(mut guard) = instance.try_lock() else {
+        return Ok(None);
+    };
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let func = match dynamic::find_func(store, instance, HEALTH_EXPORT) {
+        Ok(func) => func,
+        Err(PluginError::NoSuchFunction(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    // The usual fuel budget, but a much shorter deadline than a normal call
+    let mut results = [Val::Bool(false)];
+    arm_call_limits(store, fuel_budget, HEALTH_EXPORT, Some(CHECK_TIMEOUT))?;
+    func.call_async(&mut *store, &[], &mut results)
+        .await
+        .map_err(PluginError::trap)?;
+    func.post_return_async(&mut *store)
+        .await
+        .map_err(PluginError::trap)?;
+
+    match &results[0] {
+        Val::Enum(name) => HealthStatus::from_name(name)
+            .map(Some)
+            .ok_or_else(|| PluginError::LinkError(format!("unknown health status {}", name))),
+        other => Err(PluginError::LinkError(format!("health returned {:?}", other))),
+    }
+}
@@ -0,0 +1,637 @@
+use sled::transaction::{ConflictableTransactionError, TransactionError, UnabortableTransactionError};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use wasmtime::component::Resource;
+use wasmtime_wasi::{ResourceTable, WasiCtx, WasiView};
+
+use crate::embedder::AppHandle;
+use crate::error::CapabilityDenied;
+use crate::handles::OpenFile;
+use crate::manifest::{Capability, EventSchema, LogThreshold};
+use crate::messaging::{Event, Message};
+use crate::pato::plugin_ui::chunks;
+use crate::pato::plugin_ui::config;
+use crate::pato::plugin_ui::db::{self, BatchError, DbOp};
+use crate::pato::plugin_ui::events;
+use crate::pato::plugin_ui::filter_helper;
+use crate::pato::plugin_ui::handles;
+use crate::pato::plugin_ui::host_info;
+use crate::pato::plugin_ui::http::{self, HttpError, HttpRequest, HttpResponse};
+use crate::pato::plugin_ui::log::{self as plugin_log, LogLevel};
+use crate::pato::plugin_ui::messaging;
+use crate::pato::plugin_ui::timers;
+use crate::ratelimit::HostRateLimiter;
+use crate::stream::Chunk;
+use crate::usage::UsageLimiter;
+
+/// Per-plugin state owned by the plugin's `Store`. Host interface calls from
+/// the plugin land here.
+pub struct PluginHost {
+    pub plugin_id: String,
+    pub app: AppHandle,
+    /// The app's version, answered by `host-info.version`
+    pub host_version: String,
+    pub wasi: WasiCtx,
+    pub table: ResourceTable,
+    pub limits: UsageLimiter,
+    /// What `get-config` answers from
+    pub config: HashMap<String, String>,
+    /// Plugin ids `send` may target, from the manifest's `send-to`
+    pub send_to: Vec<String>,
+    /// Topics `publish` may use, from the manifest's `publishes`
+    pub publishes: Vec<String>,
+    /// `log` messages below this are dropped, from the manifest's `log-level`
+    pub log_level: LogThreshold,
+    /// The plugin's own pato-db store, in its data directory; `None` without the storage capability
+    pub db: Option<sled::Db>,
+    /// From the manifest's `[events]`, checked by `emit-json`
+    pub event_schemas: HashMap<String, EventSchema>,
+    /// From the manifest's `allowed-hosts`, checked by `fetch`
+    pub allowed_hosts: Vec<String>,
+    /// Host side of `/data`, where `handle`s open their files
+    pub data_dir: PathBuf,
+    /// What the plugin was granted, checked by the host interfaces that need a capability
+    pub capabilities: Vec<Capability>,
+    /// How many `handle`s the plugin currently holds
+    pub open_handles: usize,
+    /// Set by `cancel_plugin_call` to stop the running call at the next epoch tick
+    pub cancel: Arc<AtomicBool>,
+    /// Epoch ticks the running call has left before it times out
+    pub ticks_left: u64,
+    /// From the manifest's `timeouts`: each function's own deadline, by name
+    pub function_timeouts: HashMap<String, Duration>,
+    /// The running call's function, when its deadline came from `function_timeouts`
+    pub timed_function: Option<String>,
+    /// One token bucket per rate-limited interface
+    pub rate_limits: HostRateLimiter,
+    /// Tags the running call's `emit-chunk` output
+    pub call_id: u64,
+    /// How many chunks the running call has emitted so far
+    pub chunks_sent: u64,
+}
+
+impl PluginHost {
+    /// Checked first by every host function that needs a capability: `log`,
+    /// `emit-event`, `emit-json`, and `emit-chunk` need `stdio`, `get-config` and
+    /// pato-db need `storage`, `timers` need `clock`, and `fetch` needs `network`.
+    /// Functions that return a `result` hand the plugin `CapabilityDenied`'s
+    /// message as their error; the rest behave as if there were nothing there
+    /// (`get` finds nothing, `schedule` returns timer 0, output goes nowhere).
+    /// `messaging` is gated by the manifest's `send-to` and `publishes`, and
+    /// `filter-helper` and `host-info` only compute answers. Filesystem access is
+    /// gated by which directories are preopened instead, so WASI reports missing
+    /// ones itself.
+    fn require(&self, capability: Capability) -> Result<(), String> {
+        if self.capabilities.contains(&capability) {
+            return Ok(());
+        }
+        let denied = CapabilityDenied(capability);
+        log::info!("[{}] {}", self.plugin_id, denied);
+        Err(denied.to_string())
+    }
+
+    /// The manifest's deadline for `function`, looked up by its full name
+    /// (`test#get-number`) and then by its bare name (`get-number`).
+    pub fn declared_timeout(&self, function: &str) -> Option<Duration> {
+        let bare = function.rsplit(['#', '/']).next().unwrap_or(function);
+        self.function_timeouts
+            .get(function)
+            .or_else(|| self.function_timeouts.get(bare))
+            .copied()
+    }
+
+    fn db(&self) -> Result<&sled::Db, String> {
+        self.require(Capability::Storage)?;
+        self.db.as_ref().ok_or_else(|| "plugin has no database".to_string())
+    }
+}
+
+impl WasiView for PluginHost {
+    fn ctx(&mut self) -> &mut WasiCtx {
+        &mut self.wasi
+    }
+
+    fn table(&mut self) -> &mut ResourceTable {
+        &mut self.table
+    }
+}
+
+impl plugin_log::Host for PluginHost {
+    fn log(&mut self, level: LogLevel, message: String) {
+        if self.require(Capability::Stdio).is_err() {
+            return;
+        }
+        let (threshold, level) = match level {
+            LogLevel::Trace => (LogThreshold::Trace, log::Level::Trace),
+            LogLevel::Debug => (LogThreshold::Debug, log::Level::Debug),
+            LogLevel::Info => (LogThreshold::Info, log::Level::Info),
+            LogLevel::Warn => (LogThreshold::Warn, log::Level::Warn),
+            LogLevel::Error => (LogThreshold::Error, log::Level::Error),
+        };
+        if threshold < self.log_level {
+            return;
+        }
+        // Only lines that would be written count against the limit
+        if !self.rate_limits.log.try_take() {
+            crate::metrics::record_throttled(&self.plugin_id, "log");
+            return;
+        }
+        // Tag every line with the plugin id so output from several plugins can be told apart
+        log::log!(target: "pato::plugin", level, "[{}] {}", self.plugin_id, message);
+    }
+}
+
+impl events::Host for PluginHost {
+    fn emit_event(&mut self, name: String, payload: String) {
+        if self.require(Capability::Stdio).is_err() {
+            return;
+        }
+        if !self.rate_limits.events.try_take() {
+            crate::metrics::record_throttled(&self.plugin_id, "events");
+            return;
+        }
+        // Namespace by plugin id so plugins can't collide with each other or with host events
+        let event = format!("plugin:{}:{}", self.plugin_id, name);
+        if let Err(e) = self.app.emit(&event, payload) {
+            log::warn!("[{}] Failed to emit event {}: {}", self.plugin_id, event, e);
+        }
+    }
+
+    fn emit_json(&mut self, name: String, payload: String) {
+        if self.require(Capability::Stdio).is_err() {
+            return;
+        }
+        if !self.rate_limits.events.try_take() {
+            crate::metrics::record_throttled(&self.plugin_id, "events");
+            return;
+        }
+        // Malformed payloads are dropped rather than trapping, so a bad event can't crash the call
+        let value: serde_json::Value = match serde_json::from_str(&payload) {
+            Ok(value) => value,
+            Err(e) => {
+                log::warn!("[{}] Dropped event {}: payload isn't JSON: {}", self.plugin_id, name, e);
+                return;
+            }
+        };
+        if let Some(schema) = self.event_schemas.get(&name) {
+            if let Err(e) = schema.check(&value) {
+                log::warn!("[{}] Dropped event {}: {}", self.plugin_id, name, e);
+                return;
+            }
+        }
+
+        // Sent as a JSON value, so listeners get an object rather than a string to parse
+        let event = format!("plugin:{}:{}", self.plugin_id, name);
+        if let Err(e) = self.app.emit(&event, value) {
+            log::warn!("[{}] Failed to emit event {}: {}", self.plugin_id, event, e);
+        }
+    }
+}
+
+impl config::Host for PluginHost {
+    fn get_config(&mut self, key: String) -> Option<String> {
+        self.require(Capability::Storage).ok()?;
+        self.config.get(&key).cloned()
+    }
+}
+
+impl messaging::Host for PluginHost {
+    fn send(&mut self, target_plugin: String, message: Vec<u8>) {
+        let allowed = self.send_to.iter().any(|target| target == "*" || *target == target_plugin);
+        if !allowed {
+            log::warn!(
+                "[{}] Dropped message to {}: not listed in the manifest's send-to",
+                self.plugin_id,
+                target_plugin
+            );
+            return;
+        }
+
+        // Delivered later by the messaging task, never re-entering a store from here
+        crate::messaging::enqueue(Message {
+            from: self.plugin_id.clone(),
+            to: target_plugin,
+            body: message,
+        });
+    }
+
+    fn publish(&mut self, topic: String, payload: Vec<u8>) {
+        if !self.publishes.contains(&topic) {
+            log::warn!(
+                "[{}] Dropped event on {}: not listed in the manifest's publishes",
+                self.plugin_id,
+                topic
+            );
+            return;
+        }
+
+        // Subscribers are looked up when the event is delivered, not here
+        crate::messaging::publish(Event {
+            from: self.plugin_id.clone(),
+            topic,
+            payload,
+        });
+    }
+}
+
+#[async_trait::async_trait]
+impl http::Host for PluginHost {
+    async fn fetch(&mut self, request: HttpRequest) -> Result<HttpResponse, HttpError> {
+        let url = request.url.clone();
+        self.require(Capability::Network).map_err(HttpError::Blocked)?;
+        if !self.rate_limits.http.try_take() {
+            crate::metrics::record_throttled(&self.plugin_id, "http");
+            return Err(HttpError::Blocked("rate limited".to_string()));
+        }
+        let result = crate::http::fetch(&self.allowed_hosts, request).await;
+        match &result {
+            Ok(response) => log::debug!("[{}] fetch {} -> {}", self.plugin_id, url, response.status),
+            Err(e) => log::info!("[{}] fetch {} failed: {:?}", self.plugin_id, url, e),
+        }
+        result
+    }
+}
+
+impl chunks::Host for PluginHost {
+    fn emit_chunk(&mut self, data: Vec<u8>) {
+        if self.require(Capability::Stdio).is_err() {
+            return;
+        }
+        crate::stream::forward(Chunk {
+            plugin: self.plugin_id.clone(),
+            call_id: self.call_id,
+            index: self.chunks_sent,
+            data,
+        });
+        self.chunks_sent += 1;
+    }
+}
+
+impl handles::Host for PluginHost {}
+
+impl handles::HostHandle for PluginHost {
+    fn open(&mut self, name: String) -> Result<Resource<OpenFile>, String> {
+        self.require(Capability::Storage)?;
+        let file = OpenFile::open(&self.data_dir, &name)?;
+        let handle = self.table.push(file).map_err(|e| e.to_string())?;
+        self.open_handles += 1;
+        Ok(handle)
+    }
+
+    fn read(&mut self, handle: Resource<OpenFile>, max: u32) -> Result<Vec<u8>, String> {
+        self.table.get_mut(&handle).map_err(|e| e.to_string())?.read(max)
+    }
+
+    fn write(&mut self, handle: Resource<OpenFile>, bytes: Vec<u8>) -> Result<u32, String> {
+        self.table.get_mut(&handle).map_err(|e| e.to_string())?.write(&bytes)
+    }
+
+    fn close(&mut self, handle: Resource<OpenFile>) {
+        if let Ok(file) = self.table.get_mut(&handle) {
+            file.file = None;
+        }
+    }
+
+    fn drop(&mut self, handle: Resource<OpenFile>) -> wasmtime::Result<()> {
+        self.table.delete(handle)?;
+        self.open_handles -= 1;
+        Ok(())
+    }
+}
+
+impl timers::Host for PluginHost {
+    fn schedule(&mut self, delay_ms: u64, periodic: bool) -> u64 {
+        // Timer ids start at 1, so 0 never names a real timer
+        if self.require(Capability::Clock).is_err() {
+            return 0;
+        }
+        crate::timers::schedule(&self.plugin_id, delay_ms, periodic)
+    }
+
+    fn cancel(&mut self, id: u64) {
+        if self.require(Capability::Clock).is_ok() {
+            crate::timers::cancel(&self.plugin_id, id)
+        }
+    }
+}
+
+// The plugin's store is borrowed by the `filter` call this runs inside, so this
+// must stay on host state; locking the plugin's instance here would deadlock
+impl filter_helper::Host for PluginHost {
+    fn matches(&mut self, text: String, pattern: String) -> bool {
+        crate::filter::matches(&text, &pattern)
+    }
+}
+
+impl host_info::Host for PluginHost {
+    fn version(&mut self) -> String {
+        self.host_version.clone()
+    }
+
+    fn platform(&mut self) -> String {
+        format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+    }
+
+    fn plugin_id(&mut self) -> String {
+        self.plugin_id.clone()
+    }
+}
+
+impl db::Host for PluginHost {
+    fn get(&mut self, key: String) -> Option<Vec<u8>> {
+        match self.db().and_then(|db| db.get(key.as_bytes()).map_err(|e| e.to_string())) {
+            Ok(value) => value.map(|value| value.to_vec()),
+            Err(e) => {
+                log::warn!("[{}] Failed to read {}: {}", self.plugin_id, key, e);
+                None
+            }
+        }
+    }
+
+    fn set(&mut self, key: String, value: Vec<u8>) -> Result<(), String> {
+        let max = crate::database::max_value_bytes();
+        if value.len() > max {
+            return Err(format!("value is {} bytes, over the {} byte limit", value.len(), max));
+        }
+        self.db()?
+            .insert(key.as_bytes(), value)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    fn delete(&mut self, key: String) {
+        if let Err(e) = self.db().and_then(|db| db.remove(key.as_bytes()).map_err(|e| e.to_string())) {
+            log::warn!("[{}] Failed to delete {}: {}", self.plugin_id, key, e);
+        }
+    }
+
+    fn list_keys(&mut self, prefix: String) -> Vec<String> {
+        let Ok(db) = self.db() else {
+            return Vec::new();
+        };
+        // Keys are only ever written from strings, so anything else isn't ours to report
+        db.scan_prefix(prefix.as_bytes())
+            .keys()
+            .filter_map(|key| key.ok())
+            .filter_map(|key| String::from_utf8(key.to_vec()).ok())
+            .collect()
+    }
+
+    fn batch(&mut self, ops: Vec<DbOp>) -> Result<(), BatchError> {
+        let batch_error = |index: usize, message: String| BatchError {
+            index: index as u32,
+            message,
+        };
+
+        // Check sizes up front so an oversized value never starts a transaction
+        let max = crate::database::max_value_bytes();
+        for (index, op) in ops.iter().enumerate() {
+            if let DbOp::Set(set) = op {
+                if set.value.len() > max {
+                    let message = format!("value is {} bytes, over the {} byte limit", set.value.len(), max);
+                    return Err(batch_error(index, message));
+                }
+            }
+        }
+
+        let db = self.db().map_err(|e| batch_error(0, e))?;
+
+        // Conflicts are retried by sled; a storage failure aborts with the failing index
+        let result = db.transaction(|tx| {
+            for (index, op) in ops.iter().enumerate() {
+                let applied = match op {
+                    DbOp::Set(set) => tx.insert(set.key.as_bytes(), set.value.as_slice()),
+                    DbOp::Delete(key) => tx.remove(key.as_bytes()),
+                };
+                applied.map_err(|e| match e {
+                    UnabortableTransactionError::Conflict => ConflictableTransactionError::Conflict,
+                    UnabortableTransactionError::Storage(e) => {
+                        ConflictableTransactionError::Abort(batch_error(index, e.to_string()))
+                    }
+                })?;
+            }
+            Ok(())
+        });
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(TransactionError::Abort(e)) => Err(e),
+            // Only reachable if committing failed, after every operation was staged
+            Err(TransactionError::Storage(e)) => Err(batch_error(ops.len().saturating_sub(1), e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pato::plugin_ui::db::{DbSet, Host as _};
+    use crate::testing::{with_host, TestApp, ANSWER};
+
+    fn set(key: &str, value: &[u8]) -> DbOp {
+        DbOp::Set(DbSet {
+            key: key.to_string(),
+            value: value.to_vec(),
+        })
+    }
+
+    #[tokio::test]
+    async fn batches_apply_every_operation() {
+        let app = TestApp::new();
+        let id = app.load("host-batch", ANSWER).await.unwrap();
+        with_host(&id, |host| {
+            let ops = vec![set("a", b"1"), set("b", b"2"), DbOp::Delete("a".to_string())];
+            assert!(host.batch(ops).is_ok());
+            assert_eq!(host.get("a".to_string()), None);
+            assert_eq!(host.get("b".to_string()), Some(b"2".to_vec()));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn a_failing_batch_writes_nothing() {
+        let app = TestApp::new();
+        let id = app.load("host-batch-fails", ANSWER).await.unwrap();
+        with_host(&id, |host| {
+            let too_big = vec![0; crate::database::max_value_bytes() + 1];
+            let error = host.batch(vec![set("first", b"1"), set("second", &too_big)]).unwrap_err();
+            assert_eq!(error.index, 1);
+            assert_eq!(host.get("first".to_string()), None);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn calls_without_the_capability_are_denied_by_name() {
+        let app = TestApp::new();
+        let manifest = "id = \"host-denied\"\nname = \"Denied\"\nversion = \"1.0.0\"\ncapabilities = [\"stdio\"]";
+        let id = app.load_file("host-denied.wasm", ANSWER, Some(manifest)).await.unwrap();
+        with_host(&id, |host| {
+            assert_eq!(host.set("key".to_string(), b"value".to_vec()), Err("capability-denied: storage".to_string()));
+            let error = host.batch(vec![set("key", b"value")]).unwrap_err();
+            assert!(error.message.starts_with("capability-denied:"), "{}", error.message);
+            // Functions without an error to return just come up empty
+            assert_eq!(host.get("key".to_string()), None);
+            assert!(host.list_keys(String::new()).is_empty());
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn output_config_and_timers_need_their_capabilities() {
+        use crate::pato::plugin_ui::config::Host as _;
+        use crate::pato::plugin_ui::events::Host as _;
+        use crate::pato::plugin_ui::chunks::Host as _;
+        use crate::pato::plugin_ui::timers::Host as _;
+
+        capture_logs();
+        let app = TestApp::new();
+        let manifest = "id = \"host-withheld\"\nname = \"Withheld\"\nversion = \"1.0.0\"\n[config]\ngreeting = \"hi\"";
+        let id = app.load_file("host-withheld.wasm", ANSWER, Some(manifest)).await.unwrap();
+        with_host(&id, |host| {
+            plugin_log::Host::log(host, LogLevel::Error, "unheard".to_string());
+            host.emit_event("unseen".to_string(), String::new());
+            host.emit_json("unseen".to_string(), "{}".to_string());
+            host.emit_chunk(b"unsent".to_vec());
+            assert_eq!(host.get_config("greeting".to_string()), None);
+            assert_eq!(host.schedule(1_000, false), 0);
+        })
+        .await;
+
+        assert!(!LINES.lock().unwrap().contains(&"[host-withheld] unheard".to_string()));
+        assert!(app.emitted("plugin:host-withheld:unseen").is_empty());
+        assert!(crate::timers::scheduled(&id).is_empty());
+    }
+
+    // `test#count-preopens` counts the directories WASI hands the plugin
+    const COUNTS_PREOPENS: &str = r#"
+    (component $C
+      (import "wasi:filesystem/types@0.2.0" (instance $types
+        (export "descriptor" (type (sub resource)))))
+      (alias export $types "descriptor" (type $descriptor))
+      (type $preopens (instance
+        (alias outer $C $descriptor (type $d))
+        (export "descriptor" (type $exported (eq $d)))
+        (type $own (own $exported))
+        (type $entry (tuple $own string))
+        (type $entries (list $entry))
+        (export "get-directories" (func (result $entries)))))
+      (import "wasi:filesystem/preopens@0.2.0" (instance $preopens (type $preopens)))
+      (core module $memory
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+          (local $ptr i32)
+          (local.set $ptr (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+          (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+          (local.get $ptr)))
+      (core instance $memory (instantiate $memory))
+      (core func $get-directories
+        (canon lower (func $preopens "get-directories") (memory $memory "memory") (realloc (func $memory "realloc"))))
+      (core instance $host (export "get-directories" (func $get-directories)))
+      (core module $m
+        (import "env" "memory" (memory 1))
+        (import "host" "get-directories" (func $get-directories (param i32)))
+        (func (export "count-preopens") (result i32)
+          (call $get-directories (i32.const 0))
+          (i32.load (i32.const 4))))
+      (core instance $i (instantiate $m (with "env" (instance $memory)) (with "host" (instance $host))))
+      (func $count (result u32) (canon lift (core func $i "count-preopens")))
+      (instance $test (export "count-preopens" (func $count)))
+      (export "test" (instance $test)))
+    "#;
+
+    #[tokio::test]
+    async fn the_filesystem_is_only_there_when_granted() {
+        let app = TestApp::new();
+        let preopens = |id: &'static str, capabilities: &'static str| {
+            let manifest = format!("id = \"{}\"\nname = \"Files\"\nversion = \"1.0.0\"\ncapabilities = {}", id, capabilities);
+            let app = &app;
+            async move {
+                let id = app.load_file(&format!("{}.wasm", id), COUNTS_PREOPENS, Some(&manifest)).await.unwrap();
+                crate::dynamic::invoke(&id, "test#count-preopens", serde_json::Value::Null, None).await.unwrap()
+            }
+        };
+
+        // Only `/plugin`; without the capability, there's no directory to open at all
+        assert_eq!(preopens("host-files-granted", "[\"filesystem\"]").await, 1);
+        assert_eq!(preopens("host-files-denied", "[\"stdio\"]").await, 0);
+    }
+
+    // Keeps the plugin log lines that reach the `log` crate
+    struct Captured;
+
+    static LINES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    impl log::Log for Captured {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == "pato::plugin"
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                LINES.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn capture_logs() {
+        // Whichever test gets here first installs it for all of them
+        let _ = log::set_logger(&Captured);
+        log::set_max_level(log::LevelFilter::Trace);
+    }
+
+    #[tokio::test]
+    async fn logs_below_the_plugins_level_are_dropped() {
+        capture_logs();
+        let app = TestApp::new();
+        let manifest = "id = \"host-log-level\"\nname = \"Quiet\"\nversion = \"1.0.0\"\nprofile = \"isolated\"\nlog-level = \"warn\"";
+        let id = app.load_file("host-log-level.wasm", ANSWER, Some(manifest)).await.unwrap();
+        with_host(&id, |host| {
+            plugin_log::Host::log(host, LogLevel::Info, "quiet".to_string());
+            plugin_log::Host::log(host, LogLevel::Error, "loud".to_string());
+        })
+        .await;
+
+        let lines = LINES.lock().unwrap();
+        assert!(lines.contains(&"[host-log-level] loud".to_string()));
+        assert!(!lines.contains(&"[host-log-level] quiet".to_string()));
+    }
+
+    #[tokio::test]
+    async fn dropped_logs_dont_use_up_the_rate_limit() {
+        capture_logs();
+        let app = TestApp::new();
+        let manifest = "id = \"host-log-limit\"\nname = \"Chatty\"\nversion = \"1.0.0\"\nprofile = \"isolated\"\nlog-level = \"warn\"";
+        let id = app.load_file("host-log-limit.wasm", ANSWER, Some(manifest)).await.unwrap();
+        with_host(&id, |host| {
+            host.rate_limits.log = crate::ratelimit::TokenBucket::new(1);
+            for _ in 0..10 {
+                plugin_log::Host::log(host, LogLevel::Debug, "chatter".to_string());
+            }
+            plugin_log::Host::log(host, LogLevel::Warn, "warning".to_string());
+        })
+        .await;
+
+        assert!(LINES.lock().unwrap().contains(&"[host-log-limit] warning".to_string()));
+        assert!(crate::registry().get(&id).unwrap().throttled.is_empty());
+    }
+
+    #[tokio::test]
+    async fn host_info_describes_the_app_and_the_plugin() {
+        use crate::pato::plugin_ui::host_info::Host as _;
+
+        let app = TestApp::new();
+        let id = app.load("host-info", ANSWER).await.unwrap();
+        let (version, platform, plugin_id) =
+            with_host(&id, |host| (host.version(), host.platform(), host.plugin_id())).await;
+        assert_eq!(version, "0.0.0-test");
+        assert_eq!(platform, format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH));
+        assert_eq!(plugin_id, id);
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::pato::plugin_ui::http::{HttpError, HttpRequest, HttpResponse};
+
+// Default cap on a fetched response body, overridable with PATO_HTTP_MAX_BYTES
+const DEFAULT_MAX_RESPONSE_BYTES: u64 = 10 * 1024 * 1024;
+
+// Default limit on a whole fetch, overridable with PATO_HTTP_TIMEOUT_MS
+const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+
+// Shared so plugins reuse connections; redirects could leave the allowlist, so they're off
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap_or_default()
+});
+
+/// Makes `request` for a plugin, if its host is in `allowed_hosts`.
+pub async fn fetch(allowed_hosts: &[String], request: HttpRequest) -> Result<HttpResponse, HttpError> {
+    let url = reqwest::Url::parse(&request.url).map_err(|e| HttpError::Failed(format!("{}: {}", request.url, e)))?;
+    let host = url.host_str().unwrap_or_default();
+    if !is_allowed(allowed_hosts, host) {
+        return Err(HttpError::Blocked(host.to_string()));
+    }
+    let method = reqwest::Method::from_bytes(request.method.to_ascii_uppercase().as_bytes())
+        .map_err(|e| HttpError::Failed(format!("{}: {}", request.method, e)))?;
+
+    let mut builder = CLIENT.request(method, url);
+    for (name, value) in request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Some(body) = request.body {
+        builder = builder.body(body);
+    }
+
+    match tokio::time::timeout(timeout(), send(builder)).await {
+        Ok(result) => result,
+        Err(_) => Err(HttpError::Timeout),
+    }
+}
+
+async fn send(builder: reqwest::RequestBuilder) -> Result<HttpResponse, HttpError> {
+    let failed = |e: reqwest::Error| HttpError::Failed(e.to_string());
+    let max = max_response_bytes();
+
+    let mut response = builder.send().await.map_err(failed)?;
+    if response.content_length().is_some_and(|length| length > max) {
+        return Err(HttpError::TooLarge(max));
+    }
+
+    let status = response.status();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+
+    // Content-Length can be missing or wrong, so count as the body arrives too
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(failed)? {
+        if body.len() as u64 + chunk.len() as u64 > max {
+            return Err(HttpError::TooLarge(max));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let response = HttpResponse {
+        status: status.as_u16(),
+        headers,
+        body,
+    };
+    if status.is_success() {
+        Ok(response)
+    } else {
+        Err(HttpError::BadStatus(response))
+    }
+}
+
+/// Matches `host` against entries like `api.example.com`, or `*.example.com` for any subdomain.
+fn is_allowed(allowed_hosts: &[String], host: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    allowed_hosts.iter().any(|allowed| {
+        let allowed = allowed.to_ascii_lowercase();
+        match allowed.strip_prefix("*.") {
+            Some(domain) => host.ends_with(&format!(".{}", domain)),
+            None => host == allowed,
+        }
+    })
+}
+
+fn max_response_bytes() -> u64 {
+    std::env::var("PATO_HTTP_MAX_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES)
+}
+
+fn timeout() -> Duration {
+    let millis = std::env::var("PATO_HTTP_TIMEOUT_MS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_TIMEOUT_MS);
+    Duration::from_millis(millis)
+}
@@ -0,0 +1,166 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::registry::PluginInfo;
+use crate::{is_plugin_file, load_plugin, registry, shared_engine, validate, warmup, PLUGINS_DIR};
+
+/// Downloads at least this big get `plugin-install-progress` events.
+const PROGRESS_THRESHOLD: u64 = 1024 * 1024;
+
+/// How much has to arrive between two progress events.
+const PROGRESS_STEP: u64 = 256 * 1024;
+
+/// Emitted as `plugin-install-progress` while a large plugin downloads.
+#[derive(Clone, Serialize)]
+pub struct InstallProgress {
+    pub url: String,
+    pub received: u64,
+    /// From `Content-Length`, when the server sent one
+    pub total: Option<u64>,
+}
+
+/// Downloads the `.wasm` or `.patopkg` at `url`, checks it against `<url>.sha256`
+/// if the server has one, validates it, then copies it into the plugins
+/// directory, loads it, and warms it up, so the first call doesn't pay for
+/// compiling, instantiating, or `init`.
+///
+/// Only `https` URLs are accepted unless PATO_ALLOW_INSECURE_INSTALL is set.
+pub async fn install_from_url(app: &AppHandle, url: &str) -> Result<PluginInfo, PluginError> {
+    let url = reqwest::Url::parse(url).map_err(|e| PluginError::Download(format!("{}: {}", url, e)))?;
+    if url.scheme() != "https" && !allow_insecure() {
+        return Err(PluginError::Download(format!("refusing to install over {}: {}", url.scheme(), url)));
+    }
+
+    let file_name = url
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| is_plugin_file(Path::new(name)))
+        .ok_or_else(|| PluginError::Download(format!("{} isn't a .wasm or .patopkg file", url)))?
+        .to_string();
+
+    let plugins_dir = PLUGINS_DIR
+        .get()
+        .ok_or_else(|| PluginError::Io("there's no plugins directory".to_string()))?;
+    let destination = plugins_dir.join(&file_name);
+    if destination.exists() {
+        return Err(PluginError::Io(format!("{:?} is already installed", destination)));
+    }
+
+    println!("⬇️ Downloading plugin from {}", url);
+    let client = reqwest::Client::new();
+    let bytes = download(app, &client, &url).await?;
+    verify_checksum(&client, &url, &bytes).await?;
+
+    // Validate a staged copy, so a bad download never lands in the watched directory
+    let staged = stage(app, &file_name, &bytes)?;
+    let validation = validate::validate_plugin(staged.clone()).await;
+    let validation = match validation {
+        Ok(validation) => validation,
+        Err(e) => {
+            let _ = std::fs::remove_file(&staged);
+            return Err(e);
+        }
+    };
+    if !validation.missing.is_empty() {
+        let _ = std::fs::remove_file(&staged);
+        return Err(PluginError::NoSuchFunction(validation.missing.join(", ")));
+    }
+
+    // A rename can't cross filesystems, and the cache dir may be on another one
+    std::fs::copy(&staged, &destination).map_err(|e| PluginError::Io(format!("{:?}: {}", destination, e)))?;
+    let _ = std::fs::remove_file(&staged);
+
+    let engine = shared_engine()?;
+    let id = load_plugin(app, engine, &destination).await?;
+    // Loading instantiated it and ran `init`; this resolves its exports too
+    if let Err(e) = warmup::warmup(&id).await {
+        println!("⚠️ Couldn't warm up plugin {}: {}", id, e);
+    }
+    println!("✅ Installed plugin {} from {}", id, url);
+    let _ = app.emit("plugin-loaded", &id);
+
+    registry()
+        .list_info()
+        .into_iter()
+        .find(|info| info.id == id)
+        .ok_or(PluginError::NotLoaded(id))
+}
+
+async fn download(app: &AppHandle, client: &reqwest::Client, url: &reqwest::Url) -> Result<Vec<u8>, PluginError> {
+    let failed = |e: reqwest::Error| PluginError::Download(format!("{}: {}", url, e));
+
+    let mut response = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(failed)?;
+
+    let total = response.content_length();
+    let report = total.unwrap_or(0) >= PROGRESS_THRESHOLD;
+    let mut bytes = Vec::with_capacity(total.unwrap_or(0) as usize);
+    let mut reported = 0;
+    while let Some(chunk) = response.chunk().await.map_err(failed)? {
+        bytes.extend_from_slice(&chunk);
+        let received = bytes.len() as u64;
+        if report && received - reported >= PROGRESS_STEP {
+            reported = received;
+            let progress = InstallProgress { url: url.to_string(), received, total };
+            let _ = app.emit("plugin-install-progress", progress);
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Checks `bytes` against the digest at `<url>.sha256`, in the usual `sha256sum`
+/// format. A missing checksum file is fine; a mismatched one isn't.
+async fn verify_checksum(client: &reqwest::Client, url: &reqwest::Url, bytes: &[u8]) -> Result<(), PluginError> {
+    let mut checksum_url = url.clone();
+    checksum_url.set_path(&format!("{}.sha256", url.path()));
+    let failed = |e: reqwest::Error| PluginError::Download(format!("{}: {}", checksum_url, e));
+
+    let response = client.get(checksum_url.clone()).send().await.map_err(failed)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(());
+    }
+    let text = response
+        .error_for_status()
+        .map_err(failed)?
+        .text()
+        .await
+        .map_err(failed)?;
+
+    let expected = text.split_whitespace().next().unwrap_or_default().to_ascii_lowercase();
+    let actual: String = Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect();
+    if expected != actual {
+        return Err(PluginError::Download(format!(
+            "{} has sha256 {}, but {} says {}",
+            url, actual, checksum_url, expected
+        )));
+    }
+
+    println!("🔒 Checksum matches for {}", url);
+    Ok(())
+}
+
+/// Writes a download to `<app cache>/plugin-downloads` for validation.
+fn stage(app: &AppHandle, file_name: &str, bytes: &[u8]) -> Result<PathBuf, PluginError> {
+    let dir = app
+        .app_cache_dir()
+        .ok_or_else(|| PluginError::Io("the app has no cache directory".to_string()))?
+        .join("plugin-downloads");
+    std::fs::create_dir_all(&dir).map_err(|e| PluginError::Io(format!("{:?}: {}", dir, e)))?;
+
+    let path = dir.join(file_name);
+    std::fs::write(&path, bytes).map_err(|e| PluginError::Io(format!("{:?}: {}", path, e)))?;
+    Ok(path)
+}
+
+fn allow_insecure() -> bool {
+    std::env::var("PATO_ALLOW_INSECURE_INSTALL")
+        .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
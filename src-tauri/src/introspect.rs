@@ -0,0 +1,262 @@
+use serde::Serialize;
+use std::fmt::{self, Write};
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, Type};
+use wasmtime::Engine;
+
+/// An exported function and its signature, for building call forms in the UI.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportInfo {
+    /// `func` at the top level, or `interface#func`; what `invoke_export` takes
+    pub name: String,
+    pub params: Vec<Param>,
+    pub results: Vec<WitType>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Param {
+    /// Positional, e.g. `arg0`; the engine doesn't expose the declared names
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: WitType,
+}
+
+/// A component value type, mirroring WIT.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum WitType {
+    Bool,
+    S8,
+    S16,
+    S32,
+    S64,
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+    List { element: Box<WitType> },
+    Record { fields: Vec<Field> },
+    Tuple { types: Vec<WitType> },
+    Variant { cases: Vec<Case> },
+    Enum { cases: Vec<String> },
+    Option { inner: Box<WitType> },
+    Result { ok: Option<Box<WitType>>, err: Option<Box<WitType>> },
+    Flags { names: Vec<String> },
+    /// An owned or borrowed resource handle, which can't be built from JSON
+    Resource,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: WitType,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Case {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: Option<WitType>,
+}
+
+/// Describes every function the component exports, at the top level and one
+/// level down inside exported interfaces.
+pub fn describe_exports(engine: &Engine, component: &Component) -> Vec<ExportInfo> {
+    let mut exports = Vec::new();
+    for (name, item) in component.component_type().exports(engine) {
+        match item {
+            ComponentItem::ComponentFunc(func) => exports.push(describe_func(name.to_string(), &func)),
+            ComponentItem::ComponentInstance(instance) => {
+                for (func_name, item) in instance.exports(engine) {
+                    if let ComponentItem::ComponentFunc(func) = item {
+                        exports.push(describe_func(format!("{}#{}", name, func_name), &func));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    exports
+}
+
+/// A WIT-like listing of everything the component imports and exports, for
+/// showing as plugin documentation. Types are written out inline, since the
+/// component doesn't keep the names they were declared with, so it won't match
+/// the plugin's `.wit` byte for byte.
+pub fn describe_interface(engine: &Engine, component: &Component) -> String {
+    let component_type = component.component_type();
+    let mut text = String::new();
+    for (name, item) in component_type.imports(engine) {
+        write_item(&mut text, engine, "import", name, &item);
+    }
+    for (name, item) in component_type.exports(engine) {
+        write_item(&mut text, engine, "export", name, &item);
+    }
+    text
+}
+
+// Functions get a line of their own; interfaces list their functions inside braces
+fn write_item(text: &mut String, engine: &Engine, keyword: &str, name: &str, item: &ComponentItem) {
+    match item {
+        ComponentItem::ComponentFunc(func) => {
+            let _ = writeln!(text, "{} {}: {};", keyword, name, describe_func(name.to_string(), func).signature());
+        }
+        ComponentItem::ComponentInstance(instance) => {
+            let _ = writeln!(text, "{} {} {{", keyword, name);
+            for (func_name, item) in instance.exports(engine) {
+                if let ComponentItem::ComponentFunc(func) = item {
+                    let func = describe_func(func_name.to_string(), &func);
+                    let _ = writeln!(text, "  {}: {};", func_name, func.signature());
+                }
+            }
+            let _ = writeln!(text, "}}");
+        }
+        _ => {}
+    }
+}
+
+impl ExportInfo {
+    /// The function's type as WIT writes it, e.g. `func(input: string) -> u32`.
+    pub fn signature(&self) -> String {
+        let params: Vec<String> = self.params.iter().map(|param| format!("{}: {}", param.name, param.ty)).collect();
+        let mut signature = format!("func({})", params.join(", "));
+        match self.results.as_slice() {
+            [] => {}
+            [single] => {
+                let _ = write!(signature, " -> {}", single);
+            }
+            many => {
+                let results: Vec<String> = many.iter().map(WitType::to_string).collect();
+                let _ = write!(signature, " -> tuple<{}>", results.join(", "));
+            }
+        }
+        signature
+    }
+}
+
+fn describe_func(name: String, func: &wasmtime::component::types::ComponentFunc) -> ExportInfo {
+    ExportInfo {
+        name,
+        params: func
+            .params()
+            .enumerate()
+            .map(|(index, ty)| Param {
+                name: format!("arg{}", index),
+                ty: WitType::from(&ty),
+            })
+            .collect(),
+        results: func.results().map(|ty| WitType::from(&ty)).collect(),
+    }
+}
+
+impl From<&Type> for WitType {
+    fn from(ty: &Type) -> Self {
+        match ty {
+            Type::Bool => WitType::Bool,
+            Type::S8 => WitType::S8,
+            Type::S16 => WitType::S16,
+            Type::S32 => WitType::S32,
+            Type::S64 => WitType::S64,
+            Type::U8 => WitType::U8,
+            Type::U16 => WitType::U16,
+            Type::U32 => WitType::U32,
+            Type::U64 => WitType::U64,
+            Type::Float32 => WitType::F32,
+            Type::Float64 => WitType::F64,
+            Type::Char => WitType::Char,
+            Type::String => WitType::String,
+            Type::List(list) => WitType::List {
+                element: Box::new(WitType::from(&list.ty())),
+            },
+            Type::Record(record) => WitType::Record {
+                fields: record
+                    .fields()
+                    .map(|field| Field {
+                        name: field.name.to_string(),
+                        ty: WitType::from(&field.ty),
+                    })
+                    .collect(),
+            },
+            Type::Tuple(tuple) => WitType::Tuple {
+                types: tuple.types().map(|ty| WitType::from(&ty)).collect(),
+            },
+            Type::Variant(variant) => WitType::Variant {
+                cases: variant
+                    .cases()
+                    .map(|case| Case {
+                        name: case.name.to_string(),
+                        ty: case.ty.as_ref().map(WitType::from),
+                    })
+                    .collect(),
+            },
+            Type::Enum(enum_ty) => WitType::Enum {
+                cases: enum_ty.names().map(str::to_string).collect(),
+            },
+            Type::Option(option) => WitType::Option {
+                inner: Box::new(WitType::from(&option.ty())),
+            },
+            Type::Result(result) => WitType::Result {
+                ok: result.ok().map(|ty| Box::new(WitType::from(&ty))),
+                err: result.err().map(|ty| Box::new(WitType::from(&ty))),
+            },
+            Type::Flags(flags) => WitType::Flags {
+                names: flags.names().map(str::to_string).collect(),
+            },
+            Type::Own(_) | Type::Borrow(_) => WitType::Resource,
+        }
+    }
+}
+
+impl fmt::Display for WitType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WitType::Bool => write!(f, "bool"),
+            WitType::S8 => write!(f, "s8"),
+            WitType::S16 => write!(f, "s16"),
+            WitType::S32 => write!(f, "s32"),
+            WitType::S64 => write!(f, "s64"),
+            WitType::U8 => write!(f, "u8"),
+            WitType::U16 => write!(f, "u16"),
+            WitType::U32 => write!(f, "u32"),
+            WitType::U64 => write!(f, "u64"),
+            WitType::F32 => write!(f, "f32"),
+            WitType::F64 => write!(f, "f64"),
+            WitType::Char => write!(f, "char"),
+            WitType::String => write!(f, "string"),
+            WitType::List { element } => write!(f, "list<{}>", element),
+            WitType::Record { fields } => {
+                let fields: Vec<String> = fields.iter().map(|field| format!("{}: {}", field.name, field.ty)).collect();
+                write!(f, "record {{ {} }}", fields.join(", "))
+            }
+            WitType::Tuple { types } => {
+                let types: Vec<String> = types.iter().map(WitType::to_string).collect();
+                write!(f, "tuple<{}>", types.join(", "))
+            }
+            WitType::Variant { cases } => {
+                let cases: Vec<String> = cases
+                    .iter()
+                    .map(|case| match &case.ty {
+                        Some(ty) => format!("{}({})", case.name, ty),
+                        None => case.name.clone(),
+                    })
+                    .collect();
+                write!(f, "variant {{ {} }}", cases.join(", "))
+            }
+            WitType::Enum { cases } => write!(f, "enum {{ {} }}", cases.join(", ")),
+            WitType::Option { inner } => write!(f, "option<{}>", inner),
+            WitType::Result { ok, err } => match (ok, err) {
+                (None, None) => write!(f, "result"),
+                (Some(ok), None) => write!(f, "result<{}>", ok),
+                (None, Some(err)) => write!(f, "result<_, {}>", err),
+                (Some(ok), Some(err)) => write!(f, "result<{}, {}>", ok, err),
+            },
+            WitType::Flags { names } => write!(f, "flags {{ {} }}", names.join(", ")),
+            WitType::Resource => write!(f, "resource"),
+        }
+    }
+}
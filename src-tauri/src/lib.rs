@@ -0,0 +1,1773 @@
+// Without Tauri there's no app for the commands and `run` to drive, and they
+// are the only callers of much of the runtime
+#![cfg_attr(not(feature = "tauri"), allow(dead_code, unused_imports))]
+
+mod async_runtime;
+mod cache;
+#[cfg(feature = "tauri")]
+mod cli;
+#[cfg(feature = "tauri")]
+mod commands;
+mod config;
+mod database;
+mod deps;
+mod disabled;
+mod dynamic;
+pub mod embedder;
+mod epoch;
+pub mod error;
+mod filter;
+mod handles;
+mod health;
+mod http;
+mod host;
+mod install;
+mod introspect;
+mod lifecycle;
+pub mod manifest;
+mod messaging;
+mod metrics;
+mod output;
+mod package;
+mod permissions;
+mod pipeline;
+mod recovery;
+mod ratelimit;
+mod ready;
+pub mod registry;
+pub mod runtime;
+mod sandbox;
+pub mod services;
+mod signing;
+mod snapshot;
+mod stream;
+#[cfg(test)]
+mod testing;
+mod timers;
+mod ui;
+mod usage;
+mod validate;
+mod version;
+mod warmup;
+mod watcher;
+
+use embedder::AppHandle;
+use error::PluginError;
+use host::PluginHost;
+use manifest::{Capability, PluginManifest};
+use registry::{LoadSummary, LoadedPlugin, PluginInstance, PluginRegistry, ReloadFailure, ReloadSummary};
+use runtime::{MissingDirMode, PluginRuntime};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+#[cfg(feature = "tauri")]
+use tauri::{Emitter, Manager};
+use wasmtime::component::{Component, Instance};
+use wasmtime::{Config, Engine, Store, StoreLimitsBuilder, WasmBacktraceDetails};
+
+// Generate bindings for the plugin WIT interface
+wasmtime::component::bindgen!({
+    world: "plugin",
+    path: "../plugin-ui/wit/world.wit",
+    // Exports are called with `call_async`; host imports stay synchronous,
+    // except `fetch`, which awaits the network
+    async: {
+        only_imports: ["fetch"],
+    },
+    // `handle` resources are backed by real files in the store's resource table
+    with: {
+        "pato:plugin-ui/handles/handle": crate::handles::OpenFile,
+    },
+});
+
+// Global plugin registry, keyed by plugin id (the manifest's, or derived from the file)
+static PLUGIN_REGISTRY: LazyLock<Mutex<PluginRegistry>> = LazyLock::new(|| Mutex::new(PluginRegistry::new()));
+
+/// Locks the plugin registry, recovering it if an earlier holder panicked.
+fn registry() -> MutexGuard<'static, PluginRegistry> {
+    // Every change to the registry is a single map operation, so a panic while the lock
+    // was held can't leave it half-updated. Carry on instead of failing every later call.
+    PLUGIN_REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// The one engine every plugin's store is created from. Engines are meant to be
+// shared, and compiled code and configuration live here rather than per plugin.
+static ENGINE: OnceLock<Engine> = OnceLock::new();
+
+fn shared_engine() -> Result<&'static Engine, PluginError> {
+    ENGINE
+        .get()
+        .ok_or_else(|| PluginError::CompileFailed("the plugin engine isn't running".to_string()))
+}
+
+// Where plugins are loaded from and installed to, once it's been resolved
+static PLUGINS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+// Set once the startup load has finished, whether or not any plugins loaded
+static PLUGINS_READY: AtomicBool = AtomicBool::new(false);
+
+/// Records that the startup load is over, so anything still waiting on a plugin
+/// that never turned up stops waiting.
+fn mark_plugins_ready() {
+    PLUGINS_READY.store(true, Ordering::SeqCst);
+    ready::notify();
+}
+
+// Why plugins can't run at all, if the engine couldn't be created
+static PLUGINS_UNAVAILABLE: OnceLock<String> = OnceLock::new();
+
+// Default cap on each plugin's linear memory, overridable with PATO_PLUGIN_MEMORY_MB
+const DEFAULT_MEMORY_LIMIT_MB: usize = 64;
+
+// Default fuel budget for a single plugin call, overridable with PATO_PLUGIN_FUEL
+const DEFAULT_FUEL_BUDGET: u64 = 100_000_000;
+
+// Default wall-clock limit for a single plugin call, overridable with PATO_PLUGIN_TIMEOUT_MS
+const DEFAULT_CALL_TIMEOUT_MS: u64 = 5_000;
+
+// How long each plugin's `shutdown` hook gets when the app exits, including
+// waiting for a call already running against it
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How many times each plugin id has been compiled and instantiated, so tests can
+// check that a load does each exactly once
+#[cfg(test)]
+type LoadCounts = Mutex<std::collections::BTreeMap<String, usize>>;
+#[cfg(test)]
+static COMPILES: LoadCounts = Mutex::new(std::collections::BTreeMap::new());
+#[cfg(test)]
+static INSTANTIATIONS: LoadCounts = Mutex::new(std::collections::BTreeMap::new());
+
+#[cfg(test)]
+fn count(counts: &LoadCounts, id: &str) {
+    *counts.lock().unwrap_or_else(PoisonError::into_inner).entry(id.to_string()).or_default() += 1;
+}
+
+// The most plugins each plugins directory has had loading at once
+#[cfg(test)]
+static LOAD_PEAKS: Mutex<std::collections::BTreeMap<PathBuf, usize>> = Mutex::new(std::collections::BTreeMap::new());
+
+#[cfg(test)]
+fn record_peak(plugins_dir: &Path, in_flight: usize) {
+    let mut peaks = LOAD_PEAKS.lock().unwrap_or_else(PoisonError::into_inner);
+    let peak = peaks.entry(plugins_dir.to_path_buf()).or_default();
+    *peak = (*peak).max(in_flight);
+}
+
+/// Calls `get-number` on the plugin `id`, trapping it if it runs longer than
+/// `timeout` (or the global default when `None`). An id that isn't loaded is
+/// `NotLoaded`, never a call to some other plugin.
+async fn call_plugin_function(id: &str, timeout: Option<Duration>) -> Result<u32, PluginError> {
+    let result = recovery::with_trap_retries(id, || call_get_number(id, timeout)).await;
+    if let Err(PluginError::Exited(code)) = &result {
+        handle_exit(id, *code).await;
+    }
+    result
+}
+
+async fn call_get_number(id: &str, timeout: Option<Duration>) -> Result<u32, PluginError> {
+    let (instance, fuel_budget) = plugin_instance(id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+    
+    // Create the plugin interface, if the plugin's exports match it
+    let plugin = static_bindings(store, instance);
+    
+    arm_call_limits(store, fuel_budget, "test#get-number", timeout)?;
+    
+    // Call the actual get-number function from the WASM plugin
+    println!("🔌 Calling real WASM plugin get-number() function...");
+    let started = Instant::now();
+    let result = match plugin {
+        Some(plugin) => plugin
+            .test()
+            .call_get_number(&mut *store)
+            .await
+            .map_err(PluginError::trap),
+        None => dynamic::call_typed::<(), (u32,)>(store, instance, "test#get-number", ())
+            .await
+            .map(|(number,)| number),
+    };
+    let fuel_used = metrics::fuel_used(store, fuel_budget);
+    metrics::record(id, "test#get-number", started.elapsed(), fuel_used, result.is_err());
+    let result = result?;
+    
+    println!("📝 Plugin returned: {}", result);
+    Ok(result)
+}
+
+/// Calls `transform` on the plugin `id`, passing `input` through as a WIT record.
+async fn call_plugin_transform(
+    id: &str,
+    input: &exports::test::TransformInput,
+    timeout: Option<Duration>,
+) -> Result<String, PluginError> {
+    let result = recovery::with_trap_retries(id, || call_transform(id, input, timeout)).await;
+    if let Err(PluginError::Exited(code)) = &result {
+        handle_exit(id, *code).await;
+    }
+    result
+}
+
+async fn call_transform(
+    id: &str,
+    input: &exports::test::TransformInput,
+    timeout: Option<Duration>,
+) -> Result<String, PluginError> {
+    let (instance, fuel_budget) = plugin_instance(id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+    
+    let plugin = static_bindings(store, instance);
+    
+    arm_call_limits(store, fuel_budget, "test#transform", timeout)?;
+    
+    let started = Instant::now();
+    let result = match plugin {
+        Some(plugin) => plugin
+            .test()
+            .call_transform(&mut *store, input)
+            .await
+            .map_err(PluginError::trap),
+        None => dynamic::call_typed::<(exports::test::TransformInput,), (String,)>(
+            store,
+            instance,
+            "test#transform",
+            (input.clone(),),
+        )
+        .await
+        .map(|(output,)| output),
+    };
+    let fuel_used = metrics::fuel_used(store, fuel_budget);
+    metrics::record(id, "test#transform", started.elapsed(), fuel_used, result.is_err());
+    result
+}
+
+/// Called once a call has returned because the plugin ran `proc_exit`. An
+/// instance that exited mid-call can't be called again, so it's unloaded when
+/// PATO_PLUGIN_UNLOAD_ON_EXIT is set.
+async fn handle_exit(id: &str, code: i32) {
+    println!("🚪 Plugin {} exited with code {}", id, code);
+    if !unload_on_exit() {
+        return;
+    }
+    
+    let Ok((instance, _)) = plugin_instance(id) else {
+        return;
+    };
+    // Waits for the exiting call to release the store; unloading takes the lock again
+    let app = instance.lock().await.store.data().app.clone();
+    drop(instance);
+    
+    if unload_plugin(id).await {
+        let _ = app.emit("plugin-unloaded", id);
+    }
+}
+
+/// Looks up a plugin's instance handle and fuel budget. The registry lock is
+/// released before returning, so callers can await the instance lock freely.
+fn plugin_instance(id: &str) -> Result<(Arc<tokio::sync::Mutex<PluginInstance>>, u64), PluginError> {
+    let registry = registry();
+    let loaded = registry
+        .get(id)
+        .ok_or_else(|| PluginError::NotLoaded(id.to_string()))?;
+    if !loaded.enabled {
+        return Err(PluginError::Disabled(id.to_string()));
+    }
+    Ok((loaded.instance.clone(), loaded.fuel_budget))
+}
+
+/// The generated bindings for a plugin, or `None` when its exports don't line up
+/// with the `plugin` world the host was built against. Those plugins are still
+/// callable: the typed calls above fall back to looking exports up by name.
+fn static_bindings(store: &mut Store<PluginHost>, instance: &Instance) -> Option<Plugin> {
+    Plugin::new(store, instance).ok()
+}
+
+/// Refuels the store and sets its epoch deadline ahead of a call to `function`.
+/// The deadline is `timeout` if given, else the manifest's `timeouts` entry for
+/// `function`, else the global default.
+fn arm_call_limits(
+    store: &mut Store<PluginHost>,
+    fuel_budget: u64,
+    function: &str,
+    timeout: Option<Duration>,
+) -> Result<(), PluginError> {
+    // Every call starts with a full tank so a runaway loop traps instead of hanging
+    store.set_fuel(fuel_budget).map_err(PluginError::trap)?;
+    // Fuel doesn't tick while blocked in host calls, so also bound wall-clock time
+    let declared = match timeout {
+        Some(_) => None,
+        None => store.data().declared_timeout(function),
+    };
+    let timeout = timeout.or(declared).unwrap_or_else(default_call_timeout);
+    epoch::arm(store, timeout);
+    store.data_mut().timed_function = declared.map(|_| function.to_string());
+    // Chunks from this call are told apart from the last one's by a new id
+    let host = store.data_mut();
+    host.call_id = stream::next_call_id();
+    host.chunks_sent = 0;
+    Ok(())
+}
+
+fn create_engine() -> Result<Engine, Box<dyn std::error::Error>> {
+    // Setup Wasmtime engine with component model support
+    let mut config = Config::new();
+    config.wasm_component_model(true);
+    config.async_support(true);
+    config.consume_fuel(true);
+    config.epoch_interruption(true);
+    // Keep wasm frames (and their names) on traps so plugin crashes can be located
+    config.wasm_backtrace(true);
+    config.wasm_backtrace_details(WasmBacktraceDetails::Enable);
+    Ok(Engine::new(&config)?)
+}
+
+/// Explains why the engine couldn't be created. Plugins are components, so a
+/// wasmtime built without the component model is called out by name.
+fn describe_engine_error(error: &dyn std::error::Error) -> String {
+    let message = error.to_string();
+    if message.to_ascii_lowercase().contains("component") {
+        format!(
+            "plugins need the wasm component model, which this build of wasmtime {} doesn't support: {}",
+            version::WASMTIME,
+            message
+        )
+    } else {
+        format!("couldn't start the plugin engine (wasmtime {}): {}", version::WASMTIME, message)
+    }
+}
+
+/// Resolves the plugins directory, in order of precedence:
+///
+/// 1. the `--plugins-dir <path>` command line flag
+/// 2. the `PATO_PLUGINS_DIR` environment variable
+/// 3. `plugins` in the app data directory, if it exists
+/// 4. `plugins` bundled in the app's resources, if it exists
+///
+/// When none of them exist the app data directory is used, and created on load.
+fn find_plugins_dir(app: &AppHandle) -> Option<PathBuf> {
+    let (path, source) = if let Some(path) = plugins_dir_arg(std::env::args()) {
+        (path, "--plugins-dir")
+    } else if let Some(path) = std::env::var_os("PATO_PLUGINS_DIR").filter(|value| !value.is_empty()) {
+        (PathBuf::from(path), "PATO_PLUGINS_DIR")
+    } else {
+        let app_data = app.app_data_dir().map(|dir| dir.join("plugins"));
+        let bundled = app.resource_dir().map(|dir| dir.join("plugins"));
+        
+        match (app_data, bundled) {
+            (Some(app_data), _) if app_data.exists() => (app_data, "app data directory"),
+            (_, Some(bundled)) if bundled.exists() => (bundled, "bundled default"),
+            (Some(app_data), _) => (app_data, "app data directory"),
+            (None, _) => {
+                println!("❌ No valid plugins directory found");
+                return None;
+            }
+        }
+    };
+    
+    println!("📁 Plugins directory from {}: {:?}", source, path);
+    Some(path)
+}
+
+/// Whether the app was started with `--dev` (or PATO_DEV set), which turns on
+/// the plugin watcher and reload diagnostics.
+fn dev_mode() -> bool {
+    std::env::args().any(|arg| arg == "--dev")
+        || std::env::var("PATO_DEV").is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+/// Picks `--plugins-dir <path>` or `--plugins-dir=<path>` out of the command line.
+fn plugins_dir_arg(mut args: impl Iterator<Item = String>) -> Option<PathBuf> {
+    while let Some(arg) = args.next() {
+        if arg == "--plugins-dir" {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--plugins-dir=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Loads every plugin in `plugins_dir`, returning once all have either loaded
+/// or failed. Plugins load after their dependencies, and concurrently otherwise.
+/// A missing directory is handled as `missing_dir` says, and no more than
+/// `concurrency` plugins compile and instantiate at once.
+async fn load_wasm_plugins(
+    app: &AppHandle,
+    engine: &Engine,
+    plugins_dir: &Path,
+    missing_dir: MissingDirMode,
+    concurrency: usize,
+) -> Result<LoadSummary, PluginError> {
+    println!("🔌 Loading WASM plugins...");
+    
+    println!("✅ Using plugins directory: {:?} (if missing: {:?})", plugins_dir, missing_dir);
+    
+    if !plugins_dir.exists() {
+        match missing_dir {
+            MissingDirMode::Create => {
+                // Carry on to the scan, which finds nothing, so there's only one way out of here
+                println!("📁 Plugins directory not found, creating: {:?}", plugins_dir);
+                std::fs::create_dir_all(plugins_dir).map_err(|e| PluginError::Io(e.to_string()))?;
+            }
+            MissingDirMode::Error => {
+                println!("❌ Plugins directory not found: {:?}", plugins_dir);
+                return Err(PluginError::Io(format!("plugins directory {:?} doesn't exist", plugins_dir)));
+            }
+            MissingDirMode::Ignore => {
+                println!("📁 Plugins directory not found, continuing without plugins: {:?}", plugins_dir);
+                return Ok(LoadSummary::default());
+            }
+        }
+    }
+    
+    // Scan for .wasm files and .patopkg packages, reading their manifests up front
+    let paths = sorted_entries(plugins_dir)?;
+    let mut candidates = Vec::new();
+    let mut summary = LoadSummary::default();
+    
+    for path in paths {
+        if is_plugin_file(&path) {
+            println!("🔍 Found plugin: {:?}", path.file_name().unwrap());
+            match read_candidate(&path) {
+                Ok(candidate) => candidates.push(candidate),
+                Err(e) => {
+                    println!("❌ Failed to load plugin {:?}: {}", path.file_name().unwrap(), e);
+                    summary.failed += 1;
+                }
+            }
+        } else if !is_plugin_sidecar(&path) {
+            summary.skipped += 1;
+        }
+    }
+    
+    // Derived ids are only unique against the registry so far, not each other,
+    // e.g. `tool.wasm` and `tool.patopkg` both want `tool`
+    {
+        let registry = registry();
+        let mut seen = std::collections::HashSet::new();
+        for candidate in candidates.iter_mut().filter(|candidate| candidate.manifest.is_none()) {
+            if seen.contains(&candidate.id) {
+                let path = &candidate.path;
+                let taken = |id: &str| seen.contains(id) || taken_by_another_file(&registry, id, path);
+                if let Some(id) = derived_id(path, &candidate.bytes, taken) {
+                    candidate.id = id;
+                }
+            }
+            seen.insert(candidate.id.clone());
+        }
+    }
+    
+    // Work out an order that puts dependencies first
+    let resolution = deps::resolve(candidates);
+    summary.failed += resolution.failed.len();
+    for (candidate, e) in resolution.failed {
+        println!("❌ Not loading plugin {}: {}", candidate.id, e);
+        registry().record_failure(candidate.id, candidate.path, e.to_string());
+    }
+    let load_order: Vec<String> = resolution.levels.iter().flatten().map(|c| c.id.clone()).collect();
+    println!("📋 Plugin load order: {:?}", load_order);
+    registry().set_load_order(load_order);
+    
+    // Each level only needs earlier levels, so its plugins load concurrently,
+    // but only so many at once since compiling is memory-hungry. A level waits for
+    // the one before to finish, `init` included, so a plugin's dependencies have
+    // always initialized before its own `init` runs.
+    let permits = Arc::new(tokio::sync::Semaphore::new(concurrency));
+    #[cfg(test)]
+    let in_flight = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    for level in resolution.levels {
+        let mut tasks = tokio::task::JoinSet::new();
+        for candidate in level {
+            let (app, engine, permits) = (app.clone(), engine.clone(), permits.clone());
+            #[cfg(test)]
+            let (in_flight, plugins_dir) = (in_flight.clone(), plugins_dir.to_path_buf());
+            tasks.spawn(async move {
+                // The semaphore is never closed, so this only fails if it's been dropped
+                let Ok(_permit) = permits.acquire_owned().await else {
+                    return false;
+                };
+                #[cfg(test)]
+                record_peak(&plugins_dir, in_flight.fetch_add(1, Ordering::SeqCst) + 1);
+                let file_name = candidate.path.file_name().unwrap_or_default().to_os_string();
+                let loaded = match load_candidate(&app, &engine, candidate).await {
+                    Ok(_) => {
+                        println!("✅ Successfully loaded plugin: {:?}", file_name);
+                        true
+                    }
+                    Err(e) => {
+                        println!("❌ Failed to load plugin {:?}: {}", file_name, e);
+                        false
+                    }
+                };
+                #[cfg(test)]
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                loaded
+            });
+        }
+        while let Some(joined) = tasks.join_next().await {
+            // A panicked load never registered anything, so it counts as a failure
+            if joined.unwrap_or(false) {
+                summary.loaded += 1;
+            } else {
+                summary.failed += 1;
+            }
+        }
+    }
+    
+    if summary.loaded + summary.failed == 0 {
+        println!("📁 No plugins found in {:?}", plugins_dir);
+    } else {
+        let (compile_time, size_bytes) = registry().load_costs();
+        println!(
+            "🎉 Loaded {} plugin(s), {} failed, {} other file(s) skipped ({} bytes, {:?} compiling)",
+            summary.loaded, summary.failed, summary.skipped, size_bytes, compile_time
+        );
+    }
+    
+    Ok(summary)
+}
+
+/// Files that belong to a plugin without being one, like its manifest or
+/// signature, and the compilation cache.
+fn is_plugin_sidecar(path: &Path) -> bool {
+    let hidden = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .is_some_and(|name| name.starts_with('.'));
+    hidden || matches!(path.extension().and_then(|s| s.to_str()), Some("toml" | "sig"))
+}
+
+fn is_wasm_file(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some("wasm")
+}
+
+/// A loose component or a packaged one.
+fn is_plugin_file(path: &Path) -> bool {
+    is_wasm_file(path) || package::is_package_file(path)
+}
+
+/// A plugin's file stem, e.g. `plugin_ui.wasm` -> `plugin_ui`. Failures from before
+/// a plugin's bytes could be read are recorded under this.
+fn plugin_id(plugin_path: &Path) -> Option<String> {
+    plugin_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_string())
+}
+
+/// Plugins without a manifest are identified by their file stem, e.g. `plugin_ui.wasm`
+/// -> `plugin_ui`, unless `taken` says another plugin has it. Then a short hash of the
+/// file name and bytes is added, e.g. `plugin_ui-1a2b3c4d`, so two different plugins
+/// that happen to share a file name don't collide.
+fn derived_id(plugin_path: &Path, bytes: &[u8], taken: impl Fn(&str) -> bool) -> Option<String> {
+    let stem = plugin_id(plugin_path)?;
+    if !taken(&stem) {
+        return Some(stem);
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(plugin_path.file_name()?.as_encoded_bytes());
+    hasher.update(bytes);
+    let digest = hasher.finalize();
+    let hash: String = digest[..4].iter().map(|b| format!("{:02x}", b)).collect();
+    Some(first_free_id(format!("{}-{}", stem, hash), taken))
+}
+
+/// Whether plugin `id` is registered from some file other than `plugin_path`; a
+/// plugin's own entry, as when it's reloaded, doesn't count.
+fn taken_by_another_file(registry: &PluginRegistry, id: &str, plugin_path: &Path) -> bool {
+    registry.path_of(id).is_some_and(|path| path != plugin_path)
+}
+
+/// `id`, or if `taken` says it's in use, the first of `id-2`, `id-3`, ... that isn't.
+/// Copies of one file under the same name hash alike, so this tells them apart.
+fn first_free_id(id: String, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(&id) {
+        return id;
+    }
+    (2..)
+        .map(|n| format!("{}-{}", id, n))
+        .find(|candidate| !taken(candidate))
+        .expect("ran out of plugin id suffixes")
+}
+
+async fn load_plugin(app: &AppHandle, engine: &Engine, plugin_path: &Path) -> Result<String, PluginError> {
+    let candidate = read_candidate(plugin_path)?;
+    load_candidate(app, engine, candidate).await
+}
+
+/// Reads a plugin's manifest and component, recording a failure if either can't be read.
+fn read_candidate(plugin_path: &Path) -> Result<deps::Candidate, PluginError> {
+    let stem = plugin_id(plugin_path)
+        .ok_or_else(|| PluginError::Io(format!("{:?} has no file name", plugin_path)))?;
+    
+    // A manifest id takes precedence over one derived from the file
+    match package::read_plugin(plugin_path) {
+        Ok((manifest, bytes)) => {
+            let id = match &manifest {
+                Some(manifest) => manifest.id.clone(),
+                None => {
+                    let registry = registry();
+                    derived_id(plugin_path, &bytes, |id| taken_by_another_file(&registry, id, plugin_path))
+                        .unwrap_or(stem)
+                }
+            };
+            Ok(deps::Candidate {
+                id,
+                path: plugin_path.to_path_buf(),
+                manifest,
+                bytes,
+            })
+        }
+        Err(e) => {
+            registry().record_failure(stem, plugin_path.to_path_buf(), e.to_string());
+            Err(e)
+        }
+    }
+}
+
+async fn load_candidate(
+    app: &AppHandle,
+    engine: &Engine,
+    candidate: deps::Candidate,
+) -> Result<String, PluginError> {
+    let deps::Candidate { id, path, manifest, bytes } = candidate;
+    
+    // Store the complete plugin context for later function calls, or remember why it failed.
+    // Dependencies are checked against what actually loaded, not just what was found.
+    let loaded = match deps::check_loaded(manifest.as_ref()) {
+        Ok(()) => instantiate_plugin(app, engine, &id, &path, manifest, bytes, false).await,
+        Err(e) => Err(e),
+    };
+    let result = match loaded {
+        Ok(loaded) => {
+            registry().register(id.clone(), loaded);
+            snapshot::refresh(&id);
+            Ok(id)
+        }
+        Err(e) => {
+            registry().record_failure(id, path, e.to_string());
+            Err(e)
+        }
+    };
+    ready::notify();
+    result
+}
+
+/// Compiles and registers a plugin bundled into the host with `include_bytes!`,
+/// under `name`. It sits in the registry alongside the filesystem plugins, but has
+/// no manifest, and can't be reloaded since there's no file behind it.
+async fn load_embedded_plugin(
+    app: &AppHandle,
+    engine: &Engine,
+    name: &str,
+    bytes: &[u8],
+) -> Result<String, PluginError> {
+    // Left alone rather than marked failed, since it's the other plugin's entry
+    if registry().get(name).is_some() {
+        return Err(PluginError::InstantiationFailed(format!("a plugin named {} is already loaded", name)));
+    }
+    
+    let path = PathBuf::from(format!("{}.wasm", name));
+    let loaded = instantiate_plugin(app, engine, name, &path, None, bytes.to_vec(), true).await;
+    let result = match loaded {
+        Ok(loaded) => {
+            println!("📦 Loaded embedded plugin {}", name);
+            registry().register(name.to_string(), loaded);
+            snapshot::refresh(name);
+            Ok(name.to_string())
+        }
+        Err(e) => {
+            registry().record_failure(name.to_string(), path, e.to_string());
+            Err(e)
+        }
+    };
+    ready::notify();
+    result
+}
+
+/// Reloads plugin `id` from disk as a swap: the new version is compiled,
+/// instantiated, and initialized on the side, and only replaces the old one once
+/// all of that has worked. If any step fails the old instance keeps running,
+/// `plugin-reload-failed` is emitted with the id and error, and the error is returned.
+async fn reload_plugin(app: &AppHandle, id: &str) -> Result<(), PluginError> {
+    // Only hold the lock long enough to find out where the plugin came from
+    let path = {
+        let registry = registry();
+        let loaded = registry
+            .get(id)
+            .ok_or_else(|| PluginError::NotLoaded(id.to_string()))?;
+        if loaded.embedded {
+            return Err(PluginError::Embedded(id.to_string()));
+        }
+        loaded.path.clone()
+    };
+    
+    println!("🔄 Reloading plugin {} from {:?}", id, path);
+    // The new instance's `init` may schedule timers of its own, which have to survive the swap
+    let old_timers = timers::scheduled(id);
+    let loaded = match prepare_reload(app, id, &path).await {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            println!("↩️ Keeping the running instance of plugin {}: {}", id, e);
+            let _ = app.emit("plugin-reload-failed", (id, &e));
+            return Err(e);
+        }
+    };
+    
+    // In-flight calls hold their own handle to the old instance, so they finish against it
+    let replaced = registry().register(id.to_string(), loaded);
+    snapshot::refresh(id);
+    for timer in old_timers {
+        timers::cancel(id, timer);
+    }
+    if let Some(old) = replaced {
+        if let Err(e) = lifecycle::shutdown(&old).await {
+            println!("⚠️ Plugin {} failed to shut down: {}", id, e);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Builds the replacement for a reloading plugin without touching the registry.
+async fn prepare_reload(app: &AppHandle, id: &str, path: &Path) -> Result<LoadedPlugin, PluginError> {
+    let engine = shared_engine()?;
+    let (manifest, component_bytes) = package::read_plugin(path)?;
+    instantiate_plugin(app, engine, id, path, manifest, component_bytes, false).await
+}
+
+/// Brings the registry back in line with the plugins directory: reloads every
+/// loaded plugin from disk, unloads those whose files are gone, and loads any
+/// plugin files that aren't loaded yet.
+async fn reload_all_plugins(app: &AppHandle) -> Result<ReloadSummary, PluginError> {
+    let mut summary = ReloadSummary::default();
+    
+    let plugins: Vec<(String, PathBuf)> = {
+        let registry = registry();
+        registry
+            .list_ids()
+            .into_iter()
+            .filter_map(|id| registry.get(&id).map(|loaded| (id, loaded)))
+            // Embedded plugins have no file to go back to
+            .filter(|(_, loaded)| !loaded.embedded)
+            .map(|(id, loaded)| (id, loaded.path.clone()))
+            .collect()
+    };
+    
+    // One at a time, so each replacement is complete before the next starts
+    for (id, path) in plugins {
+        if !path.exists() {
+            if unload_plugin(&id).await {
+                summary.unloaded.push(id);
+            }
+            continue;
+        }
+        match reload_plugin(app, &id).await {
+            Ok(()) => summary.reloaded.push(id),
+            Err(e) => summary.failed.push(ReloadFailure { id, error: e.to_string() }),
+        }
+    }
+    
+    // Anything on disk that isn't loaded by now is new, or failed last time
+    let Some(plugins_dir) = PLUGINS_DIR.get() else {
+        return Ok(summary);
+    };
+    let paths = sorted_entries(plugins_dir)?;
+    let engine = shared_engine()?;
+    for path in paths {
+        let is_loaded = {
+            let registry = registry();
+            registry.id_for_path(&path).is_some_and(|id| registry.get(&id).is_some())
+        };
+        if !is_plugin_file(&path) || is_loaded {
+            continue;
+        }
+        match load_plugin(app, engine, &path).await {
+            Ok(id) => summary.loaded.push(id),
+            Err(e) => {
+                let id = plugin_id(&path).unwrap_or_default();
+                summary.failed.push(ReloadFailure { id, error: e.to_string() });
+            }
+        }
+    }
+    
+    Ok(summary)
+}
+
+/// The paths in `dir`, sorted; read_dir's own order depends on the OS, and
+/// loads should be the same everywhere.
+fn sorted_entries(dir: &Path) -> Result<Vec<PathBuf>, PluginError> {
+    let mut paths = std::fs::read_dir(dir)
+        .and_then(|entries| entries.map(|entry| entry.map(|entry| entry.path())).collect::<Result<Vec<_>, _>>())
+        .map_err(|e| PluginError::Io(e.to_string()))?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Runs every plugin's `shutdown` hook as the host exits, dependents before the
+/// plugins they depend on, then flushes their databases. Each hook gets
+/// `SHUTDOWN_TIMEOUT`, so one stuck plugin can't hold up the exit. Plugins are
+/// taken out of the registry but their data directories are left alone.
+async fn shutdown_all_plugins() {
+    let ids: Vec<String> = {
+        let registry = registry();
+        // Plugins the startup load didn't order, like those installed later, go first
+        let mut ids: Vec<String> = registry
+            .list_ids()
+            .into_iter()
+            .filter(|id| !registry.load_order().contains(id))
+            .collect();
+        ids.extend(registry.load_order().iter().rev().cloned());
+        ids
+    };
+    
+    shutdown_plugins(ids).await;
+    database::flush_all();
+}
+
+/// Takes each of `ids` out of the registry in turn and runs its `shutdown` hook,
+/// giving up on any hook still running after `SHUTDOWN_TIMEOUT`.
+async fn shutdown_plugins(ids: Vec<String>) {
+    for id in ids {
+        let Some(loaded) = registry().remove(&id) else {
+            continue;
+        };
+        timers::cancel_all(&id);
+        match tokio::time::timeout(SHUTDOWN_TIMEOUT, lifecycle::shutdown(&loaded)).await {
+            Ok(Ok(())) => println!("👋 Shut down plugin {}", id),
+            Ok(Err(e)) => println!("⚠️ Plugin {} failed to shut down: {}", id, e),
+            Err(_) => println!("⚠️ Plugin {} didn't shut down within {:?}", id, SHUTDOWN_TIMEOUT),
+        }
+    }
+}
+
+async fn unload_plugin(id: &str) -> bool {
+    // Take the plugin out of the registry first so the lock isn't held while it's torn down
+    let removed = registry().remove(id);
+    timers::cancel_all(id);
+    
+    match removed {
+        Some(loaded) => {
+            // A failing shutdown hook doesn't stop the plugin from being unloaded
+            if let Err(e) = lifecycle::shutdown(&loaded).await {
+                println!("⚠️ Plugin {} failed to shut down: {}", id, e);
+            }
+            
+            let persistent = loaded.manifest.as_ref().is_some_and(|m| m.persistent);
+            let data_dir = loaded.data_dir.clone();
+            
+            // Dropping the store frees the instance along with its WASI context and resource table
+            drop(loaded);
+            database::close(&data_dir);
+            
+            // Scratch data goes with the plugin unless it asked to keep it
+            if !persistent {
+                if let Err(e) = std::fs::remove_dir_all(&data_dir) {
+                    println!("⚠️ Failed to remove data directory {:?}: {}", data_dir, e);
+                }
+            }
+            
+            println!("🗑️ Unloaded plugin {}", id);
+            true
+        }
+        None => false,
+    }
+}
+
+fn memory_limit_bytes() -> usize {
+    let megabytes = runtime::limits().memory_mb.unwrap_or_else(|| {
+        std::env::var("PATO_PLUGIN_MEMORY_MB")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MEMORY_LIMIT_MB)
+    });
+    megabytes * 1024 * 1024
+}
+
+fn fuel_budget() -> u64 {
+    runtime::limits().fuel.unwrap_or_else(|| {
+        std::env::var("PATO_PLUGIN_FUEL")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_FUEL_BUDGET)
+    })
+}
+
+/// How many plugins the startup load compiles and instantiates at once:
+/// PATO_LOAD_CONCURRENCY, or by default one per CPU.
+fn load_concurrency() -> usize {
+    parse_load_concurrency(std::env::var("PATO_LOAD_CONCURRENCY").ok().as_deref())
+}
+
+fn parse_load_concurrency(value: Option<&str>) -> usize {
+    value
+        .and_then(|value| value.parse().ok())
+        .filter(|&limit| limit > 0)
+        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1))
+}
+
+fn unload_on_exit() -> bool {
+    std::env::var("PATO_PLUGIN_UNLOAD_ON_EXIT")
+        .is_ok_and(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+}
+
+fn default_call_timeout() -> Duration {
+    if let Some(timeout) = runtime::limits().call_timeout {
+        return timeout;
+    }
+    let millis = std::env::var("PATO_PLUGIN_TIMEOUT_MS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_CALL_TIMEOUT_MS);
+    Duration::from_millis(millis)
+}
+
+/// Compiles and instantiates a plugin with a fresh store, without registering it.
+/// `embedded` plugins were compiled into the host, so they skip the signature
+/// check and the on-disk compilation cache.
+async fn instantiate_plugin(
+    app: &AppHandle,
+    engine: &Engine,
+    id: &str,
+    plugin_path: &Path,
+    manifest: Option<PluginManifest>,
+    component_bytes: Vec<u8>,
+    embedded: bool,
+) -> Result<LoadedPlugin, PluginError> {
+    // Check where the bytes came from before doing anything with them
+    if !embedded {
+        signing::verify(app, id, plugin_path, manifest.as_ref(), &component_bytes)?;
+    }
+    
+    // Compile the component once against the shared engine.
+    // Compilation is CPU-bound, so keep it off the async workers
+    let compile_engine = engine.clone();
+    let compile_path = plugin_path.to_path_buf();
+    let size_bytes = component_bytes.len() as u64;
+    let compile_started = Instant::now();
+    #[cfg(test)]
+    let compile_id = id.to_string();
+    let component = tokio::task::spawn_blocking(move || {
+        #[cfg(test)]
+        count(&COMPILES, &compile_id);
+        if embedded {
+            Component::new(&compile_engine, &component_bytes).map_err(|e| PluginError::compile(&component_bytes, e))
+        } else {
+            cache::load_component(&compile_engine, &compile_path, &component_bytes)
+        }
+    })
+    .await
+    .map_err(|e| PluginError::CompileFailed(e.to_string()))??;
+    let compile_time = compile_started.elapsed();
+    
+    // Reject plugins built against incompatible host interfaces with a clear message
+    version::check(engine, &component)?;
+    
+    let data_dir = sandbox::create_data_dir(app, id)?;
+    let output = output::CapturedOutput::default();
+    let fuel_budget = manifest.as_ref().and_then(|m| m.fuel).unwrap_or_else(fuel_budget);
+    let cancel = Arc::new(AtomicBool::new(false));
+    let mut instance = create_instance(
+        app,
+        engine,
+        id,
+        plugin_path,
+        manifest.as_ref(),
+        &component,
+        &data_dir,
+        &output,
+        fuel_budget,
+        cancel.clone(),
+    )
+    .await?;
+    
+    // Either way it's callable, but a plugin off the static path is worth knowing about
+    if static_bindings(&mut instance.store, &instance.instance).is_some() {
+        println!("🔗 Plugin {} matches the host's plugin world; using static bindings", id);
+    } else {
+        println!("🔀 Plugin {} doesn't match the host's plugin world; falling back to dynamic calls", id);
+    }
+    
+    // A plugin with broken contributions still loads, just without adding to the UI
+    let ui_contributions = ui::collect(id, &mut instance, fuel_budget).await.unwrap_or_else(|e| {
+        println!("⚠️ Plugin {} failed to list its UI contributions: {}", id, e);
+        Vec::new()
+    });
+    
+    // Likewise a broken schema just leaves the plugin's config unchecked
+    let config_schema = config::collect_schema(&mut instance, fuel_budget).await.unwrap_or_else(|e| {
+        println!("⚠️ Plugin {} failed to declare its config schema: {}", id, e);
+        None
+    });
+    
+    Ok(LoadedPlugin {
+        component,
+        instance: Arc::new(tokio::sync::Mutex::new(instance)),
+        path: plugin_path.to_path_buf(),
+        manifest,
+        loaded_at: std::time::SystemTime::now(),
+        compile_time,
+        size_bytes,
+        fuel_budget,
+        data_dir,
+        health: Default::default(),
+        metrics: Default::default(),
+        throttled: Default::default(),
+        output,
+        enabled: !disabled::is_disabled(app, id),
+        cancel,
+        dirty: false,
+        warm: false,
+        ui_contributions,
+        config_schema,
+        embedded,
+        snapshot: Default::default(),
+    })
+}
+
+/// Builds a fresh store for an already compiled plugin and instantiates it in
+/// there, running its `init` hook.
+#[allow(clippy::too_many_arguments)]
+async fn create_instance(
+    app: &AppHandle,
+    engine: &Engine,
+    id: &str,
+    plugin_path: &Path,
+    manifest: Option<&PluginManifest>,
+    component: &Component,
+    data_dir: &Path,
+    output: &output::CapturedOutput,
+    fuel_budget: u64,
+    cancel: Arc<AtomicBool>,
+) -> Result<PluginInstance, PluginError> {
+    // Each plugin gets its own store so its WASI context and resources stay isolated,
+    // and the WASI context only exposes what the manifest asked for
+    let mount_root = sandbox::mount_root(app);
+    let capabilities = permissions::granted(app, id, manifest);
+    let wasi = sandbox::build_wasi_ctx(manifest, &capabilities, plugin_path, data_dir, output, mount_root.as_deref())?;
+    // Without storage the plugin's db calls fail, so don't create a store it can't use
+    let db = if capabilities.contains(&Capability::Storage) {
+        Some(database::open(data_dir)?)
+    } else {
+        None
+    };
+    let table = wasmtime_wasi::ResourceTable::new();
+    let limits = StoreLimitsBuilder::new()
+        .memory_size(memory_limit_bytes())
+        // Trap rather than returning -1 from memory.grow so the caller gets a clear error
+        .trap_on_grow_failure(true)
+        .build();
+    let limits = usage::UsageLimiter::new(limits);
+    let host = PluginHost {
+        plugin_id: id.to_string(),
+        app: app.clone(),
+        host_version: app.version(),
+        wasi,
+        table,
+        limits,
+        config: config::load(app, id, manifest),
+        send_to: manifest.map(|m| m.send_to.clone()).unwrap_or_default(),
+        publishes: manifest.map(|m| m.publishes.clone()).unwrap_or_default(),
+        log_level: manifest.map(|m| m.log_level).unwrap_or_default(),
+        db,
+        event_schemas: manifest.map(|m| m.events.clone()).unwrap_or_default(),
+        allowed_hosts: manifest.map(|m| m.allowed_hosts.clone()).unwrap_or_default(),
+        data_dir: data_dir.to_path_buf(),
+        open_handles: 0,
+        capabilities,
+        cancel,
+        ticks_left: 0,
+        function_timeouts: manifest.map(|m| m.function_timeouts()).unwrap_or_default(),
+        timed_function: None,
+        rate_limits: ratelimit::HostRateLimiter::new(&manifest.map(|m| m.rate_limits).unwrap_or_default()),
+        call_id: 0,
+        chunks_sent: 0,
+    };
+    let mut store = Store::new(engine, host);
+    store.limiter(|host| &mut host.limits);
+    
+    // Instantiation can run start functions, so it gets a budget too
+    store.set_fuel(fuel_budget).map_err(PluginError::instantiation)?;
+    store.epoch_deadline_callback(epoch::on_deadline);
+    epoch::arm(&mut store, default_call_timeout());
+    
+    // Instantiate the component against just the host services it was granted;
+    // this is the instance kept for later calls
+    let linker = services::linker(engine, &store.data().capabilities)?;
+    #[cfg(test)]
+    count(&INSTANTIATIONS, id);
+    let instance = linker
+        .instantiate_async(&mut store, component)
+        .await
+        .map_err(PluginError::instantiation)?;
+    
+    // Give the plugin a chance to set itself up; if that traps, the load fails
+    let init_params = manifest.map(|m| m.init_params()).unwrap_or_default();
+    lifecycle::init(&mut store, &instance, fuel_budget, init_params).await?;
+    
+    Ok(PluginInstance { store, instance })
+}
+
+/// Starts the app: runs `pato run ...` headlessly if that's what was asked
+/// for, and otherwise opens the window and loads plugins in the background.
+#[cfg(feature = "tauri")]
+pub fn run() {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let context = tauri::generate_context!();
+    
+    // `pato run ...` calls one plugin function headlessly instead of opening the app
+    match cli::parse(std::env::args()) {
+        Some(Ok(command)) => std::process::exit(cli::run(command, context)),
+        Some(Err(usage)) => {
+            eprintln!("{}", usage);
+            std::process::exit(2);
+        }
+        None => {}
+    }
+    
+    tauri::Builder::default()
+        .setup(|app| {
+            println!("🦆 Pato platform starting up...");
+            
+            let engine = match create_engine() {
+                Ok(engine) => engine,
+                Err(e) => {
+                    // The app still comes up, just without plugins
+                    let reason = describe_engine_error(&*e);
+                    println!("⚠️ {}", reason);
+                    let _ = PLUGINS_UNAVAILABLE.set(reason.clone());
+                    mark_plugins_ready();
+                    let _ = app.emit("plugins-unavailable", reason);
+                    return Ok(());
+                }
+            };
+            
+            // Every plugin shares this engine; only stores are per plugin
+            let _ = ENGINE.set(engine.clone());
+            
+            // Drive epoch deadlines for every store created from this engine
+            epoch::start(engine.clone());
+            
+            // Route plugin-to-plugin messages outside of any plugin call
+            messaging::start();
+            
+            // The plugin runtime only sees the app through this
+            let handle = AppHandle::from(app.handle().clone());
+            
+            // Forward plugins' `emit-chunk` output to the frontend as it arrives
+            stream::start(handle.clone());
+            
+            // Periodically ask plugins that export `health` how they're doing
+            health::start(handle.clone());
+            
+            // The commands go through this, same as any other embedder would
+            let builder = PluginRuntime::builder();
+            // The sample plugin, built into the host so it can't go missing from the plugins directory
+            #[cfg(feature = "embed-sample-plugin")]
+            let builder = builder.embedded_plugin(
+                "plugin-ui",
+                include_bytes!("../../plugin-ui/target/wasm32-wasip2/release/plugin_ui.wasm"),
+            );
+            let runtime = match builder.build(&handle) {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    println!("⚠️ Error starting the plugin runtime: {}", e);
+                    mark_plugins_ready();
+                    return Ok(());
+                }
+            };
+            let plugins_dir = runtime.plugins_dir().to_path_buf();
+            app.manage(runtime);
+            
+            // Load WASM plugins in the background so the window can come up straight away
+            let app = app.handle().clone();
+            async_runtime::spawn(async move {
+                let summary = match app.state::<PluginRuntime>().load_all().await {
+                    Ok(summary) => summary,
+                    Err(e) => {
+                        println!("⚠️ Error loading plugins: {}", e);
+                        LoadSummary::default()
+                    }
+                };
+                mark_plugins_ready();
+                let _ = app.emit("plugins-ready", summary);
+                
+                // Get every plugin ready for its first call while nothing's waiting on it
+                let ids = registry().list_ids();
+                for id in ids {
+                    if let Err(e) = warmup::warmup(&id).await {
+                        println!("⚠️ Couldn't warm up plugin {}: {}", id, e);
+                    }
+                }
+                
+                // In dev mode, pick up plugins that are added, rebuilt, or removed while the
+                // app runs. Production leaves loaded plugins alone for stability.
+                if !dev_mode() {
+                    return;
+                }
+                println!("🧑‍💻 Dev mode: watching for plugin changes");
+                if let Err(e) = watcher::watch_plugins_dir(handle, engine, plugins_dir) {
+                    println!("⚠️ Error watching plugins directory: {}", e);
+                }
+            });
+            
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            commands::handle_button_click,
+            commands::list_plugins,
+            commands::list_failed_plugins,
+            commands::retry_failed,
+            commands::plugins_ready,
+            commands::wait_for_plugin_ready,
+            commands::plugins_unavailable,
+            commands::plugin_load_order,
+            commands::invoke_plugin,
+            commands::invoke_export,
+            commands::invoke_simple,
+            commands::transform,
+            commands::sum_all_numbers,
+            commands::run_pipeline,
+            commands::filter_items,
+            commands::manifest_schema,
+            commands::update_plugin_config,
+            commands::plugin_config_schema,
+            commands::set_plugin_log_level,
+            commands::list_ui_contributions,
+            commands::export_plugin_data,
+            commands::import_plugin_data,
+            commands::plugin_permissions,
+            commands::set_permission,
+            commands::reload_plugin,
+            commands::warmup_plugin,
+            commands::reload_all_plugins,
+            commands::unload_plugin,
+            commands::validate_plugin,
+            commands::list_exports,
+            commands::plugin_interface,
+            commands::plugin_metrics,
+            commands::plugin_output,
+            commands::plugin_resource_usage,
+            commands::set_plugin_enabled,
+            commands::install_plugin_from_url,
+            commands::clear_component_cache,
+            commands::cache_stats,
+            commands::invoke_async,
+            commands::invoke_bytes,
+            commands::cancel_plugin_call,
+        ])
+        .build(context)
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                watcher::stop();
+                // Before the epoch ticker stops, so the hooks still have deadlines
+                async_runtime::block_on(shutdown_all_plugins());
+                epoch::stop();
+            }
+        });
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PluginTrap;
+    use crate::registry::IdSource;
+    use crate::testing::{self, TestApp};
+
+    // `get-number` never returns, so a call holds the plugin's store until it times out
+    const SPINS: &str = r#"
+    (component
+      (core module $m
+        (func (export "get-number") (result i32)
+          (loop $spin (br $spin))
+          i32.const 0))
+      (core instance $i (instantiate $m))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "test" (instance $test)))
+    "#;
+
+    // `init` sets the number `get-number` answers with, so only the instance
+    // `init` ran in answers 9
+    const INIT_SETS_NUMBER: &str = r#"
+    (component
+      (core module $m
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (global $number (mut i32) (i32.const 0))
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+          (local $ptr i32)
+          (local.set $ptr (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+          (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+          (local.get $ptr))
+        (func (export "init") (param i32 i32)
+          (global.set $number (i32.const 9)))
+        (func (export "get-number") (result i32) (global.get $number)))
+      (core instance $i (instantiate $m))
+      (func $init (param "params" (list (tuple string string)))
+        (canon lift (core func $i "init") (memory $i "memory") (realloc (func $i "realloc"))))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $lifecycle (export "init" (func $init)))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "pato:plugin-ui/lifecycle@0.1.0" (instance $lifecycle))
+      (export "test" (instance $test)))
+    "#;
+
+    #[tokio::test]
+    async fn loaded_plugins_keep_the_instance_they_were_initialized_in() {
+        let app = TestApp::new();
+        let id = app.load_file("lib-single-instance.wasm", INIT_SETS_NUMBER, None).await.unwrap();
+
+        // Compiled against the shared engine rather than one of its own
+        let instance = registry().get(&id).unwrap().instance.clone();
+        assert!(Engine::same(instance.lock().await.store.engine(), testing::engine()));
+        assert_eq!(call_plugin_function(&id, None).await.unwrap(), 9);
+
+        // Calls reuse it; nothing was compiled or instantiated just to look at the
+        // plugin, beyond the spare instance kept as its `init` snapshot
+        testing::wait_for_snapshot(&id).await;
+        let counted = |counts: &LoadCounts| counts.lock().unwrap()[&id];
+        assert_eq!(counted(&COMPILES), 1);
+        assert_eq!(counted(&INSTANTIATIONS), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn listing_plugins_doesnt_wait_for_a_running_call() {
+        let app = TestApp::new();
+        let id = app.load("lib-long-call", SPINS).await.unwrap();
+        // Only the timeout stops the call, not running out of fuel
+        registry().get_mut(&id).unwrap().fuel_budget = u64::MAX;
+
+        let call = {
+            let id = id.clone();
+            tokio::spawn(async move { call_plugin_function(&id, Some(Duration::from_secs(1))).await })
+        };
+        let instance = registry().get(&id).unwrap().instance.clone();
+        while instance.try_lock().is_ok() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let started = Instant::now();
+        let listed = registry().list_info();
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(listed.iter().any(|info| info.id == id));
+        assert!(!call.is_finished(), "the call ended before the plugins were listed");
+
+        assert!(call.await.unwrap().is_err());
+    }
+
+    // `get-number` asks for 128MiB more memory, twice the default limit
+    const GROWS_PAST_LIMIT: &str = r#"
+    (component
+      (core module $m
+        (memory 1)
+        (func (export "get-number") (result i32)
+          (memory.grow (i32.const 2048))))
+      (core instance $i (instantiate $m))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "test" (instance $test)))
+    "#;
+
+    #[tokio::test]
+    async fn growing_memory_past_the_limit_traps() {
+        let app = TestApp::new();
+        let id = app.load("lib-memory-limit", GROWS_PAST_LIMIT).await.unwrap();
+        let result = call_plugin_function(&id, None).await;
+        assert!(matches!(result, Err(PluginError::Trap(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn endless_loops_run_out_of_fuel() {
+        let app = TestApp::new();
+        let id = app.load("lib-fuel", SPINS).await.unwrap();
+        // Long enough that fuel, not the timeout, is what stops it
+        let result = call_plugin_function(&id, Some(Duration::from_secs(60))).await;
+        assert!(matches!(result, Err(PluginError::Limit(PluginTrap::OutOfFuel))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn a_panic_holding_the_registry_doesnt_break_it() {
+        let app = TestApp::new();
+        let id = app.load("lib-poisoned", testing::ANSWER).await.unwrap();
+
+        let panicked = std::thread::spawn(|| {
+            let _registry = registry();
+            panic!("a plugin call panicked");
+        })
+        .join();
+        assert!(panicked.is_err());
+
+        assert!(registry().get(&id).is_some());
+        assert_eq!(call_plugin_function(&id, None).await.unwrap(), 42);
+    }
+
+    // `get-number` exits through WASI with `err`, which is exit code 1
+    const EXITS: &str = r#"
+    (component
+      (import "wasi:cli/exit@0.2.0" (instance $exit
+        (export "exit" (func (param "status" (result))))))
+      (core func $exit (canon lower (func $exit "exit")))
+      (core instance $wasi (export "exit" (func $exit)))
+      (core module $m
+        (import "wasi" "exit" (func $exit (param i32)))
+        (func (export "get-number") (result i32)
+          (call $exit (i32.const 1))
+          unreachable))
+      (core instance $i (instantiate $m (with "wasi" (instance $wasi))))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "test" (instance $test)))
+    "#;
+
+    #[tokio::test]
+    async fn exiting_reports_the_exit_code() {
+        let app = TestApp::new();
+        let id = app.load("lib-exits", EXITS).await.unwrap();
+        let result = call_plugin_function(&id, None).await;
+        assert!(matches!(result, Err(PluginError::Exited(1))), "{:?}", result);
+        // Exiting alone doesn't unload the plugin
+        assert!(registry().get(&id).is_some());
+    }
+
+    // Exports the whole `test` interface, so it matches the static bindings.
+    // `transform` answers with the input's `name`
+    const WHOLE_WORLD: &str = r#"
+    (component
+      (core module $m
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (data (i32.const 32) "stats")
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+          (local $ptr i32)
+          (local.set $ptr (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+          (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+          (local.get $ptr))
+        (func (export "get-number") (result i32) i32.const 3)
+        (func (export "transform") (param $name i32) (param $len i32) (param $count i32) (result i32)
+          (i32.store (i32.const 0) (local.get $name))
+          (i32.store (i32.const 4) (local.get $len))
+          (i32.const 0))
+        (func (export "get-stats") (result i32)
+          (i32.store (i32.const 8) (i32.const 1))
+          (i32.store (i32.const 12) (i32.const 32))
+          (i32.store (i32.const 16) (i32.const 5))
+          (i32.const 8)))
+      (core instance $i (instantiate $m))
+      ;; Types in exported functions have to be exported themselves
+      (type $transform-input-def (record (field "name" string) (field "count" u32)))
+      (type $stats-def (record (field "count" u32) (field "label" string)))
+      (export $transform-input "transform-input" (type $transform-input-def))
+      (export $stats "stats" (type $stats-def))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (func $transform (param "input" $transform-input) (result string)
+        (canon lift (core func $i "transform") (memory $i "memory") (realloc (func $i "realloc"))))
+      (func $get-stats (result $stats) (canon lift (core func $i "get-stats") (memory $i "memory")))
+      (instance $test
+        (export "get-number" (func $get-number))
+        (export "transform" (func $transform))
+        (export "get-stats" (func $get-stats)))
+      (export "test" (instance $test)))
+    "#;
+
+    #[tokio::test]
+    async fn records_go_through_the_static_bindings() {
+        let app = TestApp::new();
+        let id = app.load("lib-transform", WHOLE_WORLD).await.unwrap();
+        let instance = registry().get(&id).unwrap().instance.clone();
+        {
+            let mut guard = instance.lock().await;
+            let PluginInstance { store, instance } = &mut *guard;
+            assert!(static_bindings(store, instance).is_some());
+        }
+
+        let input = exports::test::TransformInput {
+            name: "pato".to_string(),
+            count: 2,
+        };
+        assert_eq!(call_plugin_transform(&id, &input, None).await.unwrap(), "pato");
+    }
+
+    #[tokio::test]
+    async fn a_missing_plugins_directory_is_created_and_loads_nothing() {
+        let app = TestApp::new();
+        let dir = app.plugins_dir();
+        let summary = load_wasm_plugins(&app.handle, testing::engine(), &dir, MissingDirMode::Create, load_concurrency())
+            .await
+            .unwrap();
+        assert!(dir.is_dir());
+        assert_eq!((summary.loaded, summary.failed, summary.skipped), (0, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn a_missing_plugins_directory_can_fail_startup() {
+        let app = TestApp::new();
+        let dir = app.plugins_dir();
+        let result = load_wasm_plugins(&app.handle, testing::engine(), &dir, MissingDirMode::Error, load_concurrency())
+            .await;
+        assert!(matches!(result, Err(PluginError::Io(_))));
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn a_missing_plugins_directory_can_be_ignored() {
+        let app = TestApp::new();
+        let dir = app.plugins_dir();
+        let summary = load_wasm_plugins(&app.handle, testing::engine(), &dir, MissingDirMode::Ignore, load_concurrency())
+            .await
+            .unwrap();
+        assert!(!dir.exists());
+        assert_eq!((summary.loaded, summary.failed, summary.skipped), (0, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn the_summary_counts_what_was_in_the_directory() {
+        let app = TestApp::new();
+        app.write_plugin("lib-summary.wasm", &testing::component(testing::ANSWER), None);
+        app.write_plugin("lib-summary-broken.wasm", b"not wasm", None);
+        std::fs::write(app.plugins_dir().join("README.md"), "not a plugin").unwrap();
+
+        let dir = app.plugins_dir();
+        let summary = load_wasm_plugins(&app.handle, testing::engine(), &dir, MissingDirMode::Create, load_concurrency())
+            .await
+            .unwrap();
+        assert_eq!((summary.loaded, summary.failed, summary.skipped), (1, 1, 1));
+    }
+
+    #[tokio::test]
+    async fn no_more_than_the_limit_load_at_once() {
+        let app = TestApp::new();
+        for n in 0..4 {
+            app.write_plugin(&format!("lib-concurrency-{}.wasm", n), &testing::component(testing::ANSWER), None);
+        }
+
+        let dir = app.plugins_dir();
+        let summary = load_wasm_plugins(&app.handle, testing::engine(), &dir, MissingDirMode::Error, 1).await;
+        assert_eq!(summary.unwrap().loaded, 4);
+        assert_eq!(LOAD_PEAKS.lock().unwrap()[&dir], 1);
+    }
+
+    #[test]
+    fn load_concurrency_falls_back_to_one_per_cpu() {
+        assert_eq!(parse_load_concurrency(Some("3")), 3);
+        // Zero isn't a limit
+        let per_cpu = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        assert_eq!(parse_load_concurrency(Some("0")), per_cpu);
+        assert_eq!(parse_load_concurrency(Some("lots")), per_cpu);
+        assert_eq!(parse_load_concurrency(None), per_cpu);
+    }
+
+    #[test]
+    fn engine_errors_call_out_a_missing_component_model() {
+        let missing = std::io::Error::other("support for the component model is not enabled");
+        let reason = describe_engine_error(&missing);
+        assert!(reason.starts_with("plugins need the wasm component model"), "{}", reason);
+        assert!(reason.contains(version::WASMTIME));
+
+        let other = std::io::Error::other("out of memory");
+        let reason = describe_engine_error(&other);
+        assert!(reason.starts_with("couldn't start the plugin engine"), "{}", reason);
+    }
+
+    #[tokio::test]
+    async fn functions_stop_at_their_own_deadline() {
+        let app = TestApp::new();
+        let manifest = r#"
+            id = "lib-function-timeout"
+            name = "Function timeout"
+            version = "1.0.0"
+            fuel = 1000000000000
+            [timeouts]
+            get-number = 50
+        "#;
+        let id = app.load_file("lib-function-timeout.wasm", SPINS, Some(manifest)).await.unwrap();
+
+        let started = Instant::now();
+        let result = call_plugin_function(&id, None).await;
+        assert!(matches!(&result, Err(PluginError::FunctionTimeout(f)) if f == "test#get-number"), "{:?}", result);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn plugins_off_the_static_bindings_are_called_dynamically() {
+        let app = TestApp::new();
+        // Only `get-number`, so it doesn't match the `plugin` world
+        let id = app.load("lib-dynamic-fallback", testing::ANSWER).await.unwrap();
+        let instance = registry().get(&id).unwrap().instance.clone();
+        {
+            let mut guard = instance.lock().await;
+            let PluginInstance { store, instance } = &mut *guard;
+            assert!(static_bindings(store, instance).is_none());
+        }
+        assert_eq!(call_plugin_function(&id, None).await.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn a_failed_reload_keeps_the_running_instance() {
+        let app = TestApp::new();
+        let id = app.load_file("lib-reload-broken.wasm", testing::ANSWER, None).await.unwrap();
+        let before = registry().get(&id).unwrap().instance.clone();
+
+        app.write_plugin("lib-reload-broken.wasm", b"not wasm", None);
+        assert!(reload_plugin(&app.handle, &id).await.is_err());
+
+        assert!(Arc::ptr_eq(&before, &registry().get(&id).unwrap().instance));
+        assert_eq!(call_plugin_function(&id, None).await.unwrap(), 42);
+        assert_eq!(app.emitted("plugin-reload-failed").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_successful_reload_swaps_the_instance() {
+        let app = TestApp::new();
+        let id = app.load_file("lib-reload-swap.wasm", testing::ANSWER, None).await.unwrap();
+        let before = registry().get(&id).unwrap().instance.clone();
+
+        reload_plugin(&app.handle, &id).await.unwrap();
+
+        assert!(!Arc::ptr_eq(&before, &registry().get(&id).unwrap().instance));
+        assert_eq!(call_plugin_function(&id, None).await.unwrap(), 42);
+        assert!(app.emitted("plugin-reload-failed").is_empty());
+    }
+
+    #[tokio::test]
+    async fn embedded_plugins_load_from_memory_and_cant_be_reloaded() {
+        let app = TestApp::new();
+        let id = app.load("lib-embedded", testing::ANSWER).await.unwrap();
+        assert_eq!(id, "lib-embedded");
+
+        let info = registry().list_info().into_iter().find(|info| info.id == id).unwrap();
+        assert_eq!(info.id_source, Some(IdSource::Embedded));
+        assert_eq!(call_plugin_function(&id, None).await.unwrap(), 42);
+
+        let result = reload_plugin(&app.handle, &id).await;
+        assert!(matches!(&result, Err(PluginError::Embedded(embedded)) if *embedded == id), "{:?}", result);
+    }
+
+    // `shutdown` either records that it ran, for `get-number` to report, or never returns
+    fn shutdown_hook(body: &str) -> String {
+        format!(
+            r#"
+    (component
+      (core module $m
+        (global $shut (mut i32) (i32.const 0))
+        (func (export "shutdown") {})
+        (func (export "get-number") (result i32) (global.get $shut)))
+      (core instance $i (instantiate $m))
+      (func $shutdown (canon lift (core func $i "shutdown")))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $lifecycle (export "shutdown" (func $shutdown)))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "pato:plugin-ui/lifecycle@0.1.0" (instance $lifecycle))
+      (export "test" (instance $test)))
+    "#,
+            body
+        )
+    }
+
+    #[tokio::test]
+    async fn a_hanging_shutdown_hook_doesnt_stop_the_others() {
+        let app = TestApp::new();
+        let hangs = app.load("lib-shutdown-hangs", &shutdown_hook("(loop br 0)")).await.unwrap();
+        let finishes = app
+            .load("lib-shutdown-finishes", &shutdown_hook("(global.set $shut (i32.const 1))"))
+            .await
+            .unwrap();
+        let (instance, fuel) = plugin_instance(&finishes).unwrap();
+
+        let started = Instant::now();
+        shutdown_plugins(vec![hangs.clone(), finishes.clone()]).await;
+        assert!(started.elapsed() < SHUTDOWN_TIMEOUT + Duration::from_secs(2));
+        assert!(registry().get(&hangs).is_none() && registry().get(&finishes).is_none());
+
+        // The second plugin's hook still ran, after the first was given up on
+        let mut guard = instance.lock().await;
+        let PluginInstance { store, instance } = &mut *guard;
+        let get_number = dynamic::find_func(store, instance, "test#get-number")
+            .unwrap()
+            .typed::<(), (u32,)>(&*store)
+            .unwrap();
+        arm_call_limits(store, fuel, "test#get-number", None).unwrap();
+        assert_eq!(get_number.call_async(&mut *store, ()).await.unwrap(), (1,));
+    }
+
+    #[test]
+    fn derived_ids_follow_the_file() {
+        let path = Path::new("plugins/plugin_ui.wasm");
+        assert_eq!(derived_id(path, b"one", |_| false).unwrap(), "plugin_ui");
+
+        // Once the stem is taken, the file name and bytes tell plugins apart
+        let taken = |id: &str| id == "plugin_ui";
+        let hashed = |path: &str, bytes: &[u8]| derived_id(Path::new(path), bytes, taken).unwrap();
+        let id = hashed("plugins/plugin_ui.wasm", b"one");
+        assert!(id.starts_with("plugin_ui-") && id.len() == "plugin_ui-".len() + 8, "{}", id);
+        // Where the file lives doesn't matter, only its name and bytes
+        assert_eq!(hashed("elsewhere/plugin_ui.wasm", b"one"), id);
+        assert_ne!(hashed("plugins/plugin_ui.wasm", b"two"), id);
+    }
+
+    #[test]
+    fn taken_ids_get_the_first_free_suffix() {
+        let taken = ["dup", "dup-2", "dup-4"];
+        let is_taken = |id: &str| taken.contains(&id);
+        assert_eq!(first_free_id("free".to_string(), is_taken), "free");
+        assert_eq!(first_free_id("dup".to_string(), is_taken), "dup-3");
+        assert_eq!(first_free_id("dup-2".to_string(), is_taken), "dup-2-2");
+    }
+
+    #[tokio::test]
+    async fn plugins_sharing_a_file_name_get_their_own_ids() {
+        let app = TestApp::new();
+        let seven = testing::component(&testing::ANSWER.replace("i32.const 42", "i32.const 7"));
+        let first = app.write_plugin("lib-twins.wasm", &testing::component(testing::ANSWER), None);
+        let first = load_plugin(&app.handle, testing::engine(), &first).await.unwrap();
+        assert_eq!(first, "lib-twins");
+
+        // The same file name in two other directories, the second a byte-for-byte copy
+        let mut ids = Vec::new();
+        for dir in ["elsewhere", "copy"] {
+            let path = app.config_dir().join(dir).join("lib-twins.wasm");
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &seven).unwrap();
+            ids.push(load_plugin(&app.handle, testing::engine(), &path).await.unwrap());
+        }
+        assert!(ids[0].starts_with("lib-twins-"), "{}", ids[0]);
+        assert_eq!(ids[1], format!("{}-2", ids[0]));
+
+        assert_eq!(call_plugin_function(&first, None).await.unwrap(), 42);
+        assert_eq!(call_plugin_function(&ids[1], None).await.unwrap(), 7);
+        // Reloading a file keeps the id it has
+        let reloaded = load_plugin(&app.handle, testing::engine(), &app.plugins_dir().join("lib-twins.wasm")).await;
+        assert_eq!(reloaded.unwrap(), first);
+    }
+
+    #[tokio::test]
+    async fn calls_go_to_the_plugin_named() {
+        let app = TestApp::new();
+        let seven = testing::ANSWER.replace("i32.const 42", "i32.const 7");
+        app.load("lib-by-name", &seven).await.unwrap();
+        app.load("lib-by-name-other", testing::ANSWER).await.unwrap();
+
+        assert_eq!(call_plugin_function("lib-by-name", None).await.unwrap(), 7);
+        assert_eq!(call_plugin_function("lib-by-name-other", None).await.unwrap(), 42);
+        let missing = call_plugin_function("lib-by-name-missing", None).await;
+        assert!(matches!(&missing, Err(PluginError::NotLoaded(name)) if name == "lib-by-name-missing"), "{:?}", missing);
+    }
+
+    #[cfg(feature = "tauri")]
+    #[tokio::test]
+    async fn invoke_plugin_calls_the_function_named() {
+        let app = TestApp::new();
+        let seven = testing::ANSWER.replace("i32.const 42", "i32.const 7");
+        app.load("lib-invoke-plugin", &seven).await.unwrap();
+
+        let invoke = |function: &str| commands::invoke_plugin("lib-invoke-plugin".to_string(), function.to_string(), None);
+        assert_eq!(invoke("get-number").await.unwrap(), 7);
+        assert_eq!(invoke("test#get-number").await.unwrap(), 7);
+        assert!(matches!(invoke("test#missing").await, Err(PluginError::NoSuchFunction(_))));
+    }
+}
@@ -0,0 +1,76 @@
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{Component, ComponentNamedList, Instance, Lower};
+use wasmtime::{Engine, Store};
+
+use crate::error::PluginError;
+use crate::host::PluginHost;
+use crate::registry::{LoadedPlugin, PluginInstance};
+use crate::{arm_call_limits, dynamic};
+
+/// Optional exports from the `lifecycle` interface.
+const INIT_EXPORT: &str = "pato:plugin-ui/lifecycle@0.1.0#init";
+const SHUTDOWN_EXPORT: &str = "pato:plugin-ui/lifecycle@0.1.0#shutdown";
+
+/// Runs the plugin's `init` export, if it has one, on a freshly instantiated store,
+/// passing it the manifest's `init-params`. This runs on every load; respawns
+/// restore the plugin's `Snapshot` instead when it has one ready.
+pub async fn init(
+    store: &mut Store<PluginHost>,
+    instance: &Instance,
+    fuel_budget: u64,
+    params: Vec<(String, String)>,
+) -> Result<(), PluginError> {
+    call_hook(store, instance, INIT_EXPORT, fuel_budget, (params,)).await
+}
+
+/// Whether `component` exports an `init` hook.
+pub fn has_init(engine: &Engine, component: &Component) -> bool {
+    let Some((interface, function)) = INIT_EXPORT.split_once('#') else {
+        return false;
+    };
+    component
+        .component_type()
+        .exports(engine)
+        .any(|(name, item)| match item {
+            ComponentItem::ComponentInstance(instance) => {
+                name == interface && instance.exports(engine).any(|(name, _)| name == function)
+            }
+            _ => false,
+        })
+}
+
+/// Runs the plugin's `shutdown` export, if it has one. Waits for any call
+/// already running against the plugin to finish first.
+pub async fn shutdown(plugin: &LoadedPlugin) -> Result<(), PluginError> {
+    let mut guard = plugin.instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+    call_hook(store, instance, SHUTDOWN_EXPORT, plugin.fuel_budget, ()).await
+}
+
+async fn call_hook<P>(
+    store: &mut Store<PluginHost>,
+    instance: &Instance,
+    name: &str,
+    fuel_budget: u64,
+    params: P,
+) -> Result<(), PluginError>
+where
+    P: ComponentNamedList + Lower + Send + Sync,
+{
+    // Plugins that don't export the hook simply don't need it
+    let func = match dynamic::find_func(store, instance, name) {
+        Ok(func) => func,
+        Err(PluginError::NoSuchFunction(_)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let hook = func
+        .typed::<P, ()>(&*store)
+        .map_err(|e| PluginError::LinkError(e.to_string()))?;
+
+    arm_call_limits(store, fuel_budget, name, None)?;
+    hook.call_async(&mut *store, params).await.map_err(PluginError::trap)?;
+    hook.post_return_async(&mut *store)
+        .await
+        .map_err(PluginError::trap)?;
+    Ok(())
+}
@@ -0,0 +1,414 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::PluginError;
+
+/// Metadata read from the `.toml` file next to a plugin's `.wasm`, e.g.
+/// `plugin_ui.toml` for `plugin_ui.wasm`.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub struct PluginManifest {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub author: Option<String>,
+    /// A preset set of capabilities to start from; see `Profile`
+    #[serde(default)]
+    pub profile: Option<Profile>,
+    /// Host capabilities the plugin asks for on top of its profile; anything not granted is withheld
+    #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    /// Capabilities to take away from the profile
+    #[serde(default)]
+    pub remove_capabilities: Vec<Capability>,
+    /// Where the plugin runs in aggregate calls like `sum_all_numbers`: lower first, ties by id
+    #[serde(default)]
+    pub priority: i32,
+    /// Host directories to preopen, each read-only or read-write
+    #[serde(default)]
+    pub mounts: Vec<Mount>,
+    /// Overrides the default fuel budget for each call
+    #[serde(default)]
+    pub fuel: Option<u64>,
+    /// Send stdout/stderr straight to the host's terminal instead of capturing it (for debugging)
+    #[serde(default)]
+    pub inherit_stdio: bool,
+    /// Replace the WASI clocks and random sources with reproducible ones, for tests.
+    /// The wall clock reads zero even with the `clock` capability, the monotonic
+    /// clock starts at zero and ticks forward a fixed step on every read, and
+    /// random bytes come from a generator seeded with `seed`.
+    #[serde(default)]
+    pub deterministic: bool,
+    /// Seed for the random sources in `deterministic` mode
+    #[serde(default)]
+    pub seed: u64,
+    /// Keep the plugin's `/data` directory when it's unloaded
+    #[serde(default)]
+    pub persistent: bool,
+    /// The least severe `log` messages kept; quieter ones are dropped before
+    /// reaching the host's logger. `set_plugin_log_level` overrides it at runtime
+    #[serde(default)]
+    pub log_level: LogThreshold,
+    /// Plugin ids this plugin may message, or `"*"` for any
+    #[serde(default)]
+    pub send_to: Vec<String>,
+    /// Event bus topics this plugin may `publish` to
+    #[serde(default)]
+    pub publishes: Vec<String>,
+    /// Event bus topics delivered to this plugin's `on-event`
+    #[serde(default)]
+    pub subscribes: Vec<String>,
+    /// Hosts `fetch` may reach, e.g. `api.example.com` or `*.example.com`
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+    /// Default values served to the plugin by `get-config`
+    #[serde(default)]
+    pub config: HashMap<String, String>,
+    /// Startup parameters passed to the plugin's `init` hook
+    #[serde(default)]
+    pub init_params: HashMap<String, String>,
+    /// Shapes of the events the plugin sends with `emit-json`, by event name
+    #[serde(default)]
+    pub events: HashMap<String, EventSchema>,
+    /// Hex Ed25519 signature over the component bytes; a `.sig` file next to the plugin works too
+    #[serde(default)]
+    pub signature: Option<String>,
+    /// Environment variables the plugin sees through `std::env`
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Host environment variables to pass through by name, when they're set; the
+    /// host environment is otherwise hidden, and `env` overrides these
+    #[serde(default)]
+    pub inherit_env: Vec<String>,
+    /// Deadlines for individual functions in milliseconds, overriding the global
+    /// call timeout, e.g. `get-number = 50` or `"test#transform" = 5000`
+    #[serde(default)]
+    pub timeouts: HashMap<String, u64>,
+    /// Host calls the plugin may make per second, by interface
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+    /// Plugins that must load first, by id, with a semver range, e.g. `other-plugin = "^0.2"`
+    #[serde(default)]
+    pub dependencies: HashMap<String, String>,
+}
+
+/// A host directory preopened for the plugin, e.g.
+///
+/// ```toml
+/// [[mounts]]
+/// host = "reports"
+/// guest = "/reports"
+/// mode = "read-only"
+/// ```
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub struct Mount {
+    /// Relative to the mount root, or absolute but inside it
+    pub host: PathBuf,
+    pub guest: String,
+    #[serde(default)]
+    pub mode: MountMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum MountMode {
+    #[default]
+    ReadOnly,
+    ReadWrite,
+}
+
+/// A host capability a plugin can request in its manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capability {
+    /// stdout and stderr, `log`, and events and chunks sent to the app
+    Stdio,
+    /// The plugin's own directory, read-only, at `/plugin`
+    Filesystem,
+    /// The real wall and monotonic clocks, and `timers`
+    Clock,
+    /// `fetch`, still limited to `allowed-hosts`
+    Network,
+    /// `/data`, pato-db, `handle`s, and `get-config`
+    Storage,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Capability::Stdio => "stdio",
+            Capability::Filesystem => "filesystem",
+            Capability::Clock => "clock",
+            Capability::Network => "network",
+            Capability::Storage => "storage",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Named capability presets, chosen with `profile = "..."`:
+///
+/// - `isolated`: `stdio`
+/// - `network`: `stdio`, `network`
+/// - `storage`: `stdio`, `storage`
+/// - `trusted`: `stdio`, `filesystem`, `clock`, `network`, `storage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Profile {
+    Isolated,
+    Network,
+    Storage,
+    Trusted,
+}
+
+impl Profile {
+    pub fn capabilities(self) -> &'static [Capability] {
+        match self {
+            Profile::Isolated => &[Capability::Stdio],
+            Profile::Network => &[Capability::Stdio, Capability::Network],
+            Profile::Storage => &[Capability::Stdio, Capability::Storage],
+            Profile::Trusted => &[
+                Capability::Stdio,
+                Capability::Filesystem,
+                Capability::Clock,
+                Capability::Network,
+                Capability::Storage,
+            ],
+        }
+    }
+}
+
+/// How chatty a plugin's `log` calls may be, e.g. `log-level = "warn"`.
+/// Ordered from most to least verbose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogThreshold {
+    #[default]
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// Calls per second a plugin may make to the chattier host interfaces, e.g.
+///
+/// ```toml
+/// [rate-limits]
+/// log = 50
+/// http = 2
+/// ```
+///
+/// Over the limit, `log` and `emit-event`/`emit-json` calls are dropped and
+/// `fetch` fails with `blocked`.
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct RateLimits {
+    pub log: u32,
+    pub events: u32,
+    pub http: u32,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            log: 100,
+            events: 100,
+            http: 10,
+        }
+    }
+}
+
+/// The expected shape of an `emit-json` payload, e.g.
+///
+/// ```toml
+/// [events.progress]
+/// required = { percent = "number" }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub struct EventSchema {
+    /// Keys the payload object must have, and the JSON type of each
+    #[serde(default)]
+    pub required: HashMap<String, JsonKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum JsonKind {
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Null,
+    Any,
+}
+
+impl JsonKind {
+    fn matches(self, value: &serde_json::Value) -> bool {
+        use serde_json::Value;
+        matches!(
+            (self, value),
+            (JsonKind::Any, _)
+                | (JsonKind::String, Value::String(_))
+                | (JsonKind::Number, Value::Number(_))
+                | (JsonKind::Boolean, Value::Bool(_))
+                | (JsonKind::Array, Value::Array(_))
+                | (JsonKind::Object, Value::Object(_))
+                | (JsonKind::Null, Value::Null)
+        )
+    }
+}
+
+impl EventSchema {
+    /// Describes the first way `payload` doesn't fit the schema, if any.
+    pub fn check(&self, payload: &serde_json::Value) -> Result<(), String> {
+        if self.required.is_empty() {
+            return Ok(());
+        }
+        let object = payload.as_object().ok_or("payload must be a JSON object")?;
+        for (key, kind) in &self.required {
+            match object.get(key) {
+                Some(value) if kind.matches(value) => {}
+                Some(value) => return Err(format!("`{}` should be {:?}, got {}", key, kind, value)),
+                None => return Err(format!("missing `{}`", key)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PluginManifest {
+    /// The profile's capabilities, plus `capabilities`, minus `remove-capabilities`.
+    pub fn granted_capabilities(&self) -> Vec<Capability> {
+        let mut granted = self.profile.map(|p| p.capabilities().to_vec()).unwrap_or_default();
+        for capability in &self.capabilities {
+            if !granted.contains(capability) {
+                granted.push(*capability);
+            }
+        }
+        granted.retain(|capability| !self.remove_capabilities.contains(capability));
+        granted
+    }
+
+    /// `timeouts` as durations.
+    pub fn function_timeouts(&self) -> HashMap<String, Duration> {
+        self.timeouts
+            .iter()
+            .map(|(function, millis)| (function.clone(), Duration::from_millis(*millis)))
+            .collect()
+    }
+
+    /// `init-params` as the list `init` takes, sorted by key so every load sees the same order.
+    pub fn init_params(&self) -> Vec<(String, String)> {
+        let mut params: Vec<(String, String)> = self
+            .init_params
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        params.sort();
+        params
+    }
+
+    /// The JSON Schema for manifests, derived from this struct so it can't drift from what's parsed.
+    /// `plugin-manifest.schema.json` at the repo root is a copy, regenerated with
+    /// `cargo run --bin manifest-schema`.
+    pub fn schema_json() -> String {
+        let schema = schemars::schema_for!(PluginManifest);
+        serde_json::to_string_pretty(&schema).expect("schemas always serialize")
+    }
+
+    /// Where the manifest for the plugin at `wasm_path` lives.
+    pub fn path_for(wasm_path: &Path) -> PathBuf {
+        wasm_path.with_extension("toml")
+    }
+
+    /// Reads the manifest next to `wasm_path`, or `Ok(None)` if the plugin doesn't have one.
+    pub fn load_for(wasm_path: &Path) -> Result<Option<Self>, PluginError> {
+        let path = Self::path_for(wasm_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let text = std::fs::read_to_string(&path).map_err(|e| PluginError::Io(e.to_string()))?;
+        Self::parse(&text, &path).map(Some)
+    }
+
+    /// Parses and validates manifest text; `origin` is only used in error messages.
+    pub fn parse(text: &str, origin: &Path) -> Result<Self, PluginError> {
+        let manifest: PluginManifest = toml::from_str(text)
+            .map_err(|e| PluginError::InvalidManifest(format!("{:?}: {}", origin, e)))?;
+        manifest.validate()?;
+        Ok(manifest)
+    }
+
+    /// Serde catches missing fields; this catches ones that are present but empty.
+    fn validate(&self) -> Result<(), PluginError> {
+        for (field, value) in [("id", &self.id), ("name", &self.name), ("version", &self.version)] {
+            if value.trim().is_empty() {
+                return Err(PluginError::InvalidManifest(format!("`{}` must not be empty", field)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(extra: &str) -> PluginManifest {
+        let text = format!("id = \"example\"\nname = \"Example\"\nversion = \"1.0.0\"\n{}", extra);
+        PluginManifest::parse(&text, Path::new("example.toml")).unwrap()
+    }
+
+    #[test]
+    fn profiles_are_a_starting_point() {
+        let manifest = manifest("profile = \"network\"\ncapabilities = [\"clock\"]\nremove-capabilities = [\"stdio\"]");
+        assert_eq!(manifest.granted_capabilities(), [Capability::Network, Capability::Clock]);
+    }
+
+    #[test]
+    fn asking_for_a_profile_capability_again_doesnt_duplicate_it() {
+        let manifest = manifest("profile = \"isolated\"\ncapabilities = [\"stdio\"]");
+        assert_eq!(manifest.granted_capabilities(), [Capability::Stdio]);
+    }
+
+    #[test]
+    fn unknown_profiles_are_rejected() {
+        let text = "id = \"example\"\nname = \"Example\"\nversion = \"1.0.0\"\nprofile = \"root\"";
+        assert!(PluginManifest::parse(text, Path::new("example.toml")).is_err());
+    }
+
+    #[test]
+    fn the_committed_schema_matches_the_manifest() {
+        let committed = include_str!("../../plugin-manifest.schema.json");
+        let committed: serde_json::Value = serde_json::from_str(committed).unwrap();
+        let current: serde_json::Value = serde_json::from_str(&PluginManifest::schema_json()).unwrap();
+        assert!(
+            committed == current,
+            "plugin-manifest.schema.json is out of date; run `cargo run --bin manifest-schema > ../plugin-manifest.schema.json`"
+        );
+    }
+
+    #[test]
+    fn the_sample_manifest_fits_the_schema() {
+        let text = include_str!("../../plugin-ui/plugin_ui.toml");
+        let manifest = PluginManifest::parse(text, Path::new("plugin_ui.toml")).unwrap();
+        assert_eq!(manifest.profile, Some(Profile::Storage));
+
+        // Every key it uses is one the schema describes
+        let schema: serde_json::Value = serde_json::from_str(&PluginManifest::schema_json()).unwrap();
+        let table: toml::Table = toml::from_str(text).unwrap();
+        for key in table.keys() {
+            assert!(schema["properties"].get(key).is_some(), "the schema doesn't describe {}", key);
+        }
+    }
+}
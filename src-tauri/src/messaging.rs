@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
+use tokio::sync::Notify;
+
+use crate::error::PluginError;
+use crate::registry::PluginInstance;
+use crate::{arm_call_limits, dynamic, plugin_instance, recovery, registry};
+
+/// The export a plugin implements to receive messages, from the `inbox` interface.
+const RECEIVE_EXPORT: &str = "pato:plugin-ui/inbox@0.1.0#receive";
+
+/// The export a plugin implements to receive events, from the `subscriber` interface.
+const ON_EVENT_EXPORT: &str = "pato:plugin-ui/subscriber@0.1.0#on-event";
+
+/// A message from one plugin to another, waiting to be delivered.
+pub struct Message {
+    pub from: String,
+    pub to: String,
+    pub body: Vec<u8>,
+}
+
+/// An event published on a topic, waiting to be delivered to its subscribers.
+pub struct Event {
+    pub from: String,
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+// Messages and events share a queue so they're delivered in the order they were sent
+enum Delivery {
+    Message(Message),
+    Event(Event),
+}
+
+// Sent but not yet delivered, oldest first
+static QUEUE: LazyLock<Mutex<VecDeque<Delivery>>> = LazyLock::new(|| Mutex::new(VecDeque::new()));
+
+// Wakes the delivery task when something is queued
+static PENDING: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Queues `message` for delivery. This is called from inside the sender's
+/// store, so it never touches a plugin directly.
+pub fn enqueue(message: Message) {
+    queue().push_back(Delivery::Message(message));
+    PENDING.notify_one();
+}
+
+/// Queues `event` for every plugin subscribed to its topic. Like `enqueue`, this
+/// is called from inside the publisher's store.
+pub fn publish(event: Event) {
+    queue().push_back(Delivery::Event(event));
+    PENDING.notify_one();
+}
+
+/// Starts the task that delivers queued messages and events, one at a time and in order.
+pub fn start() {
+    crate::async_runtime::spawn(async {
+        loop {
+            PENDING.notified().await;
+            while let Some(delivery) = next_delivery() {
+                match delivery {
+                    Delivery::Message(message) => {
+                        if let Err(e) = deliver(&message).await {
+                            println!("⚠️ Failed to deliver message from {} to {}: {}", message.from, message.to, e);
+                        }
+                    }
+                    Delivery::Event(event) => broadcast(event).await,
+                }
+            }
+        }
+    });
+}
+
+// A panic elsewhere while holding the lock can't leave a VecDeque half-pushed
+fn queue() -> MutexGuard<'static, VecDeque<Delivery>> {
+    QUEUE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn next_delivery() -> Option<Delivery> {
+    queue().pop_front()
+}
+
+/// Calls the target's `receive`. The sender's call has returned (or is running in
+/// its own store), so this never re-enters a store that's already borrowed. A trap
+/// leaves the target to be re-instantiated, like any other call.
+async fn deliver(message: &Message) -> Result<(), PluginError> {
+    recovery::with_trap_retries(&message.to, || deliver_once(message)).await
+}
+
+async fn deliver_once(message: &Message) -> Result<(), PluginError> {
+    let (instance, fuel_budget) = plugin_instance(&message.to)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let receive = dynamic::find_func(store, instance, RECEIVE_EXPORT)?
+        .typed::<(String, Vec<u8>), ()>(&*store)
+        .map_err(|e| PluginError::LinkError(e.to_string()))?;
+
+    arm_call_limits(store, fuel_budget, RECEIVE_EXPORT, None)?;
+    receive
+        .call_async(&mut *store, (message.from.clone(), message.body.clone()))
+        .await
+        .map_err(PluginError::trap)?;
+    receive
+        .post_return_async(&mut *store)
+        .await
+        .map_err(PluginError::trap)?;
+
+    Ok(())
+}
+
+/// Delivers `event` to each current subscriber in turn. One subscriber failing
+/// doesn't stop the others from hearing about it.
+async fn broadcast(event: Event) {
+    // Looked up now rather than at publish time, so plugins loaded since still hear it
+    let subscribers = registry().subscribers(&event.topic);
+    for subscriber in subscribers {
+        if let Err(e) = deliver_event(&subscriber, &event).await {
+            println!(
+                "⚠️ Failed to deliver {} event from {} to {}: {}",
+                event.topic, event.from, subscriber, e
+            );
+        }
+    }
+}
+
+/// Calls the subscriber's `on-event`, behind its store lock and with the same
+/// trap recovery as any other call.
+async fn deliver_event(subscriber: &str, event: &Event) -> Result<(), PluginError> {
+    recovery::with_trap_retries(subscriber, || deliver_event_once(subscriber, event)).await
+}
+
+async fn deliver_event_once(subscriber: &str, event: &Event) -> Result<(), PluginError> {
+    let (instance, fuel_budget) = plugin_instance(subscriber)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let on_event = dynamic::find_func(store, instance, ON_EVENT_EXPORT)?
+        .typed::<(String, Vec<u8>), ()>(&*store)
+        .map_err(|e| PluginError::LinkError(e.to_string()))?;
+
+    arm_call_limits(store, fuel_budget, ON_EVENT_EXPORT, None)?;
+    on_event
+        .call_async(&mut *store, (event.topic.clone(), event.payload.clone()))
+        .await
+        .map_err(PluginError::trap)?;
+    on_event
+        .post_return_async(&mut *store)
+        .await
+        .map_err(PluginError::trap)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pato::plugin_ui::messaging::Host as _;
+    use crate::testing::{with_host, TestApp, ANSWER};
+
+    // `on-event` counts the events it's been handed, and `get-number` says how many
+    const COUNTS_EVENTS: &str = r#"
+    (component
+      (core module $m
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (global $events (mut i32) (i32.const 0))
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+          (local $ptr i32)
+          (local.set $ptr (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+          (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+          (local.get $ptr))
+        (func (export "on-event") (param i32 i32 i32 i32)
+          (global.set $events (i32.add (global.get $events) (i32.const 1))))
+        (func (export "get-number") (result i32) (global.get $events)))
+      (core instance $i (instantiate $m))
+      (func $on-event (param "topic" string) (param "payload" (list u8))
+        (canon lift (core func $i "on-event") (memory $i "memory") (realloc (func $i "realloc"))))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $subscriber (export "on-event" (func $on-event)))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "pato:plugin-ui/subscriber@0.1.0" (instance $subscriber))
+      (export "test" (instance $test)))
+    "#;
+
+    fn manifest(id: &str, extra: &str) -> String {
+        format!("id = \"{}\"\nname = \"{}\"\nversion = \"1.0.0\"\n{}", id, id, extra)
+    }
+
+    async fn events_seen(id: &str) -> serde_json::Value {
+        dynamic::invoke(id, "test#get-number", serde_json::Value::Null, None).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn events_reach_only_the_topics_subscribers() {
+        let app = TestApp::new();
+        let subscriber = app
+            .load_file("bus-subscriber.wasm", COUNTS_EVENTS, Some(&manifest("bus-subscriber", "subscribes = [\"bus-news\"]")))
+            .await
+            .unwrap();
+        let bystander = app
+            .load_file("bus-bystander.wasm", COUNTS_EVENTS, Some(&manifest("bus-bystander", "subscribes = [\"bus-other\"]")))
+            .await
+            .unwrap();
+
+        assert_eq!(registry().subscribers("bus-news"), [subscriber.as_str()]);
+        broadcast(Event {
+            from: "bus-publisher".to_string(),
+            topic: "bus-news".to_string(),
+            payload: b"hello".to_vec(),
+        })
+        .await;
+
+        assert_eq!(events_seen(&subscriber).await, 1);
+        assert_eq!(events_seen(&bystander).await, 0);
+    }
+
+    #[tokio::test]
+    async fn plugins_only_publish_to_declared_topics() {
+        let app = TestApp::new();
+        let publisher = app
+            .load_file("bus-publisher.wasm", ANSWER, Some(&manifest("bus-publisher", "publishes = [\"bus-declared\"]")))
+            .await
+            .unwrap();
+        let queued = |topic: &str| {
+            queue()
+                .iter()
+                .any(|delivery| matches!(delivery, Delivery::Event(event) if event.topic == topic))
+        };
+
+        with_host(&publisher, |host| {
+            host.publish("bus-declared".to_string(), Vec::new());
+            host.publish("bus-undeclared".to_string(), Vec::new());
+        })
+        .await;
+        assert!(queued("bus-declared"));
+        assert!(!queued("bus-undeclared"));
+    }
+
+    #[tokio::test]
+    async fn subscribers_that_trap_start_afresh() {
+        let app = TestApp::new();
+        // Counts the event, then traps with the count still in its store
+        let traps = COUNTS_EVENTS.replace(
+            "(global.set $events (i32.add (global.get $events) (i32.const 1))))",
+            "(global.set $events (i32.add (global.get $events) (i32.const 1))) unreachable)",
+        );
+        let subscriber = app
+            .load_file("bus-trapping.wasm", &traps, Some(&manifest("bus-trapping", "subscribes = [\"bus-traps\"]")))
+            .await
+            .unwrap();
+
+        broadcast(Event {
+            from: "bus-publisher".to_string(),
+            topic: "bus-traps".to_string(),
+            payload: Vec::new(),
+        })
+        .await;
+
+        assert!(registry().get(&subscriber).unwrap().dirty);
+        assert_eq!(events_seen(&subscriber).await, 0);
+    }
+}
@@ -0,0 +1,58 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use wasmtime::Store;
+
+use crate::host::PluginHost;
+use crate::registry;
+
+/// Running totals for one exported function of one plugin.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FunctionMetrics {
+    pub calls: u64,
+    /// Calls that trapped, timed out, or otherwise failed
+    pub errors: u64,
+    pub total_micros: u64,
+    pub max_micros: u64,
+    pub fuel_consumed: u64,
+}
+
+/// Everything recorded for a plugin since it was last (re)loaded, by function name.
+#[derive(Debug, Clone, Serialize)]
+pub struct Metrics {
+    pub id: String,
+    pub functions: BTreeMap<String, FunctionMetrics>,
+    /// Host calls dropped or refused for going over the plugin's rate limits, by interface
+    pub throttled: BTreeMap<String, u64>,
+}
+
+/// Adds a finished call to `id`'s metrics. Calls that outlive a reload are dropped,
+/// since the new instance starts from zero.
+pub fn record(id: &str, function: &str, elapsed: Duration, fuel_consumed: u64, failed: bool) {
+    let mut registry = registry();
+    let Some(loaded) = registry.get_mut(id) else {
+        return;
+    };
+
+    let micros = elapsed.as_micros() as u64;
+    let metrics = loaded.metrics.entry(function.to_string()).or_default();
+    metrics.calls += 1;
+    metrics.errors += u64::from(failed);
+    metrics.total_micros += micros;
+    metrics.max_micros = metrics.max_micros.max(micros);
+    metrics.fuel_consumed += fuel_consumed;
+}
+
+/// Counts a host call plugin `id` made over its rate limit for `interface`. Runs
+/// inside the plugin's call, which never holds the registry lock.
+pub fn record_throttled(id: &str, interface: &str) {
+    let mut registry = registry();
+    if let Some(loaded) = registry.get_mut(id) {
+        *loaded.throttled.entry(interface.to_string()).or_default() += 1;
+    }
+}
+
+/// How much of `budget` the last call burned, given the store was refuelled to it first.
+pub fn fuel_used(store: &Store<PluginHost>, budget: u64) -> u64 {
+    budget.saturating_sub(store.get_fuel().unwrap_or(budget))
+}
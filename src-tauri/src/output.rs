@@ -0,0 +1,114 @@
+use bytes::Bytes;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use wasmtime_wasi::{HostOutputStream, StdoutStream, StreamResult, Subscribe};
+
+/// Lines kept per stream; older lines are dropped first.
+pub const MAX_LINES: usize = 1_000;
+
+/// Longest a single line may grow before it's split, so a plugin that never
+/// prints a newline can't grow the buffer without bound.
+const MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// A plugin's captured stdout or stderr, as the last `MAX_LINES` lines.
+#[derive(Clone, Default)]
+pub struct OutputBuffer(Arc<Mutex<Lines>>);
+
+#[derive(Default)]
+struct Lines {
+    lines: VecDeque<String>,
+    /// Output after the last newline
+    partial: Vec<u8>,
+}
+
+impl OutputBuffer {
+    fn lock(&self) -> MutexGuard<'_, Lines> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, bytes: &[u8]) {
+        let mut buffer = self.lock();
+        for &byte in bytes {
+            if byte == b'\n' || buffer.partial.len() >= MAX_LINE_BYTES {
+                let line = String::from_utf8_lossy(&buffer.partial).into_owned();
+                buffer.partial.clear();
+                if buffer.lines.len() == MAX_LINES {
+                    buffer.lines.pop_front();
+                }
+                buffer.lines.push_back(line);
+                if byte == b'\n' {
+                    continue;
+                }
+            }
+            buffer.partial.push(byte);
+        }
+    }
+
+    /// The buffered lines, oldest first, including any unfinished last line.
+    pub fn lines(&self) -> Vec<String> {
+        let buffer = self.lock();
+        let mut lines: Vec<String> = buffer.lines.iter().cloned().collect();
+        if !buffer.partial.is_empty() {
+            lines.push(String::from_utf8_lossy(&buffer.partial).into_owned());
+        }
+        lines
+    }
+}
+
+impl StdoutStream for OutputBuffer {
+    fn stream(&self) -> Box<dyn HostOutputStream> {
+        Box::new(CaptureStream(self.clone()))
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+/// The stream half handed to WASI; every write lands in the shared buffer.
+struct CaptureStream(OutputBuffer);
+
+impl HostOutputStream for CaptureStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.0.push(&bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        // The buffer drops old lines instead of filling up, so it's always ready for more
+        Ok(64 * 1024)
+    }
+}
+
+#[async_trait::async_trait]
+impl Subscribe for CaptureStream {
+    async fn ready(&mut self) {}
+}
+
+/// Both captured streams of one plugin.
+#[derive(Clone, Default)]
+pub struct CapturedOutput {
+    pub stdout: OutputBuffer,
+    pub stderr: OutputBuffer,
+}
+
+/// A snapshot of a plugin's captured output, as returned by `plugin_output`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginOutput {
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+}
+
+impl CapturedOutput {
+    pub fn snapshot(&self) -> PluginOutput {
+        PluginOutput {
+            stdout: self.stdout.lines(),
+            stderr: self.stderr.lines(),
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::PluginError;
+use crate::manifest::PluginManifest;
+
+/// Extension of single-file plugin packages: a zip holding `plugin.toml` and one `.wasm`.
+pub const EXTENSION: &str = "patopkg";
+
+/// Where the manifest lives inside a package.
+const MANIFEST_NAME: &str = "plugin.toml";
+
+/// The most any one entry may unpack to. Sizes in the archive's directory can
+/// lie, so this is enforced while reading, not just checked up front.
+const MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// The most a whole package may unpack to, manifest included.
+const MAX_PACKAGE_BYTES: u64 = 512 * 1024 * 1024;
+
+pub fn is_package_file(path: &Path) -> bool {
+    path.extension().and_then(|s| s.to_str()) == Some(EXTENSION)
+}
+
+/// Reads a plugin's manifest and component bytes, either from a loose `.wasm`
+/// (with its optional `.toml` alongside) or from a `.patopkg`.
+pub fn read_plugin(path: &Path) -> Result<(Option<PluginManifest>, Vec<u8>), PluginError> {
+    if is_package_file(path) {
+        let (manifest, bytes) = read_package(path)?;
+        return Ok((Some(manifest), bytes));
+    }
+
+    let manifest = PluginManifest::load_for(path)?;
+    let bytes = std::fs::read(path).map_err(|e| PluginError::Io(e.to_string()))?;
+    Ok((manifest, bytes))
+}
+
+/// Reads the manifest and component straight out of the archive, without unpacking it.
+///
+/// A package must have `plugin.toml` and exactly one `.wasm` at its root; anything
+/// else in it (a README, a licence) is ignored.
+fn read_package(path: &Path) -> Result<(PluginManifest, Vec<u8>), PluginError> {
+    let invalid = |message: String| PluginError::InvalidPackage(format!("{:?}: {}", path, message));
+
+    let file = std::fs::File::open(path).map_err(|e| PluginError::Io(e.to_string()))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| invalid(e.to_string()))?;
+
+    let mut manifest_text = None;
+    let mut component = None;
+    let mut unpacked = 0;
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index).map_err(|e| invalid(e.to_string()))?;
+        let name = entry.name().to_string();
+        if entry.is_dir() || name.contains('/') {
+            continue;
+        }
+
+        let limit = MAX_ENTRY_BYTES.min(MAX_PACKAGE_BYTES - unpacked);
+        if name == MANIFEST_NAME {
+            let size = entry.size();
+            let bytes = read_entry(entry, size, limit).map_err(|e| invalid(format!("{}: {}", name, e)))?;
+            unpacked += bytes.len() as u64;
+            let text = String::from_utf8(bytes).map_err(|e| invalid(format!("{}: {}", name, e)))?;
+            manifest_text = Some(text);
+        } else if name.ends_with(".wasm") {
+            if component.is_some() {
+                return Err(invalid("contains more than one .wasm component".to_string()));
+            }
+            let size = entry.size();
+            let bytes = read_entry(entry, size, limit).map_err(|e| invalid(format!("{}: {}", name, e)))?;
+            unpacked += bytes.len() as u64;
+            component = Some(bytes);
+        }
+    }
+
+    let manifest_text = manifest_text.ok_or_else(|| invalid(format!("missing {}", MANIFEST_NAME)))?;
+    let component = component.ok_or_else(|| invalid("missing a .wasm component".to_string()))?;
+    let manifest = PluginManifest::parse(&manifest_text, &path.join(MANIFEST_NAME))?;
+    Ok((manifest, component))
+}
+
+/// Reads an entry that claims to be `size` bytes, refusing to go past `limit`
+/// whatever it claims.
+fn read_entry(entry: impl Read, size: u64, limit: u64) -> Result<Vec<u8>, String> {
+    let too_big = || format!("unpacks to more than {} bytes", limit);
+    if size > limit {
+        return Err(too_big());
+    }
+
+    let mut bytes = Vec::with_capacity(size as usize);
+    entry
+        .take(limit + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    if bytes.len() as u64 > limit {
+        return Err(too_big());
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_are_read_up_to_the_limit() {
+        assert_eq!(read_entry(&b"wasm"[..], 4, 4).unwrap(), b"wasm");
+        assert!(read_entry(&b"wasm"[..], 4, 3).is_err());
+    }
+
+    #[test]
+    fn entries_that_understate_their_size_stop_at_the_limit() {
+        let endless = std::io::repeat(0);
+        let error = read_entry(endless, 10, 1024).unwrap_err();
+        assert!(error.contains("more than 1024 bytes"), "{}", error);
+    }
+}
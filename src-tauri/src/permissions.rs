@@ -0,0 +1,163 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use crate::embedder::AppHandle;
+use crate::manifest::{Capability, PluginManifest};
+use crate::sandbox;
+
+/// The user's answers to each plugin's capability requests, kept in the app
+/// config dir so they survive restarts:
+///
+/// ```toml
+/// [plugin-ui]
+/// granted = ["stdio"]
+/// denied = ["storage"]
+/// ```
+const PERMISSIONS_FILE: &str = "permissions.toml";
+
+#[derive(Default, Serialize, Deserialize)]
+struct Decisions {
+    #[serde(default)]
+    granted: BTreeSet<Capability>,
+    #[serde(default)]
+    denied: BTreeSet<Capability>,
+}
+
+/// What the user has decided about one requested capability.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionStatus {
+    Granted,
+    Denied,
+    /// Not asked yet; granted, so plugins keep working until the user says otherwise
+    Undecided,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Permission {
+    pub capability: Capability,
+    pub status: PermissionStatus,
+}
+
+/// Every capability plugin `id` requests, and whether it's allowed.
+#[derive(Debug, Clone, Serialize)]
+pub struct PermissionReport {
+    pub id: String,
+    pub permissions: Vec<Permission>,
+}
+
+/// The capabilities plugin `id` actually gets: what it requests, less anything the user denied.
+pub fn granted(app: &AppHandle, id: &str, manifest: Option<&PluginManifest>) -> Vec<Capability> {
+    let mut capabilities = sandbox::capabilities_for(manifest);
+    let decisions = decisions_for(app, id);
+    capabilities.retain(|capability| !decisions.denied.contains(capability));
+    capabilities
+}
+
+pub fn report(app: &AppHandle, id: &str, manifest: Option<&PluginManifest>) -> PermissionReport {
+    let decisions = decisions_for(app, id);
+    let permissions = sandbox::capabilities_for(manifest)
+        .into_iter()
+        .map(|capability| {
+            let status = if decisions.denied.contains(&capability) {
+                PermissionStatus::Denied
+            } else if decisions.granted.contains(&capability) {
+                PermissionStatus::Granted
+            } else {
+                PermissionStatus::Undecided
+            };
+            Permission { capability, status }
+        })
+        .collect();
+
+    PermissionReport {
+        id: id.to_string(),
+        permissions,
+    }
+}
+
+/// Records the user's decision on `capability` for plugin `id`, for the next time it loads.
+pub fn save(app: &AppHandle, id: &str, capability: Capability, granted: bool) -> Result<(), String> {
+    let path = permissions_path(app).ok_or("no app config directory")?;
+    // Don't overwrite a file we couldn't parse; the user would lose every other decision in it
+    let mut file = read(app)?;
+    let decisions = file.entry(id.to_string()).or_default();
+    if granted {
+        decisions.denied.remove(&capability);
+        decisions.granted.insert(capability);
+    } else {
+        decisions.granted.remove(&capability);
+        decisions.denied.insert(capability);
+    }
+
+    let text = toml::to_string(&file).map_err(|e| e.to_string())?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("{:?}: {}", dir, e))?;
+    }
+    std::fs::write(&path, text).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+fn decisions_for(app: &AppHandle, id: &str) -> Decisions {
+    // A broken file shouldn't stop anything from running
+    match read(app) {
+        Ok(mut file) => file.remove(id).unwrap_or_default(),
+        Err(e) => {
+            println!("⚠️ Ignoring permission decisions: {}", e);
+            Decisions::default()
+        }
+    }
+}
+
+fn permissions_path(app: &AppHandle) -> Option<PathBuf> {
+    app.app_config_dir().map(|dir| dir.join(PERMISSIONS_FILE))
+}
+
+fn read(app: &AppHandle) -> Result<BTreeMap<String, Decisions>, String> {
+    let Some(path) = permissions_path(app).filter(|path| path.exists()) else {
+        return Ok(BTreeMap::new());
+    };
+
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("{:?}: {}", path, e))?;
+    toml::from_str(&text).map_err(|e| format!("{:?}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn only_denied_capabilities_are_withheld() {
+        let app = TestApp::new();
+        // No manifest asks for stdio and storage, and nothing's been decided yet
+        let requested = [Capability::Stdio, Capability::Storage];
+        assert_eq!(granted(&app.handle, "permissions", None), requested);
+
+        save(&app.handle, "permissions", Capability::Storage, true).unwrap();
+        assert_eq!(granted(&app.handle, "permissions", None), requested);
+        let statuses: Vec<_> = report(&app.handle, "permissions", None)
+            .permissions
+            .into_iter()
+            .map(|permission| (permission.capability, permission.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [(Capability::Stdio, PermissionStatus::Undecided), (Capability::Storage, PermissionStatus::Granted)]
+        );
+
+        save(&app.handle, "permissions", Capability::Storage, false).unwrap();
+        assert_eq!(granted(&app.handle, "permissions", None), [Capability::Stdio]);
+    }
+
+    #[test]
+    fn a_broken_permissions_file_isnt_overwritten() {
+        let app = TestApp::new();
+        std::fs::create_dir_all(app.config_dir()).unwrap();
+        let path = app.config_dir().join(PERMISSIONS_FILE);
+        std::fs::write(&path, "not = [toml").unwrap();
+
+        assert!(save(&app.handle, "permissions", Capability::Stdio, true).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "not = [toml");
+    }
+}
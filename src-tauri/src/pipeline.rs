@@ -0,0 +1,62 @@
+use std::time::Instant;
+
+use crate::error::PluginError;
+use crate::registry::PluginInstance;
+use crate::{arm_call_limits, dynamic, handle_exit, metrics, plugin_instance, recovery, registry};
+
+/// Optional export from the `pipeline` interface.
+const PROCESS_EXPORT: &str = "pato:plugin-ui/pipeline@0.1.0#process";
+
+/// Feeds `input` through every enabled plugin's `process`, in priority order,
+/// each output becoming the next input. Plugins without `process` are passed
+/// over; the first stage to fail stops the pipeline.
+pub async fn run(input: String) -> Result<String, PluginError> {
+    let ids = registry().ids_by_priority();
+
+    let mut value = input;
+    for id in ids {
+        let result = recovery::with_trap_retries(&id, || process(&id, value.clone())).await;
+        value = match result {
+            Ok(Some(output)) => output,
+            Ok(None) | Err(PluginError::Disabled(_)) => continue,
+            Err(e) => {
+                if let PluginError::Exited(code) = e {
+                    handle_exit(&id, code).await;
+                }
+                return Err(PluginError::PipelineStage {
+                    stage: id,
+                    error: Box::new(e),
+                });
+            }
+        };
+    }
+
+    Ok(value)
+}
+
+/// Runs one stage, or `Ok(None)` if the plugin has no `process` export.
+async fn process(id: &str, input: String) -> Result<Option<String>, PluginError> {
+    let (instance, fuel_budget) = plugin_instance(id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let func = match dynamic::find_func(store, instance, PROCESS_EXPORT) {
+        Ok(func) => func,
+        Err(PluginError::NoSuchFunction(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let process = func
+        .typed::<(String,), (String,)>(&*store)
+        .map_err(|e| PluginError::LinkError(e.to_string()))?;
+
+    arm_call_limits(store, fuel_budget, PROCESS_EXPORT, None)?;
+    let started = Instant::now();
+    let called = match process.call_async(&mut *store, (input,)).await {
+        Ok((output,)) => process.post_return_async(&mut *store).await.map(|()| output),
+        Err(e) => Err(e),
+    };
+    let fuel_used = metrics::fuel_used(store, fuel_budget);
+    metrics::record(id, "pipeline#process", started.elapsed(), fuel_used, called.is_err());
+
+    called.map(Some).map_err(PluginError::trap)
+}
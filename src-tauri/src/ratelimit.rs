@@ -0,0 +1,92 @@
+use std::time::Instant;
+
+use crate::manifest::RateLimits;
+
+/// Allows `rate` calls a second on average, with bursts of up to a second's worth.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u32) -> Self {
+        TokenBucket {
+            rate: f64::from(rate),
+            tokens: f64::from(rate),
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes a token if there is one; `false` means the call is over the limit.
+    pub fn try_take(&mut self) -> bool {
+        let now = Instant::now();
+        let refill = now.duration_since(self.refilled_at).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.rate);
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// One bucket per rate-limited host interface, for one plugin.
+#[derive(Debug, Clone)]
+pub struct HostRateLimiter {
+    pub log: TokenBucket,
+    pub events: TokenBucket,
+    pub http: TokenBucket,
+}
+
+impl HostRateLimiter {
+    pub fn new(limits: &RateLimits) -> Self {
+        HostRateLimiter {
+            log: TokenBucket::new(limits.log),
+            events: TokenBucket::new(limits.events),
+            http: TokenBucket::new(limits.http),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn take_all(bucket: &mut TokenBucket) -> usize {
+        std::iter::from_fn(|| bucket.try_take().then_some(())).count()
+    }
+
+    #[test]
+    fn bursts_are_capped_at_a_seconds_worth() {
+        let mut bucket = TokenBucket::new(10);
+        assert_eq!(take_all(&mut bucket), 10);
+        assert!(!bucket.try_take());
+    }
+
+    #[test]
+    fn tokens_refill_at_the_rate() {
+        let mut bucket = TokenBucket::new(10);
+        take_all(&mut bucket);
+
+        // Half a second buys half the rate back
+        bucket.refilled_at -= Duration::from_millis(500);
+        assert_eq!(take_all(&mut bucket), 5);
+
+        // However long it's been, no more than the cap comes back
+        bucket.refilled_at -= Duration::from_secs(5);
+        assert_eq!(take_all(&mut bucket), 10);
+    }
+
+    #[test]
+    fn a_zero_rate_allows_nothing() {
+        let mut bucket = TokenBucket::new(0);
+        bucket.refilled_at -= Duration::from_secs(5);
+        assert!(!bucket.try_take());
+    }
+}
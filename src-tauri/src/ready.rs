@@ -0,0 +1,73 @@
+use std::sync::atomic::Ordering;
+use std::sync::LazyLock;
+use tokio::sync::Notify;
+
+use crate::error::PluginError;
+use crate::{registry, PLUGINS_READY};
+
+// Woken whenever a plugin finishes loading or fails to, and when the startup load ends
+static CHANGED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Wakes everyone in `wait_for` to look at the registry again.
+pub fn notify() {
+    CHANGED.notify_waiters();
+}
+
+/// Waits until plugin `id` is loaded. A plugin is only registered once its `init`
+/// has returned, and only loads after its dependencies are registered, so by then
+/// everything it depends on has finished initializing too.
+///
+/// Fails straight away if `id` failed to load, or if the startup load is over
+/// and it never turned up.
+pub async fn wait_for(id: &str) -> Result<(), PluginError> {
+    loop {
+        // Registered before looking, so a load finishing in between still wakes us
+        let changed = CHANGED.notified();
+        tokio::pin!(changed);
+        changed.as_mut().enable();
+
+        {
+            let registry = registry();
+            if registry.get(id).is_some() {
+                return Ok(());
+            }
+            if registry.is_failed(id) {
+                return Err(PluginError::NotLoaded(id.to_string()));
+            }
+        }
+        if PLUGINS_READY.load(Ordering::SeqCst) {
+            return Err(PluginError::NotLoaded(id.to_string()));
+        }
+
+        changed.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{engine, TestApp, ANSWER};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn waiting_returns_once_the_plugin_loads() {
+        let app = TestApp::new();
+        let waiting = tokio::spawn(wait_for("ready-late"));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        app.load("ready-late", ANSWER).await.unwrap();
+        let waited = tokio::time::timeout(Duration::from_secs(5), waiting).await;
+        assert!(waited.expect("never woke up").unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn waiting_on_a_failed_plugin_fails() {
+        let app = TestApp::new();
+        let loaded = crate::load_embedded_plugin(&app.handle, engine(), "ready-failed", b"not wasm").await;
+        assert!(loaded.is_err());
+
+        let result = tokio::time::timeout(Duration::from_secs(5), wait_for("ready-failed")).await;
+        assert!(matches!(result, Ok(Err(PluginError::NotLoaded(_)))));
+    }
+}
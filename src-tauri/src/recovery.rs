@@ -0,0 +1,265 @@
+use std::future::Future;
+
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::{create_instance, registry, shared_engine, snapshot};
+
+// How many times a call that trapped is retried on a fresh instance, overridable
+// with PATO_PLUGIN_TRAP_RETRIES. Off by default, since a retry re-runs `init`.
+const DEFAULT_TRAP_RETRIES: u32 = 0;
+
+fn trap_retries() -> u32 {
+    std::env::var("PATO_PLUGIN_TRAP_RETRIES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_TRAP_RETRIES)
+}
+
+/// Runs `call` against plugin `id`. A trap can leave the store in any state, so
+/// when `call` traps the plugin is re-instantiated from its compiled component
+/// and `call` runs again, up to `PATO_PLUGIN_TRAP_RETRIES` times.
+///
+/// A call that trapped, or that the host interrupted, and wasn't retried marks the
+/// plugin `dirty`, and the next call through here re-instantiates it before running.
+pub async fn with_trap_retries<T, F, Fut>(id: &str, mut call: F) -> Result<T, PluginError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, PluginError>>,
+{
+    let retries = trap_retries();
+    let mut attempt = 0;
+    let mut app = None;
+    if is_dirty(id) {
+        println!("🧹 Plugin {} was interrupted last time, starting it afresh", id);
+        respawn(id).await?;
+        set_dirty(id, false);
+    }
+    loop {
+        match call().await {
+            // Bounded by `retries`, so a plugin that traps every time still fails in the end
+            Err(e) if e.is_trap() && attempt < retries => {
+                attempt += 1;
+                println!("🔁 Plugin {} trapped ({}), retrying on a fresh instance ({}/{})", id, e, attempt, retries);
+                app = Some(respawn(id).await?);
+            }
+            Ok(value) => {
+                set_warm(id);
+                if let Some(app) = app {
+                    println!("🩹 Plugin {} recovered after {} retries", id, attempt);
+                    let _ = app.emit("plugin-recovered", id);
+                }
+                return Ok(value);
+            }
+            Err(e) => {
+                // Traps and interruptions alike can stop the plugin halfway through updating its state
+                if e.is_trap() || e.is_interrupted() {
+                    set_dirty(id, true);
+                }
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn is_dirty(id: &str) -> bool {
+    registry().get(id).is_some_and(|loaded| loaded.dirty)
+}
+
+fn set_dirty(id: &str, dirty: bool) {
+    if let Some(loaded) = registry().get_mut(id) {
+        loaded.dirty = dirty;
+        // The next call sets up a new instance, so there's nothing warm about it
+        loaded.warm &= !dirty;
+    }
+}
+
+fn set_warm(id: &str) {
+    if let Some(loaded) = registry().get_mut(id) {
+        loaded.warm = true;
+    }
+}
+
+/// Swaps plugin `id`'s instance for a new one built from the same component, or
+/// for its snapshot when one is ready, which skips instantiating and `init`.
+/// Everything else about the plugin, like its metrics and output, carries over.
+async fn respawn(id: &str) -> Result<AppHandle, PluginError> {
+    let (instance, snapshot, component, path, manifest, data_dir, output, fuel_budget, cancel) = {
+        let registry = registry();
+        let loaded = registry.get(id).ok_or_else(|| PluginError::NotLoaded(id.to_string()))?;
+        (
+            loaded.instance.clone(),
+            loaded.snapshot.clone(),
+            loaded.component.clone(),
+            loaded.path.clone(),
+            loaded.manifest.clone(),
+            loaded.data_dir.clone(),
+            loaded.output.clone(),
+            loaded.fuel_budget,
+            loaded.cancel.clone(),
+        )
+    };
+
+    // Held until the new instance is in place, so no other call runs on the broken store
+    let mut guard = instance.lock().await;
+    let app = guard.store.data().app.clone();
+    *guard = match snapshot.take() {
+        Some(restored) => {
+            println!("📸 Restored plugin {} from its snapshot", id);
+            restored
+        }
+        None => {
+            let engine = shared_engine()?;
+            create_instance(
+                &app,
+                engine,
+                id,
+                &path,
+                manifest.as_ref(),
+                &component,
+                &data_dir,
+                &output,
+                fuel_budget,
+                cancel,
+            )
+            .await?
+        }
+    };
+    drop(guard);
+
+    // Ready for the next time
+    snapshot::refresh(id);
+    Ok(app)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{wait_for_snapshot, TestApp};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    // `init` emits an `init` event, so the host can count how often it runs
+    const EMITS_ON_INIT: &str = r#"
+    (component
+      (import "pato:plugin-ui/events@0.1.0" (instance $events
+        (export "emit-event" (func (param "name" string) (param "payload" string)))))
+      (core module $memory
+        (memory (export "memory") 1)
+        (data (i32.const 0) "init"))
+      (core instance $memory (instantiate $memory))
+      (core func $emit-event (canon lower (func $events "emit-event") (memory $memory "memory")))
+      (core instance $host (export "emit-event" (func $emit-event)))
+      (core module $m
+        (import "env" "memory" (memory 1))
+        (import "host" "emit-event" (func $emit-event (param i32 i32 i32 i32)))
+        (global $next (mut i32) (i32.const 1024))
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+          (local $ptr i32)
+          (local.set $ptr (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+          (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+          (local.get $ptr))
+        (func (export "init") (param i32 i32)
+          (call $emit-event (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 0)))
+        (func (export "get-number") (result i32) i32.const 42))
+      (core instance $i (instantiate $m (with "env" (instance $memory)) (with "host" (instance $host))))
+      (func $init (param "params" (list (tuple string string)))
+        (canon lift (core func $i "init") (memory $memory "memory") (realloc (func $i "realloc"))))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $lifecycle (export "init" (func $init)))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "pato:plugin-ui/lifecycle@0.1.0" (instance $lifecycle))
+      (export "test" (instance $test)))
+    "#;
+
+    #[tokio::test]
+    async fn respawn_restores_the_snapshot_instead_of_rerunning_init() {
+        let app = TestApp::new();
+        let id = app.load("recovery-snapshot", EMITS_ON_INIT).await.unwrap();
+        let inits = || app.emitted(&format!("plugin:{}:init", id)).len();
+
+        // Once for the load, once for the snapshot
+        wait_for_snapshot(&id).await;
+        assert_eq!(inits(), 2);
+
+        // Restoring spends the snapshot; taking the next one is the only new `init`
+        respawn(&id).await.unwrap();
+        wait_for_snapshot(&id).await;
+        assert_eq!(inits(), 3);
+
+        let result = crate::dynamic::invoke(&id, "test#get-number", Value::Null, None).await;
+        assert_eq!(result.unwrap(), json!(42));
+    }
+
+    #[tokio::test]
+    async fn plugins_without_init_respawn_from_scratch() {
+        let app = TestApp::new();
+        let id = app.load("recovery-no-init", crate::testing::ANSWER).await.unwrap();
+        respawn(&id).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!registry().get(&id).unwrap().snapshot.is_ready());
+
+        let result = crate::dynamic::invoke(&id, "test#get-number", Value::Null, None).await;
+        assert_eq!(result.unwrap(), json!(42));
+    }
+
+    // `spin` never returns; `test#get-number` answers 42, and `test#boom` traps
+    const SPINS_OR_ANSWERS: &str = r#"
+    (component
+      (core module $m
+        (func (export "spin") (loop $spin (br $spin)))
+        (func (export "get-number") (result i32) i32.const 42)
+        (func (export "boom") (result i32) unreachable))
+      (core instance $i (instantiate $m))
+      (func $spin (canon lift (core func $i "spin")))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (func $boom (result u32) (canon lift (core func $i "boom")))
+      (instance $test (export "get-number" (func $get-number)) (export "boom" (func $boom)))
+      (export "spin" (func $spin))
+      (export "test" (instance $test)))
+    "#;
+
+    fn is_dirty(id: &str) -> bool {
+        registry().get(id).unwrap().dirty
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn cancelled_calls_leave_the_next_one_a_fresh_instance() {
+        let app = TestApp::new();
+        let id = app.load("recovery-cancelled", SPINS_OR_ANSWERS).await.unwrap();
+        registry().get_mut(&id).unwrap().fuel_budget = u64::MAX;
+
+        let call = {
+            let id = id.clone();
+            tokio::spawn(async move { crate::dynamic::invoke(&id, "spin", Value::Null, None).await })
+        };
+        let (instance, cancel) = {
+            let registry = registry();
+            let loaded = registry.get(&id).unwrap();
+            (loaded.instance.clone(), loaded.cancel.clone())
+        };
+        while instance.try_lock().is_ok() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        // Arming the call clears any earlier cancel, so give it time to get going
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        cancel.store(true, std::sync::atomic::Ordering::SeqCst);
+
+        let result = call.await.unwrap();
+        assert!(matches!(result, Err(PluginError::Cancelled(_))), "{:?}", result);
+        assert!(is_dirty(&id));
+
+        let result = crate::dynamic::invoke(&id, "test#get-number", Value::Null, None).await;
+        assert_eq!(result.unwrap(), json!(42));
+        assert!(!is_dirty(&id));
+    }
+
+    #[tokio::test]
+    async fn traps_mark_the_plugin_dirty_too() {
+        let app = TestApp::new();
+        let id = app.load("recovery-trapped", SPINS_OR_ANSWERS).await.unwrap();
+
+        let result = crate::dynamic::invoke(&id, "test#boom", Value::Null, None).await;
+        assert!(matches!(result, Err(PluginError::Trap(_))), "{:?}", result);
+        assert!(is_dirty(&id));
+    }
+}
@@ -0,0 +1,431 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use wasmtime::component::{Component, Instance};
+use wasmtime::Store;
+
+use crate::config::ConfigField;
+use crate::health::PluginHealth;
+use crate::host::PluginHost;
+use crate::manifest::PluginManifest;
+use crate::metrics::FunctionMetrics;
+use crate::output::CapturedOutput;
+use crate::snapshot::Snapshot;
+use crate::ui::UiContribution;
+
+/// A plugin's store and the instance living in it.
+///
+/// Wasmtime stores are single-threaded: only one call can run in a store at a
+/// time. Each plugin's store sits behind its own lock, so calls to different
+/// plugins run concurrently while calls to the same plugin queue up. Tokio's
+/// mutex is fair, so queued calls run in the order they arrived, and each gets
+/// its result back from its own await without interleaving with another.
+pub struct PluginInstance {
+    pub store: Store<PluginHost>,
+    pub instance: Instance,
+}
+
+/// A compiled and instantiated plugin, ready to be called.
+pub struct LoadedPlugin {
+    /// Kept for introspection; instantiation has already happened
+    pub component: Component,
+    /// Behind an async lock so a call can hold it across awaits without
+    /// holding the registry lock too. Cloning the `Arc` out of the registry is
+    /// the only thing done under the registry lock.
+    pub instance: Arc<tokio::sync::Mutex<PluginInstance>>,
+    pub path: PathBuf,
+    pub manifest: Option<PluginManifest>,
+    pub loaded_at: SystemTime,
+    /// How long compiling (or fetching from the cache) took on the last (re)load
+    pub compile_time: Duration,
+    /// Size of the plugin's component on disk
+    pub size_bytes: u64,
+    /// Fuel each call starts with
+    pub fuel_budget: u64,
+    /// Host side of the plugin's `/data` directory
+    pub data_dir: PathBuf,
+    /// Updated by the health poller
+    pub health: PluginHealth,
+    /// Per-function call stats, starting from zero on every (re)load
+    pub metrics: BTreeMap<String, FunctionMetrics>,
+    /// Host calls over the rate limit since the last (re)load, by interface
+    pub throttled: BTreeMap<String, u64>,
+    /// What the plugin has written to stdout and stderr
+    pub output: CapturedOutput,
+    /// A disabled plugin stays instantiated but refuses calls
+    pub enabled: bool,
+    /// Shared with the store, so a call can be cancelled without taking its lock
+    pub cancel: Arc<AtomicBool>,
+    /// Set when a call was interrupted partway, so the store may hold half-finished
+    /// state; the next call re-instantiates the plugin first
+    pub dirty: bool,
+    /// Set once the current instance has been through `warmup` or a call, so the
+    /// next call has nothing to set up first; cleared when the plugin goes dirty
+    pub warm: bool,
+    /// What the plugin asked to add to the UI when it loaded
+    pub ui_contributions: Vec<UiContribution>,
+    /// The config keys the plugin declared when it loaded, if it declared any
+    pub config_schema: Option<Vec<ConfigField>>,
+    /// Compiled into the host rather than read from the plugins directory; these
+    /// can't be reloaded, and `path` is just their name
+    pub embedded: bool,
+    /// A spare instance, initialized and ready for a respawn to swap in
+    pub snapshot: Snapshot,
+}
+
+impl LoadedPlugin {
+    pub fn priority(&self) -> i32 {
+        self.manifest.as_ref().map(|m| m.priority).unwrap_or(0)
+    }
+}
+
+/// A plugin whose last load attempt failed, kept so users can see why.
+pub struct FailedPlugin {
+    pub path: PathBuf,
+    pub error: String,
+    pub failed_at: SystemTime,
+}
+
+/// Where a plugin's id came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdSource {
+    /// The `id` in its manifest
+    Manifest,
+    /// Its file stem and a hash of its file, for plugins without a manifest
+    Derived,
+    /// The name it was embedded under
+    Embedded,
+}
+
+impl IdSource {
+    pub fn of(plugin: &LoadedPlugin) -> Self {
+        if plugin.embedded {
+            IdSource::Embedded
+        } else if plugin.manifest.is_some() {
+            IdSource::Manifest
+        } else {
+            IdSource::Derived
+        }
+    }
+}
+
+/// Metadata about a plugin, as shown to the frontend.
+#[derive(Serialize)]
+pub struct PluginInfo {
+    pub id: String,
+    pub path: PathBuf,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub author: Option<String>,
+    /// Milliseconds since the Unix epoch
+    pub loaded_at: u64,
+    /// Set when the plugin failed to load
+    pub error: Option<String>,
+    /// Last health check, for loaded plugins
+    pub health: Option<PluginHealth>,
+    /// Whether calls are allowed; failed plugins are never enabled
+    pub enabled: bool,
+    /// From the manifest; 0 without one
+    pub priority: i32,
+    /// Whether the last call was interrupted and the next one will start from a fresh instance
+    pub dirty: bool,
+    /// Whether the next call can run straight away, without instantiating anything first
+    pub warm: bool,
+    /// How long the plugin took to compile when it last loaded; failed plugins have none
+    pub compile_ms: Option<u64>,
+    /// Size of the plugin's component on disk, for loaded plugins
+    pub size_bytes: Option<u64>,
+    /// How the id was arrived at; unknown for plugins that failed to load
+    pub id_source: Option<IdSource>,
+}
+
+/// How the startup load went, sent with `plugins-ready`.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct LoadSummary {
+    pub loaded: usize,
+    pub failed: usize,
+    /// Files in the plugins directory that aren't plugins or their sidecars
+    pub skipped: usize,
+}
+
+/// What `reload_all_plugins` did, sent with `plugins-reloaded`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadSummary {
+    /// Already loaded, and reloaded from disk
+    pub reloaded: Vec<String>,
+    /// Found in the plugins directory without being loaded before
+    pub loaded: Vec<String>,
+    /// Their files were gone
+    pub unloaded: Vec<String>,
+    /// Plugins that couldn't be reloaded or loaded, with why; a plugin that fails
+    /// to reload keeps running its old instance
+    pub failed: Vec<ReloadFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadFailure {
+    pub id: String,
+    pub error: String,
+}
+
+/// All loaded plugins, keyed by plugin id.
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: HashMap<String, LoadedPlugin>,
+    failed: HashMap<String, FailedPlugin>,
+    /// The dependency order the startup load resolved, for debugging
+    load_order: Vec<String>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a plugin under `id`, returning the plugin it replaced, if any.
+    pub fn register(&mut self, id: String, plugin: LoadedPlugin) -> Option<LoadedPlugin> {
+        self.failed.remove(&id);
+        self.plugins.insert(id, plugin)
+    }
+
+    /// Records that loading `id` failed. A plugin that is already loaded stays loaded.
+    pub fn record_failure(&mut self, id: String, path: PathBuf, error: String) {
+        let failed = FailedPlugin {
+            path,
+            error,
+            failed_at: SystemTime::now(),
+        };
+        self.failed.insert(id, failed);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&LoadedPlugin> {
+        self.plugins.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut LoadedPlugin> {
+        self.plugins.get_mut(id)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Option<LoadedPlugin> {
+        self.failed.remove(id);
+        self.plugins.remove(id)
+    }
+
+    /// Finds the id of the loaded or failed plugin that came from `path`.
+    pub fn id_for_path(&self, path: &Path) -> Option<String> {
+        let loaded = self.plugins.iter().map(|(id, plugin)| (id, &plugin.path));
+        let failed = self.failed.iter().map(|(id, plugin)| (id, &plugin.path));
+        loaded
+            .chain(failed)
+            .find(|(_, plugin_path)| plugin_path.as_path() == path)
+            .map(|(id, _)| id.clone())
+    }
+
+    /// Where the loaded or failed plugin `id` came from.
+    pub fn path_of(&self, id: &str) -> Option<&Path> {
+        self.plugins
+            .get(id)
+            .map(|plugin| plugin.path.as_path())
+            .or_else(|| self.failed.get(id).map(|plugin| plugin.path.as_path()))
+    }
+
+    /// Whether the last attempt to load `id` failed.
+    pub fn is_failed(&self, id: &str) -> bool {
+        self.failed.contains_key(id)
+    }
+
+    pub fn set_load_order(&mut self, load_order: Vec<String>) {
+        self.load_order = load_order;
+    }
+
+    pub fn load_order(&self) -> &[String] {
+        &self.load_order
+    }
+
+    /// Returns the ids of all registered plugins, sorted so callers get a stable order.
+    pub fn list_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.plugins.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Returns the ids of all registered plugins in the order aggregate calls run
+    /// them: by priority, lowest first, then by id.
+    pub fn ids_by_priority(&self) -> Vec<String> {
+        let mut plugins: Vec<(i32, &String)> = self
+            .plugins
+            .iter()
+            .map(|(id, plugin)| (plugin.priority(), id))
+            .collect();
+        plugins.sort();
+        plugins.into_iter().map(|(_, id)| id.clone()).collect()
+    }
+
+    /// The ids of enabled plugins whose manifest subscribes to `topic`, in priority order.
+    pub fn subscribers(&self, topic: &str) -> Vec<String> {
+        self.ids_by_priority()
+            .into_iter()
+            .filter(|id| {
+                self.plugins.get(id).is_some_and(|plugin| {
+                    plugin.enabled
+                        && plugin
+                            .manifest
+                            .as_ref()
+                            .is_some_and(|m| m.subscribes.iter().any(|t| t == topic))
+                })
+            })
+            .collect()
+    }
+
+    /// Total compile time and component size across every loaded plugin.
+    pub fn load_costs(&self) -> (Duration, u64) {
+        self.plugins.values().fold((Duration::ZERO, 0), |(time, size), plugin| {
+            (time + plugin.compile_time, size + plugin.size_bytes)
+        })
+    }
+
+    /// Every enabled plugin's UI contributions, in priority order.
+    pub fn ui_contributions(&self) -> Vec<UiContribution> {
+        self.ids_by_priority()
+            .iter()
+            .filter_map(|id| self.plugins.get(id))
+            .filter(|plugin| plugin.enabled)
+            .flat_map(|plugin| plugin.ui_contributions.iter().cloned())
+            .collect()
+    }
+
+    /// Describes every loaded plugin plus any that failed to load, sorted by id.
+    pub fn list_info(&self) -> Vec<PluginInfo> {
+        let loaded = self.plugins.iter().map(|(id, plugin)| PluginInfo {
+            id: id.clone(),
+            path: plugin.path.clone(),
+            name: plugin.manifest.as_ref().map(|m| m.name.clone()),
+            version: plugin.manifest.as_ref().map(|m| m.version.clone()),
+            author: plugin.manifest.as_ref().and_then(|m| m.author.clone()),
+            loaded_at: millis_since_epoch(plugin.loaded_at),
+            error: None,
+            health: Some(plugin.health.clone()),
+            enabled: plugin.enabled,
+            priority: plugin.priority(),
+            dirty: plugin.dirty,
+            warm: plugin.warm,
+            compile_ms: Some(plugin.compile_time.as_millis() as u64),
+            size_bytes: Some(plugin.size_bytes),
+            id_source: Some(IdSource::of(plugin)),
+        });
+        let failed = self.failed.iter().map(|(id, plugin)| PluginInfo {
+            id: id.clone(),
+            path: plugin.path.clone(),
+            name: None,
+            version: None,
+            author: None,
+            loaded_at: millis_since_epoch(plugin.failed_at),
+            error: Some(plugin.error.clone()),
+            health: None,
+            enabled: false,
+            priority: 0,
+            dirty: false,
+            warm: false,
+            compile_ms: None,
+            size_bytes: None,
+            id_source: None,
+        });
+
+        let mut infos: Vec<PluginInfo> = loaded.chain(failed).collect();
+        infos.sort_by(|a, b| a.id.cmp(&b.id));
+        infos
+    }
+}
+
+pub fn millis_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::TestApp;
+    use crate::{dynamic, registry};
+    use serde_json::Value;
+    use std::time::Duration;
+
+    // Each call to `next` returns one more than the last, so the results show
+    // which order calls ran in and whether any overlapped
+    const COUNTER: &str = r#"
+    (component
+      (core module $m
+        (global $count (mut i32) (i32.const 0))
+        (func (export "next") (result i32)
+          (global.set $count (i32.add (global.get $count) (i32.const 1)))
+          (global.get $count)))
+      (core instance $i (instantiate $m))
+      (func $next (result u32) (canon lift (core func $i "next")))
+      (export "next" (func $next)))
+    "#;
+
+    async fn next(id: String) -> u64 {
+        let result = dynamic::invoke(&id, "next", Value::Null, None).await.unwrap();
+        result.as_u64().unwrap()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_calls_to_several_plugins_all_complete() {
+        const PLUGINS: usize = 4;
+        const CALLS: usize = 50;
+
+        let app = TestApp::new();
+        let mut ids = Vec::new();
+        for n in 0..PLUGINS {
+            ids.push(app.load(&format!("registry-stress-{}", n), COUNTER).await.unwrap());
+        }
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for id in &ids {
+            for _ in 0..CALLS {
+                let id = id.clone();
+                tasks.spawn(async move { (id.clone(), next(id).await) });
+            }
+        }
+        let joined = tokio::time::timeout(Duration::from_secs(30), async {
+            let mut results = Vec::new();
+            while let Some(result) = tasks.join_next().await {
+                results.push(result.unwrap());
+            }
+            results
+        })
+        .await
+        .expect("calls deadlocked");
+
+        // Every call saw its own count: none were lost or ran on top of another
+        for id in &ids {
+            let mut counts: Vec<u64> = joined.iter().filter(|(i, _)| i == id).map(|(_, n)| *n).collect();
+            counts.sort();
+            assert_eq!(counts, (1..=CALLS as u64).collect::<Vec<_>>(), "{}", id);
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_calls_to_one_plugin_run_in_arrival_order() {
+        const CALLS: usize = 20;
+
+        let app = TestApp::new();
+        let id = app.load("registry-queue", COUNTER).await.unwrap();
+
+        // Hold the plugin's store so every call has to queue behind it
+        let instance = registry().get(&id).unwrap().instance.clone();
+        let guard = instance.lock().await;
+        let calls: Vec<_> = (0..CALLS).map(|_| tokio::spawn(next(id.clone()))).collect();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(guard);
+
+        // The n-th call to arrive gets the n-th result, and gets it back itself
+        for (n, call) in calls.into_iter().enumerate() {
+            assert_eq!(call.await.unwrap(), n as u64 + 1);
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::registry::{LoadSummary, PluginInfo};
+use crate::services::{self, HostService};
+use crate::{create_engine, describe_engine_error, dynamic, epoch, registry, ENGINE, PLUGINS_DIR};
+
+/// Limits for every plugin the runtime loads. Anything left unset falls back to
+/// the PATO_PLUGIN_* environment variables, then the built-in defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuntimeLimits {
+    pub memory_mb: Option<usize>,
+    pub fuel: Option<u64>,
+    pub call_timeout: Option<Duration>,
+}
+
+/// What loading does when the plugins directory doesn't exist. Set with
+/// `PluginRuntimeBuilder::missing_dir`, or PATO_MISSING_PLUGINS_DIR (`create`,
+/// `error`, or `ignore`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingDirMode {
+    /// Create it, then load from it like any empty directory
+    #[default]
+    Create,
+    /// Fail the load, for packaged apps whose plugins directory should ship pre-populated
+    Error,
+    /// Log it and carry on without plugins, leaving the filesystem alone
+    Ignore,
+}
+
+impl MissingDirMode {
+    fn from_env() -> Option<Self> {
+        let value = std::env::var("PATO_MISSING_PLUGINS_DIR").ok()?;
+        match value.to_ascii_lowercase().as_str() {
+            "create" => Some(MissingDirMode::Create),
+            "error" => Some(MissingDirMode::Error),
+            "ignore" => Some(MissingDirMode::Ignore),
+            _ => {
+                println!("⚠️ Ignoring PATO_MISSING_PLUGINS_DIR={}: expected create, error, or ignore", value);
+                None
+            }
+        }
+    }
+}
+
+// Set by the first runtime built; plugins share one engine, so they share limits too
+static LIMITS: OnceLock<RuntimeLimits> = OnceLock::new();
+
+/// The limits the runtime was built with, if one has been.
+pub fn limits() -> RuntimeLimits {
+    LIMITS.get().copied().unwrap_or_default()
+}
+
+/// Loads, calls, lists, and unloads plugins, for the Tauri commands and for
+/// anything else that wants to host plugins, like the CLI.
+///
+/// Host interfaces emit events and read the app's config and data directories
+/// through the embedder's `AppHandle`, which can be the Tauri app's or any other
+/// `Embedder`; nothing here needs a window or Tauri's event loop to be running.
+pub struct PluginRuntime {
+    app: AppHandle,
+    plugins_dir: PathBuf,
+    missing_dir: MissingDirMode,
+    embedded: Vec<(String, &'static [u8])>,
+}
+
+#[derive(Default)]
+pub struct PluginRuntimeBuilder {
+    plugins_dir: Option<PathBuf>,
+    limits: Option<RuntimeLimits>,
+    missing_dir: Option<MissingDirMode>,
+    services: Vec<Arc<dyn HostService>>,
+    embedded: Vec<(String, &'static [u8])>,
+}
+
+impl PluginRuntime {
+    pub fn builder() -> PluginRuntimeBuilder {
+        PluginRuntimeBuilder::default()
+    }
+
+    pub fn plugins_dir(&self) -> &Path {
+        &self.plugins_dir
+    }
+
+    /// Loads the embedded plugins, then every plugin in the plugins directory,
+    /// dependencies first.
+    pub async fn load_all(&self) -> Result<LoadSummary, PluginError> {
+        let engine = crate::shared_engine()?;
+        let mut embedded = LoadSummary::default();
+        for (name, bytes) in &self.embedded {
+            match crate::load_embedded_plugin(&self.app, engine, name, bytes).await {
+                Ok(_) => embedded.loaded += 1,
+                Err(e) => {
+                    println!("❌ Failed to load embedded plugin {}: {}", name, e);
+                    embedded.failed += 1;
+                }
+            }
+        }
+
+        // Artifacts from another wasmtime or engine configuration can never be loaded again
+        let pruned = crate::cache::prune(engine, &self.plugins_dir);
+        if pruned > 0 {
+            println!("🧹 Pruned {} bytes of stale compiled plugins", pruned);
+        }
+
+        let mut summary = crate::load_wasm_plugins(
+            &self.app,
+            engine,
+            &self.plugins_dir,
+            self.missing_dir,
+            crate::load_concurrency(),
+        )
+        .await?;
+        summary.loaded += embedded.loaded;
+        summary.failed += embedded.failed;
+        Ok(summary)
+    }
+
+    /// Compiles and registers a plugin from `bytes`, e.g. from `include_bytes!`,
+    /// under `name`. It can be called and unloaded like any other, but not reloaded.
+    pub async fn load_embedded(&self, name: &str, bytes: &[u8]) -> Result<String, PluginError> {
+        let engine = crate::shared_engine()?;
+        crate::load_embedded_plugin(&self.app, engine, name, bytes).await
+    }
+
+    /// Calls `function` on plugin `id` with JSON arguments, like `invoke_export`.
+    pub async fn call(
+        &self,
+        id: &str,
+        function: &str,
+        args: Value,
+        timeout: Option<Duration>,
+    ) -> Result<Value, PluginError> {
+        dynamic::invoke(id, function, args, timeout).await
+    }
+
+    /// Every loaded plugin, plus those that failed to load.
+    pub fn list(&self) -> Vec<PluginInfo> {
+        registry().list_info()
+    }
+
+    /// Unloads plugin `id`, returning whether it was loaded.
+    pub async fn unload(&self, id: &str) -> bool {
+        crate::unload_plugin(id).await
+    }
+
+    /// Runs every plugin's `shutdown` hook, with a deadline each, and flushes their
+    /// databases. For when the host is exiting; the plugins are gone afterwards.
+    pub async fn shutdown(&self) {
+        crate::shutdown_all_plugins().await
+    }
+}
+
+impl PluginRuntimeBuilder {
+    /// Where to load plugins from; by default it's worked out like the app does.
+    pub fn plugins_dir(mut self, plugins_dir: impl Into<PathBuf>) -> Self {
+        self.plugins_dir = Some(plugins_dir.into());
+        self
+    }
+
+    pub fn limits(mut self, limits: RuntimeLimits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Offers `service` to plugins alongside the built-in host interfaces.
+    pub fn service(mut self, service: impl HostService + 'static) -> Self {
+        self.services.push(Arc::new(service));
+        self
+    }
+
+    /// Bundles a plugin into the runtime, loaded by `load_all` ahead of the plugins
+    /// directory, e.g. `.embedded_plugin("hello", include_bytes!("hello.wasm"))`.
+    pub fn embedded_plugin(mut self, name: impl Into<String>, bytes: &'static [u8]) -> Self {
+        self.embedded.push((name.into(), bytes));
+        self
+    }
+
+    /// What to do if the plugins directory is missing when loading; by default
+    /// PATO_MISSING_PLUGINS_DIR, or `Create` without it.
+    pub fn missing_dir(mut self, missing_dir: MissingDirMode) -> Self {
+        self.missing_dir = Some(missing_dir);
+        self
+    }
+
+    /// Sets up the shared engine, if nothing has yet, and the plugins directory.
+    /// Only one runtime runs per process, so building a second reuses the first's
+    /// engine, limits, and plugins directory, and fails with `RuntimeConflict` if
+    /// it asks for different limits or a different plugins directory.
+    pub fn build(self, app: &AppHandle) -> Result<PluginRuntime, PluginError> {
+        if let (Some(current), Some(asked)) = (LIMITS.get(), self.limits) {
+            if *current != asked {
+                return Err(PluginError::RuntimeConflict(format!("limits {:?}, not {:?}", current, asked)));
+            }
+        }
+        if let (Some(current), Some(asked)) = (PLUGINS_DIR.get(), &self.plugins_dir) {
+            if current != asked {
+                return Err(PluginError::RuntimeConflict(format!(
+                    "plugins directory {:?}, not {:?}",
+                    current, asked
+                )));
+            }
+        }
+        let plugins_dir = match PLUGINS_DIR.get().cloned().or(self.plugins_dir) {
+            Some(plugins_dir) => plugins_dir,
+            None => crate::find_plugins_dir(app).ok_or_else(|| PluginError::Io("no plugins directory".to_string()))?,
+        };
+
+        LIMITS.get_or_init(|| self.limits.unwrap_or_default());
+        for service in self.services {
+            services::register(service);
+        }
+
+        if ENGINE.get().is_none() {
+            let engine = create_engine().map_err(|e| PluginError::InstantiationFailed(describe_engine_error(&*e)))?;
+            if ENGINE.set(engine.clone()).is_ok() {
+                epoch::start(engine);
+            }
+        }
+
+        let plugins_dir = PLUGINS_DIR.get_or_init(|| plugins_dir).clone();
+
+        Ok(PluginRuntime {
+            app: app.clone(),
+            plugins_dir,
+            missing_dir: self.missing_dir.or_else(MissingDirMode::from_env).unwrap_or_default(),
+            embedded: self.embedded,
+        })
+    }
+}
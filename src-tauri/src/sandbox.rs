@@ -0,0 +1,357 @@
+use cap_rand::rngs::StdRng;
+use cap_rand::SeedableRng;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use wasmtime_wasi::{DirPerms, FilePerms, HostMonotonicClock, HostWallClock, WasiCtx, WasiCtxBuilder};
+
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::manifest::{Capability, Mount, MountMode, PluginManifest};
+use crate::output::CapturedOutput;
+
+/// What a plugin without a manifest gets: enough to print and keep its own data.
+pub const DEFAULT_CAPABILITIES: &[Capability] = &[Capability::Stdio, Capability::Storage];
+
+/// The capabilities a plugin is granted: what its manifest works out to, or
+/// `DEFAULT_CAPABILITIES` without one.
+pub fn capabilities_for(manifest: Option<&PluginManifest>) -> Vec<Capability> {
+    match manifest {
+        Some(manifest) => manifest.granted_capabilities(),
+        None => DEFAULT_CAPABILITIES.to_vec(),
+    }
+}
+
+/// Builds the WASI context for a plugin, granting only `capabilities`.
+///
+/// - `stdio`: stdout/stderr are captured into `output`, or with `inherit-stdio` go to the
+///   host's terminal; otherwise output is discarded
+/// - `filesystem`: the directory containing the plugin, read-only, at `/plugin`
+/// - `clock`: the real wall and monotonic clocks; otherwise both are frozen at zero
+/// - `storage`: `data_dir`, read-write, at `/data`
+///
+/// Every plugin also gets its `mounts`, which must lie inside `mount_root`, and the
+/// environment from `plugin_env`. In `deterministic` mode the clocks and random
+/// sources are replaced whatever the capabilities say: there's no wall-clock time,
+/// and two runs see the same monotonic readings and random bytes.
+pub fn build_wasi_ctx(
+    manifest: Option<&PluginManifest>,
+    capabilities: &[Capability],
+    plugin_path: &Path,
+    data_dir: &Path,
+    output: &CapturedOutput,
+    mount_root: Option<&Path>,
+) -> Result<WasiCtx, PluginError> {
+    let inherit_stdio = manifest.is_some_and(|m| m.inherit_stdio);
+    let deterministic_seed = manifest.filter(|m| m.deterministic).map(|m| m.seed);
+    let mut builder = WasiCtxBuilder::new();
+
+    if capabilities.contains(&Capability::Storage) {
+        builder
+            .preopened_dir(data_dir, "/data", DirPerms::all(), FilePerms::all())
+            .map_err(|e| PluginError::InstantiationFailed(format!("failed to preopen {:?}: {}", data_dir, e)))?;
+    }
+
+    if capabilities.contains(&Capability::Stdio) {
+        if inherit_stdio {
+            builder.inherit_stdio();
+        } else {
+            builder.stdout(output.stdout.clone());
+            builder.stderr(output.stderr.clone());
+        }
+    }
+
+    if capabilities.contains(&Capability::Filesystem) {
+        // Embedded plugins have a bare name for a path, and no directory to share
+        if let Some(plugin_dir) = plugin_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            builder
+                .preopened_dir(plugin_dir, "/plugin", DirPerms::READ, FilePerms::READ)
+                .map_err(|e| PluginError::InstantiationFailed(format!("failed to preopen {:?}: {}", plugin_dir, e)))?;
+        }
+    }
+
+    for mount in manifest.map(|m| m.mounts.as_slice()).unwrap_or_default() {
+        let host = resolve_mount(mount, mount_root)?;
+        let (dir_perms, file_perms) = match mount.mode {
+            MountMode::ReadOnly => (DirPerms::READ, FilePerms::READ),
+            MountMode::ReadWrite => (DirPerms::all(), FilePerms::all()),
+        };
+        builder
+            .preopened_dir(&host, &mount.guest, dir_perms, file_perms)
+            .map_err(|e| PluginError::InstantiationFailed(format!("failed to preopen {:?}: {}", host, e)))?;
+    }
+
+    for (key, value) in manifest.map(plugin_env).unwrap_or_default() {
+        builder.env(&key, &value);
+    }
+
+    if let Some(seed) = deterministic_seed {
+        builder.wall_clock(FrozenClock);
+        builder.monotonic_clock(SteppingClock::default());
+        builder.secure_random(StdRng::seed_from_u64(seed));
+        builder.insecure_random(StdRng::seed_from_u64(seed.wrapping_add(1)));
+        builder.insecure_random_seed(u128::from(seed));
+    } else if !capabilities.contains(&Capability::Clock) {
+        builder.wall_clock(FrozenClock);
+        builder.monotonic_clock(FrozenClock);
+    }
+
+    Ok(builder.build())
+}
+
+/// The environment variables a plugin sees: the host's values for the names in
+/// `inherit-env` that are set, then the manifest's `env`, which wins on a clash.
+/// Nothing else from the host environment is passed through.
+fn plugin_env(manifest: &PluginManifest) -> Vec<(String, String)> {
+    let mut env: BTreeMap<String, String> = manifest
+        .inherit_env
+        .iter()
+        .filter_map(|name| std::env::var(name).ok().map(|value| (name.clone(), value)))
+        .collect();
+    env.extend(manifest.env.iter().map(|(key, value)| (key.clone(), value.clone())));
+    env.into_iter().collect()
+}
+
+/// Where mounts may point: PATO_MOUNT_ROOT, or `<app data>/shared`.
+pub fn mount_root(app: &AppHandle) -> Option<PathBuf> {
+    match std::env::var_os("PATO_MOUNT_ROOT").filter(|value| !value.is_empty()) {
+        Some(root) => Some(PathBuf::from(root)),
+        None => app.app_data_dir().map(|dir| dir.join("shared")),
+    }
+}
+
+/// Finds the host directory for `mount`, refusing anything outside `mount_root`.
+fn resolve_mount(mount: &Mount, mount_root: Option<&Path>) -> Result<PathBuf, PluginError> {
+    let refuse = |reason: String| PluginError::InstantiationFailed(format!("can't mount {:?}: {}", mount.host, reason));
+
+    let root = mount_root.ok_or_else(|| refuse("there's no mount root".to_string()))?;
+    let root = root
+        .canonicalize()
+        .map_err(|e| refuse(format!("mount root {:?}: {}", root, e)))?;
+    // Canonicalizing resolves `..` and symlinks, so the prefix check can't be sidestepped
+    let host = root
+        .join(&mount.host)
+        .canonicalize()
+        .map_err(|e| refuse(e.to_string()))?;
+    if !host.starts_with(&root) || !host.is_dir() {
+        return Err(refuse(format!("not a directory inside {:?}", root)));
+    }
+    if matches!(mount.guest.as_str(), "/data" | "/plugin") || !mount.guest.starts_with('/') {
+        return Err(refuse(format!("{:?} isn't a guest path plugins can mount at", mount.guest)));
+    }
+    Ok(host)
+}
+
+/// Creates (if needed) the plugin's scratch directory, `<app data>/plugin-data/<id>`.
+pub fn create_data_dir(app: &AppHandle, id: &str) -> Result<PathBuf, PluginError> {
+    // Manifest ids end up in a path, so they mustn't be able to climb out of it
+    let is_safe = !id.is_empty()
+        && id != "."
+        && id != ".."
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !is_safe {
+        return Err(PluginError::InstantiationFailed(format!(
+            "plugin id {:?} can't be used as a directory name",
+            id
+        )));
+    }
+
+    let app_data = app
+        .app_data_dir()
+        .ok_or_else(|| PluginError::Io("the app has no data directory".to_string()))?;
+    let data_dir = app_data.join("plugin-data").join(id);
+    std::fs::create_dir_all(&data_dir).map_err(|e| PluginError::Io(e.to_string()))?;
+    Ok(data_dir)
+}
+
+/// A clock that never moves, handed to plugins that weren't granted clock access.
+struct FrozenClock;
+
+impl HostWallClock for FrozenClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn now(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+impl HostMonotonicClock for FrozenClock {
+    fn resolution(&self) -> u64 {
+        1_000_000_000
+    }
+
+    fn now(&self) -> u64 {
+        0
+    }
+}
+
+/// A monotonic clock for deterministic plugins: starts at zero and moves forward
+/// by `STEP` nanoseconds each time it's read, so elapsed times are never zero but
+/// are the same on every run.
+#[derive(Default)]
+struct SteppingClock {
+    now: AtomicU64,
+}
+
+impl SteppingClock {
+    const STEP: u64 = 1_000_000;
+}
+
+impl HostMonotonicClock for SteppingClock {
+    fn resolution(&self) -> u64 {
+        Self::STEP
+    }
+
+    fn now(&self) -> u64 {
+        self.now.fetch_add(Self::STEP, Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    fn manifest(extra: &str) -> PluginManifest {
+        let text = format!("id = \"sandboxed\"\nname = \"Sandboxed\"\nversion = \"1.0.0\"\n{}", extra);
+        PluginManifest::parse(&text, Path::new("sandboxed.toml")).unwrap()
+    }
+
+    #[test]
+    fn plugins_without_a_manifest_get_the_defaults() {
+        assert_eq!(capabilities_for(None), DEFAULT_CAPABILITIES);
+    }
+
+    #[test]
+    fn manifests_get_only_what_they_ask_for() {
+        let manifest = manifest("capabilities = [\"clock\"]");
+        assert_eq!(capabilities_for(Some(&manifest)), [Capability::Clock]);
+        assert!(capabilities_for(Some(&self::manifest(""))).is_empty());
+    }
+
+    #[test]
+    fn storage_is_only_preopened_when_granted() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = CapturedOutput::default();
+        let build = |capabilities: &[Capability]| {
+            build_wasi_ctx(None, capabilities, &dir.path().join("p.wasm"), &dir.path().join("missing"), &output, None)
+        };
+        // Preopening a directory that isn't there fails, so only a granted `storage` trips on it
+        assert!(build(&[Capability::Stdio]).is_ok());
+        assert!(build(&[Capability::Storage]).is_err());
+    }
+
+    #[test]
+    fn deterministic_clocks_read_the_same_every_run() {
+        let readings = || {
+            let clock = SteppingClock::default();
+            (0..3).map(|_| clock.now()).collect::<Vec<_>>()
+        };
+        let step = SteppingClock::STEP;
+        assert_eq!(readings(), [0, step, 2 * step]);
+        assert_eq!(readings(), readings());
+        assert_eq!(HostWallClock::now(&FrozenClock), Duration::ZERO);
+    }
+
+    // `test#roll` returns `wasi:random/random.get-random-u64`
+    const ROLLS: &str = r#"
+    (component
+      (import "wasi:random/random@0.2.0" (instance $random
+        (export "get-random-u64" (func (result u64)))))
+      (core func $get (canon lower (func $random "get-random-u64")))
+      (core instance $host (export "get" (func $get)))
+      (core module $m
+        (import "random" "get" (func $get (result i64)))
+        (func (export "roll") (result i64) call $get))
+      (core instance $i (instantiate $m (with "random" (instance $host))))
+      (func $roll (result u64) (canon lift (core func $i "roll")))
+      (instance $test (export "roll" (func $roll)))
+      (export "test" (instance $test)))
+    "#;
+
+    #[tokio::test]
+    async fn deterministic_plugins_roll_the_same_every_run() {
+        let app = TestApp::new();
+        let roll = |id: &'static str, seed: u64| {
+            let manifest = format!("id = \"{}\"\nname = \"Dice\"\nversion = \"1.0.0\"\ndeterministic = true\nseed = {}", id, seed);
+            let app = &app;
+            async move {
+                let id = app.load_file(&format!("{}.wasm", id), ROLLS, Some(&manifest)).await.unwrap();
+                crate::dynamic::invoke(&id, "test#roll", serde_json::Value::Null, None).await.unwrap()
+            }
+        };
+
+        let first = roll("sandbox-dice-1", 7).await;
+        assert_eq!(roll("sandbox-dice-2", 7).await, first);
+        assert_ne!(roll("sandbox-dice-3", 8).await, first);
+    }
+
+    fn mount(host: &str, guest: &str) -> Mount {
+        Mount {
+            host: PathBuf::from(host),
+            guest: guest.to_string(),
+            mode: MountMode::ReadOnly,
+        }
+    }
+
+    #[test]
+    fn mounts_stay_inside_the_mount_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("shared");
+        std::fs::create_dir_all(root.join("reports")).unwrap();
+        std::fs::create_dir_all(dir.path().join("secrets")).unwrap();
+
+        let resolved = resolve_mount(&mount("reports", "/reports"), Some(&root)).unwrap();
+        assert_eq!(resolved, root.join("reports").canonicalize().unwrap());
+
+        assert!(resolve_mount(&mount("../secrets", "/secrets"), Some(&root)).is_err());
+        let absolute = dir.path().join("secrets");
+        assert!(resolve_mount(&mount(absolute.to_str().unwrap(), "/secrets"), Some(&root)).is_err());
+        assert!(resolve_mount(&mount("reports", "/reports"), None).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_dont_lead_out_of_the_mount_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("shared");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(dir.path().join("secrets")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secrets"), root.join("link")).unwrap();
+
+        assert!(resolve_mount(&mount("link", "/secrets"), Some(&root)).is_err());
+    }
+
+    #[test]
+    fn mounts_cant_shadow_the_host_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("reports")).unwrap();
+        for guest in ["/data", "/plugin", "reports"] {
+            assert!(resolve_mount(&mount("reports", guest), Some(dir.path())).is_err(), "{}", guest);
+        }
+    }
+
+    #[test]
+    fn plugins_see_only_the_env_they_declare() {
+        std::env::set_var("PATO_SANDBOX_TEST_INHERITED", "from host");
+        std::env::set_var("PATO_SANDBOX_TEST_OVERRIDDEN", "from host");
+        let manifest = manifest(
+            r#"inherit-env = ["PATO_SANDBOX_TEST_INHERITED", "PATO_SANDBOX_TEST_OVERRIDDEN", "PATO_SANDBOX_TEST_UNSET"]
+            [env]
+            PATO_SANDBOX_TEST_OVERRIDDEN = "from manifest"
+            GREETING = "hello""#,
+        );
+
+        let env = plugin_env(&manifest);
+        let expected = [
+            ("GREETING", "hello"),
+            ("PATO_SANDBOX_TEST_INHERITED", "from host"),
+            ("PATO_SANDBOX_TEST_OVERRIDDEN", "from manifest"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        assert_eq!(env, expected);
+    }
+}
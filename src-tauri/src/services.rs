@@ -0,0 +1,185 @@
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+use wasmtime::component::Linker;
+use wasmtime::Engine;
+
+use crate::error::PluginError;
+use crate::host::PluginHost;
+use crate::manifest::Capability;
+use crate::pato::plugin_ui::{
+    chunks, config, db, events, filter_helper, handles, host_info, http, log, messaging, timers,
+};
+
+/// A host interface plugins can import. Each plugin gets a linker of its own,
+/// with only the services it has been granted, so adding an interface is a
+/// matter of registering one of these rather than touching the load path.
+pub trait HostService: Send + Sync {
+    /// Identifies the service in logs; registering another with the same name replaces it
+    fn name(&self) -> &str;
+
+    /// Linked only for plugins granted this capability; `None` links it for every plugin
+    fn capability(&self) -> Option<Capability> {
+        None
+    }
+
+    /// Defines the service's functions. They run against the calling plugin's `PluginHost`.
+    fn add_to_linker(&self, linker: &mut Linker<PluginHost>) -> wasmtime::Result<()>;
+}
+
+/// The host's own interfaces, linked with their generated `add_to_linker`.
+/// Those behind a capability aren't linked without it, and check it again when called.
+struct Builtin {
+    name: &'static str,
+    capability: Option<Capability>,
+    link: fn(&mut Linker<PluginHost>) -> wasmtime::Result<()>,
+}
+
+impl Builtin {
+    fn new(name: &'static str, link: fn(&mut Linker<PluginHost>) -> wasmtime::Result<()>) -> Self {
+        Builtin { name, capability: None, link }
+    }
+
+    fn requires(mut self, capability: Capability) -> Self {
+        self.capability = Some(capability);
+        self
+    }
+}
+
+impl HostService for Builtin {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn capability(&self) -> Option<Capability> {
+        self.capability
+    }
+
+    fn add_to_linker(&self, linker: &mut Linker<PluginHost>) -> wasmtime::Result<()> {
+        (self.link)(linker)
+    }
+}
+
+/// Every registered service, in the order they're linked.
+pub struct HostServices {
+    services: Vec<Arc<dyn HostService>>,
+}
+
+impl HostServices {
+    fn builtin() -> Self {
+        // WASI covers stdio, gated by what the plugin's WASI context exposes
+        let builtins = [
+            Builtin::new("wasi", wasmtime_wasi::add_to_linker_async),
+            Builtin::new("log", |linker| log::add_to_linker(linker, |host: &mut PluginHost| host)),
+            Builtin::new("events", |linker| events::add_to_linker(linker, |host: &mut PluginHost| host)),
+            Builtin::new("config", |linker| config::add_to_linker(linker, |host: &mut PluginHost| host)),
+            Builtin::new("messaging", |linker| messaging::add_to_linker(linker, |host: &mut PluginHost| host)),
+            Builtin::new("db", |linker| db::add_to_linker(linker, |host: &mut PluginHost| host))
+                .requires(Capability::Storage),
+            Builtin::new("http", |linker| http::add_to_linker(linker, |host: &mut PluginHost| host))
+                .requires(Capability::Network),
+            Builtin::new("handles", |linker| handles::add_to_linker(linker, |host: &mut PluginHost| host))
+                .requires(Capability::Storage),
+            Builtin::new("timers", |linker| timers::add_to_linker(linker, |host: &mut PluginHost| host)),
+            Builtin::new("filter-helper", |linker| filter_helper::add_to_linker(linker, |host: &mut PluginHost| host)),
+            Builtin::new("chunks", |linker| chunks::add_to_linker(linker, |host: &mut PluginHost| host)),
+            Builtin::new("host-info", |linker| host_info::add_to_linker(linker, |host: &mut PluginHost| host)),
+        ];
+        HostServices {
+            services: builtins
+                .into_iter()
+                .map(|builtin| Arc::new(builtin) as Arc<dyn HostService>)
+                .collect(),
+        }
+    }
+
+    fn register(&mut self, service: Arc<dyn HostService>) {
+        match self.services.iter_mut().find(|existing| existing.name() == service.name()) {
+            Some(existing) => *existing = service,
+            None => self.services.push(service),
+        }
+    }
+
+    fn linker(&self, engine: &Engine, capabilities: &[Capability]) -> Result<Linker<PluginHost>, PluginError> {
+        let mut linker = Linker::new(engine);
+        for service in &self.services {
+            if service.capability().is_some_and(|capability| !capabilities.contains(&capability)) {
+                continue;
+            }
+            service
+                .add_to_linker(&mut linker)
+                .map_err(|e| PluginError::LinkError(format!("{}: {}", service.name(), e)))?;
+        }
+        Ok(linker)
+    }
+}
+
+static SERVICES: LazyLock<RwLock<HostServices>> = LazyLock::new(|| RwLock::new(HostServices::builtin()));
+
+/// Adds `service` for plugins loaded from now on, replacing any with the same name.
+pub fn register(service: Arc<dyn HostService>) {
+    println!("🧩 Registered host service {}", service.name());
+    SERVICES.write().unwrap_or_else(PoisonError::into_inner).register(service);
+}
+
+/// A linker with every service a plugin granted `capabilities` may use.
+pub fn linker(engine: &Engine, capabilities: &[Capability]) -> Result<Linker<PluginHost>, PluginError> {
+    SERVICES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .linker(engine, capabilities)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{component, engine};
+    use wasmtime::component::Component;
+
+    // Needs the network, and answers `ping` with 7
+    struct Pinger;
+
+    impl HostService for Pinger {
+        fn name(&self) -> &str {
+            "pinger"
+        }
+
+        fn capability(&self) -> Option<Capability> {
+            Some(Capability::Network)
+        }
+
+        fn add_to_linker(&self, linker: &mut Linker<PluginHost>) -> wasmtime::Result<()> {
+            linker
+                .instance("test:pinger/ping")?
+                .func_wrap("ping", |_, (): ()| Ok((7u32,)))
+        }
+    }
+
+    const IMPORTS_PING: &str = r#"
+    (component
+      (import "test:pinger/ping" (instance (export "ping" (func (result u32))))))
+    "#;
+
+    #[test]
+    fn services_are_only_linked_for_plugins_granted_their_capability() {
+        let mut services = HostServices::builtin();
+        services.register(Arc::new(Pinger));
+        let plugin = Component::new(engine(), component(IMPORTS_PING)).unwrap();
+
+        let linker = services.linker(engine(), &[Capability::Stdio]).unwrap();
+        assert!(linker.instantiate_pre(&plugin).is_err());
+        let linker = services.linker(engine(), &[Capability::Network]).unwrap();
+        assert!(linker.instantiate_pre(&plugin).is_ok());
+    }
+
+    #[test]
+    fn storage_and_network_interfaces_need_their_capabilities() {
+        let services = HostServices::builtin();
+        let capability = |name: &str| {
+            let service = services.services.iter().find(|service| service.name() == name).unwrap();
+            service.capability()
+        };
+        assert_eq!(capability("db"), Some(Capability::Storage));
+        assert_eq!(capability("handles"), Some(Capability::Storage));
+        assert_eq!(capability("http"), Some(Capability::Network));
+        assert_eq!(capability("log"), None);
+    }
+}
@@ -0,0 +1,191 @@
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::embedder::AppHandle;
+use crate::error::PluginError;
+use crate::manifest::PluginManifest;
+
+/// Signature settings in the app config dir, e.g.
+///
+/// ```toml
+/// mode = "strict"
+/// trusted-keys = ["<64 hex digits of an Ed25519 public key>"]
+/// ```
+const SIGNING_FILE: &str = "signing.toml";
+
+/// What happens to a plugin that isn't signed by a trusted key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SigningMode {
+    /// Unsigned plugins are refused
+    Strict,
+    /// Unsigned plugins load with a warning
+    #[default]
+    Permissive,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct SigningConfig {
+    #[serde(default)]
+    mode: SigningMode,
+    #[serde(default)]
+    trusted_keys: Vec<String>,
+}
+
+/// Checks the Ed25519 signature over a plugin's component bytes against the
+/// trusted keys. The signature comes from the manifest's `signature`, or else a
+/// `.sig` file next to the plugin, as hex.
+///
+/// A signature that doesn't verify is always an error; a missing one only is in
+/// strict mode.
+pub fn verify(
+    app: &AppHandle,
+    id: &str,
+    plugin_path: &Path,
+    manifest: Option<&PluginManifest>,
+    component_bytes: &[u8],
+) -> Result<(), PluginError> {
+    let config = read_config(app)?;
+    let invalid = |message: String| PluginError::SignatureInvalid(format!("{}: {}", id, message));
+
+    let signature = match manifest.and_then(|m| m.signature.clone()) {
+        Some(signature) => Some(signature),
+        None => read_sig_file(plugin_path)?,
+    };
+    let Some(signature) = signature else {
+        return match config.mode {
+            SigningMode::Strict => Err(invalid("plugin isn't signed".to_string())),
+            SigningMode::Permissive => {
+                println!("⚠️ Plugin {} isn't signed", id);
+                Ok(())
+            }
+        };
+    };
+
+    let signature: [u8; 64] = decode_hex(&signature)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| invalid("signature isn't 64 hex-encoded bytes".to_string()))?;
+    let signature = Signature::from_bytes(&signature);
+
+    for key in &config.trusted_keys {
+        let key: [u8; 32] = decode_hex(key)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| PluginError::Io(format!("{}: trusted key {} isn't 32 hex-encoded bytes", SIGNING_FILE, key)))?;
+        let Ok(key) = VerifyingKey::from_bytes(&key) else {
+            continue;
+        };
+        if key.verify(component_bytes, &signature).is_ok() {
+            println!("🔏 Plugin {} is signed by a trusted key", id);
+            return Ok(());
+        }
+    }
+
+    Err(invalid("signature doesn't match any trusted key".to_string()))
+}
+
+fn read_sig_file(plugin_path: &Path) -> Result<Option<String>, PluginError> {
+    let mut sig_path = plugin_path.as_os_str().to_owned();
+    sig_path.push(".sig");
+    let sig_path = PathBuf::from(sig_path);
+    if !sig_path.exists() {
+        return Ok(None);
+    }
+
+    std::fs::read_to_string(&sig_path)
+        .map(|text| Some(text.trim().to_string()))
+        .map_err(|e| PluginError::Io(format!("{:?}: {}", sig_path, e)))
+}
+
+fn read_config(app: &AppHandle) -> Result<SigningConfig, PluginError> {
+    let path = app.app_config_dir().map(|dir| dir.join(SIGNING_FILE));
+    let Some(path) = path.filter(|path| path.exists()) else {
+        return Ok(SigningConfig::default());
+    };
+
+    // Unlike other settings, a broken signing file fails closed
+    let text = std::fs::read_to_string(&path).map_err(|e| PluginError::Io(format!("{:?}: {}", path, e)))?;
+    toml::from_str(&text).map_err(|e| PluginError::Io(format!("{:?}: {}", path, e)))
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const BYTES: &[u8] = b"component bytes";
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn configure(app: &TestApp, mode: &str, trusted: &SigningKey) {
+        std::fs::create_dir_all(app.config_dir()).unwrap();
+        let text = format!(
+            "mode = \"{}\"\ntrusted-keys = [\"{}\"]\n",
+            mode,
+            hex(&trusted.verifying_key().to_bytes())
+        );
+        std::fs::write(app.config_dir().join(SIGNING_FILE), text).unwrap();
+    }
+
+    fn sign(app: &TestApp, key: &SigningKey) -> PathBuf {
+        let path = app.write_plugin("signed.wasm", BYTES, None);
+        std::fs::write(path.with_extension("wasm.sig"), hex(&key.sign(BYTES).to_bytes())).unwrap();
+        path
+    }
+
+    #[test]
+    fn unsigned_plugins_only_load_when_permissive() {
+        let app = TestApp::new();
+        let path = app.write_plugin("unsigned.wasm", BYTES, None);
+        // No signing file at all is permissive
+        assert!(verify(&app.handle, "unsigned", &path, None, BYTES).is_ok());
+
+        configure(&app, "strict", &SigningKey::from_bytes(&[1; 32]));
+        let result = verify(&app.handle, "unsigned", &path, None, BYTES);
+        assert!(matches!(result, Err(PluginError::SignatureInvalid(_))));
+    }
+
+    #[test]
+    fn trusted_signatures_load_even_when_strict() {
+        let app = TestApp::new();
+        let key = SigningKey::from_bytes(&[1; 32]);
+        configure(&app, "strict", &key);
+        let path = sign(&app, &key);
+        assert!(verify(&app.handle, "signed", &path, None, BYTES).is_ok());
+        // The signature is over these bytes and no others
+        assert!(verify(&app.handle, "signed", &path, None, b"other bytes").is_err());
+    }
+
+    #[test]
+    fn bad_signatures_fail_even_when_permissive() {
+        let app = TestApp::new();
+        configure(&app, "permissive", &SigningKey::from_bytes(&[1; 32]));
+        let path = sign(&app, &SigningKey::from_bytes(&[2; 32]));
+        let result = verify(&app.handle, "signed", &path, None, BYTES);
+        assert!(matches!(result, Err(PluginError::SignatureInvalid(_))));
+    }
+
+    #[test]
+    fn a_broken_signing_file_fails_closed() {
+        let app = TestApp::new();
+        std::fs::create_dir_all(app.config_dir()).unwrap();
+        std::fs::write(app.config_dir().join(SIGNING_FILE), "mode = \"lenient\"").unwrap();
+        let path = app.write_plugin("unsigned.wasm", BYTES, None);
+        assert!(matches!(verify(&app.handle, "unsigned", &path, None, BYTES), Err(PluginError::Io(_))));
+    }
+}
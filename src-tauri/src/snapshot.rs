@@ -0,0 +1,92 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::error::PluginError;
+use crate::host::PluginHost;
+use crate::registry::PluginInstance;
+use crate::{create_instance, lifecycle, registry, shared_engine};
+
+/// Plugin `id` as it stood right after `init`, kept aside so a respawn can swap
+/// it in rather than instantiating the plugin and running `init` all over again.
+///
+/// Wasmtime can't copy a component instance's state out of its store, since the
+/// core memories and globals inside aren't reachable through the component API,
+/// so the snapshot is a whole spare instance, instantiated and initialized on
+/// the side while nothing is waiting on it. Only plugins that export `init` get
+/// one; for the rest a snapshot would just be a second copy of a blank instance.
+#[derive(Clone, Default)]
+pub struct Snapshot(Arc<Mutex<Option<PluginInstance>>>);
+
+impl Snapshot {
+    fn lock(&self) -> MutexGuard<'_, Option<PluginInstance>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Hands over the snapshot, leaving none until the next `refresh`.
+    pub fn take(&self) -> Option<PluginInstance> {
+        self.lock().take()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    /// Applies a change made to the running instance's host state, like a config
+    /// update, to the snapshot too, so restoring it doesn't undo the change.
+    pub fn update(&self, change: impl FnOnce(&mut PluginHost)) {
+        if let Some(snapshot) = self.lock().as_mut() {
+            change(snapshot.store.data_mut());
+        }
+    }
+}
+
+/// Takes a new snapshot of plugin `id` in the background, if it exports `init`
+/// and doesn't already have one. `init` runs for the snapshot then, so anything
+/// it does outside the instance, like emitting events, happens then too.
+pub fn refresh(id: &str) {
+    let id = id.to_string();
+    crate::async_runtime::spawn(async move {
+        if let Err(e) = capture(&id).await {
+            println!("⚠️ Couldn't snapshot plugin {}: {}", id, e);
+        }
+    });
+}
+
+async fn capture(id: &str) -> Result<(), PluginError> {
+    let engine = shared_engine()?;
+    let (snapshot, instance, component, path, manifest, data_dir, output, fuel_budget, cancel) = {
+        let registry = registry();
+        let loaded = registry.get(id).ok_or_else(|| PluginError::NotLoaded(id.to_string()))?;
+        if loaded.snapshot.is_ready() || !lifecycle::has_init(engine, &loaded.component) {
+            return Ok(());
+        }
+        (
+            loaded.snapshot.clone(),
+            loaded.instance.clone(),
+            loaded.component.clone(),
+            loaded.path.clone(),
+            loaded.manifest.clone(),
+            loaded.data_dir.clone(),
+            loaded.output.clone(),
+            loaded.fuel_budget,
+            loaded.cancel.clone(),
+        )
+    };
+
+    // The app handle lives in the running instance's store
+    let app = instance.lock().await.store.data().app.clone();
+    let spare = create_instance(
+        &app,
+        engine,
+        id,
+        &path,
+        manifest.as_ref(),
+        &component,
+        &data_dir,
+        &output,
+        fuel_budget,
+        cancel,
+    )
+    .await?;
+    *snapshot.lock() = Some(spare);
+    Ok(())
+}
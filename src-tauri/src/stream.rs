@@ -0,0 +1,94 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::embedder::AppHandle;
+
+/// A piece of a call's output, sent to the frontend as a `plugin-chunk` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct Chunk {
+    pub plugin: String,
+    /// Which call produced it; every call gets a new id
+    pub call_id: u64,
+    /// Position within the call, starting at zero
+    pub index: u64,
+    pub data: Vec<u8>,
+}
+
+static NEXT_CALL_ID: AtomicU64 = AtomicU64::new(1);
+
+// Chunks waiting to be emitted, drained by the task `start` spawns
+static CHUNKS: OnceLock<UnboundedSender<Chunk>> = OnceLock::new();
+
+/// A fresh id for the call about to start.
+pub fn next_call_id() -> u64 {
+    NEXT_CALL_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Starts the task that emits queued chunks, in the order they were sent.
+pub fn start(app: AppHandle) {
+    let (sender, mut receiver) = mpsc::unbounded_channel::<Chunk>();
+    if CHUNKS.set(sender).is_err() {
+        return;
+    }
+    crate::async_runtime::spawn(async move {
+        while let Some(chunk) = receiver.recv().await {
+            if let Err(e) = app.emit("plugin-chunk", &chunk) {
+                log::warn!("[{}] Failed to forward chunk {} of call {}: {}", chunk.plugin, chunk.index, chunk.call_id, e);
+            }
+        }
+    });
+}
+
+/// Queues `chunk` for the frontend. This runs inside the plugin's call, so it
+/// only hands the chunk off and never waits on the UI.
+pub fn forward(chunk: Chunk) {
+    match CHUNKS.get() {
+        Some(sender) => {
+            // The receiver only goes away when the app is shutting down
+            let _ = sender.send(chunk);
+        }
+        None => log::debug!("[{}] Dropping chunk before streaming started", chunk.plugin),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pato::plugin_ui::chunks::Host as _;
+    use crate::testing::{with_host, TestApp, ANSWER};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn chunks_reach_the_frontend_in_order_tagged_with_their_call() {
+        let app = TestApp::new();
+        // Streaming is started once per process, and this is the only test that does
+        start(app.handle.clone());
+        let id = app.load("stream-chunks", ANSWER).await.unwrap();
+
+        let call_id = with_host(&id, |host| {
+            host.call_id = next_call_id();
+            host.chunks_sent = 0;
+            for data in [b"a", b"b", b"c"] {
+                host.emit_chunk(data.to_vec());
+            }
+            host.call_id
+        })
+        .await;
+
+        for _ in 0..100 {
+            if app.emitted("plugin-chunk").len() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let expected: Vec<_> = [b'a', b'b', b'c']
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| json!({ "plugin": id, "call_id": call_id, "index": index, "data": [byte] }))
+            .collect();
+        assert_eq!(app.emitted("plugin-chunk"), expected);
+    }
+}
@@ -0,0 +1,146 @@
+//! Shared setup for the unit tests: the engine, an embedder rooted in a
+//! temporary directory, and plugins written as WAT.
+
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Once};
+use wasmtime::Engine;
+
+use crate::embedder::{AppHandle, Embedder};
+use crate::error::PluginError;
+use crate::host::PluginHost;
+use crate::manifest::PluginManifest;
+use crate::{create_engine, epoch, registry, ENGINE};
+
+/// A component exporting `test#get-number`, which answers 42, and nothing else.
+pub const ANSWER: &str = r#"
+(component
+  (core module $m
+    (func (export "get-number") (result i32) i32.const 42))
+  (core instance $i (instantiate $m))
+  (func $get-number (result u32) (canon lift (core func $i "get-number")))
+  (instance $test (export "get-number" (func $get-number)))
+  (export "test" (instance $test)))
+"#;
+
+/// The engine every test plugin is compiled with, with its epoch ticking.
+pub fn engine() -> &'static Engine {
+    static TICKER: Once = Once::new();
+    let engine = ENGINE.get_or_init(|| create_engine().expect("couldn't create the test engine"));
+    TICKER.call_once(|| epoch::start(engine.clone()));
+    engine
+}
+
+/// Runs `f` against loaded plugin `id`'s host state, the way its host calls see it.
+pub async fn with_host<R>(id: &str, f: impl FnOnce(&mut PluginHost) -> R) -> R {
+    let instance = registry().get(id).expect("test plugin isn't loaded").instance.clone();
+    let mut guard = instance.lock().await;
+    f(guard.store.data_mut())
+}
+
+/// Waits for plugin `id`'s background snapshot to be taken.
+pub async fn wait_for_snapshot(id: &str) {
+    for _ in 0..500 {
+        if registry().get(id).is_some_and(|loaded| loaded.snapshot.is_ready()) {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("plugin {} never got a snapshot", id);
+}
+
+/// Compiles `wat` to a component binary.
+pub fn component(wat: &str) -> Vec<u8> {
+    wat::parse_str(wat).expect("test plugin isn't valid WAT")
+}
+
+/// An app for the runtime to run in, with its directories in a temporary one
+/// that's removed when it's dropped.
+pub struct TestApp {
+    pub handle: AppHandle,
+    pub events: Arc<Mutex<Vec<(String, Value)>>>,
+    pub root: tempfile::TempDir,
+}
+
+struct TestEmbedder {
+    root: PathBuf,
+    events: Arc<Mutex<Vec<(String, Value)>>>,
+}
+
+impl Embedder for TestEmbedder {
+    fn emit_value(&self, event: &str, payload: Value) -> Result<(), String> {
+        self.events.lock().unwrap().push((event.to_string(), payload));
+        Ok(())
+    }
+
+    fn app_data_dir(&self) -> Option<PathBuf> {
+        Some(self.root.join("data"))
+    }
+
+    fn app_config_dir(&self) -> Option<PathBuf> {
+        Some(self.root.join("config"))
+    }
+
+    fn app_cache_dir(&self) -> Option<PathBuf> {
+        Some(self.root.join("cache"))
+    }
+
+    fn version(&self) -> String {
+        "0.0.0-test".to_string()
+    }
+}
+
+impl TestApp {
+    pub fn new() -> Self {
+        let root = tempfile::tempdir().expect("couldn't create a temporary directory");
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let handle = AppHandle::new(TestEmbedder {
+            root: root.path().to_path_buf(),
+            events: events.clone(),
+        });
+        TestApp { handle, events, root }
+    }
+
+    pub fn config_dir(&self) -> PathBuf {
+        self.root.path().join("config")
+    }
+
+    pub fn plugins_dir(&self) -> PathBuf {
+        self.root.path().join("plugins")
+    }
+
+    /// Payloads emitted as `event` so far, oldest first.
+    pub fn emitted(&self, event: &str) -> Vec<Value> {
+        let events = self.events.lock().unwrap();
+        events
+            .iter()
+            .filter(|(name, _)| name == event)
+            .map(|(_, payload)| payload.clone())
+            .collect()
+    }
+
+    /// Loads `wat` as an embedded plugin under `name`. Test plugins share one
+    /// registry, so every test needs names of its own.
+    pub async fn load(&self, name: &str, wat: &str) -> Result<String, PluginError> {
+        crate::load_embedded_plugin(&self.handle, engine(), name, &component(wat)).await
+    }
+
+    /// Writes `wat` into the plugins directory as `file_name` (with a manifest
+    /// next to it, if one's given) and loads it from there like any other plugin.
+    pub async fn load_file(&self, file_name: &str, wat: &str, manifest: Option<&str>) -> Result<String, PluginError> {
+        let path = self.write_plugin(file_name, &component(wat), manifest);
+        crate::load_plugin(&self.handle, engine(), &path).await
+    }
+
+    /// Writes a plugin into the plugins directory without loading it.
+    pub fn write_plugin(&self, file_name: &str, bytes: &[u8], manifest: Option<&str>) -> PathBuf {
+        let dir = self.plugins_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(file_name);
+        std::fs::write(&path, bytes).unwrap();
+        if let Some(manifest) = manifest {
+            std::fs::write(PluginManifest::path_for(&path), manifest).unwrap();
+        }
+        path
+    }
+}
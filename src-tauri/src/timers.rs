@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::error::PluginError;
+use crate::registry::PluginInstance;
+use crate::{arm_call_limits, dynamic, epoch, plugin_instance, recovery};
+
+/// The export a plugin implements to be told its timers fired, from the `timer-callback` interface.
+const ON_TIMER_EXPORT: &str = "pato:plugin-ui/timer-callback@0.1.0#on-timer";
+
+struct Timer {
+    plugin_id: String,
+    task: crate::async_runtime::JoinHandle<()>,
+}
+
+// Timers that haven't finished or been cancelled, by timer id
+static TIMERS: LazyLock<Mutex<HashMap<u64, Timer>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Schedules plugin `plugin_id`'s `on-timer` to run after `delay_ms`, and then
+/// every `delay_ms` if `periodic`. This is called from inside the plugin's
+/// store, so the timer only ever fires later, from its own task.
+pub fn schedule(plugin_id: &str, delay_ms: u64, periodic: bool) -> u64 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let delay = Duration::from_millis(delay_ms);
+    // A zero period would spin, so periodic timers tick at least once per epoch tick
+    let period = delay.max(epoch::TICK);
+
+    // Held until the timer is in the map, so a timer that finishes straight away
+    // can't try to remove itself before it's been added
+    let mut pending = timers();
+    let task_plugin_id = plugin_id.to_string();
+    let task = crate::async_runtime::spawn(async move {
+        tokio::time::sleep(delay).await;
+        loop {
+            match fire(&task_plugin_id, id).await {
+                // The plugin's gone, and its timers with it
+                Err(PluginError::NotLoaded(_)) => break,
+                Err(PluginError::Disabled(_)) => {}
+                Err(e) => println!("⚠️ Timer {} for plugin {} failed: {}", id, task_plugin_id, e),
+                Ok(()) => {}
+            }
+            if !periodic {
+                break;
+            }
+            tokio::time::sleep(period).await;
+        }
+        timers().remove(&id);
+    });
+
+    let timer = Timer {
+        plugin_id: plugin_id.to_string(),
+        task,
+    };
+    pending.insert(id, timer);
+    id
+}
+
+/// Cancels timer `id`, if it belongs to `plugin_id`; plugins can't cancel each other's timers.
+pub fn cancel(plugin_id: &str, id: u64) {
+    let mut timers = timers();
+    if timers.get(&id).is_some_and(|timer| timer.plugin_id == plugin_id) {
+        if let Some(timer) = timers.remove(&id) {
+            timer.task.abort();
+        }
+    }
+}
+
+/// The ids of every timer `plugin_id` has right now.
+pub fn scheduled(plugin_id: &str) -> Vec<u64> {
+    timers()
+        .iter()
+        .filter(|(_, timer)| timer.plugin_id == plugin_id)
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+/// Cancels every timer `plugin_id` has, e.g. when it's unloaded.
+pub fn cancel_all(plugin_id: &str) {
+    timers().retain(|_, timer| {
+        let keep = timer.plugin_id != plugin_id;
+        if !keep {
+            timer.task.abort();
+        }
+        keep
+    });
+}
+
+// A panic elsewhere while holding the lock can't leave the map half-updated
+fn timers() -> MutexGuard<'static, HashMap<u64, Timer>> {
+    TIMERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Calls the plugin's `on-timer`, queueing behind any call already running on its
+/// store. A trap leaves the plugin to be re-instantiated, like any other call.
+async fn fire(plugin_id: &str, id: u64) -> Result<(), PluginError> {
+    recovery::with_trap_retries(plugin_id, || fire_once(plugin_id, id)).await
+}
+
+async fn fire_once(plugin_id: &str, id: u64) -> Result<(), PluginError> {
+    let (instance, fuel_budget) = plugin_instance(plugin_id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let on_timer = dynamic::find_func(store, instance, ON_TIMER_EXPORT)?
+        .typed::<(u64,), ()>(&*store)
+        .map_err(|e| PluginError::LinkError(e.to_string()))?;
+
+    arm_call_limits(store, fuel_budget, ON_TIMER_EXPORT, None)?;
+    on_timer
+        .call_async(&mut *store, (id,))
+        .await
+        .map_err(PluginError::trap)?;
+    on_timer
+        .post_return_async(&mut *store)
+        .await
+        .map_err(PluginError::trap)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn timers_that_finish_straight_away_are_forgotten() {
+        // Nothing's loaded under this id, so the timer gives up as soon as it fires
+        let id = schedule("timers-not-loaded", 50, true);
+        assert_eq!(scheduled("timers-not-loaded"), [id]);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(scheduled("timers-not-loaded").is_empty());
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use wasmtime::component::Val;
+
+use crate::arm_call_limits;
+use crate::dynamic;
+use crate::error::PluginError;
+use crate::registry::PluginInstance;
+
+/// Optional export from the `ui` interface.
+const CONTRIBUTIONS_EXPORT: &str = "pato:plugin-ui/ui@0.1.0#contributions";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContributionKind {
+    MenuItem,
+    Panel,
+}
+
+/// Something a plugin wants added to the UI, as returned by its `contributions`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiContribution {
+    /// The plugin that contributed it; filled in by the host
+    #[serde(default)]
+    pub plugin: String,
+    pub id: String,
+    pub kind: ContributionKind,
+    pub label: String,
+    /// The export to call when the contribution is used, e.g. `test#get-number`
+    #[serde(rename(deserialize = "target-function"))]
+    pub target_function: String,
+}
+
+/// Asks a freshly initialised plugin for its UI contributions, keeping only
+/// those whose `target-function` it really exports. Plugins without the `ui`
+/// export contribute nothing.
+pub async fn collect(
+    id: &str,
+    plugin: &mut PluginInstance,
+    fuel_budget: u64,
+) -> Result<Vec<UiContribution>, PluginError> {
+    let PluginInstance { store, instance } = plugin;
+
+    let func = match dynamic::find_func(store, instance, CONTRIBUTIONS_EXPORT) {
+        Ok(func) => func,
+        Err(PluginError::NoSuchFunction(_)) => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    // Records come back as JSON most easily, by the same route `invoke_export` uses
+    let mut results = vec![Val::Bool(false)];
+    arm_call_limits(store, fuel_budget, CONTRIBUTIONS_EXPORT, None)?;
+    func.call_async(&mut *store, &[], &mut results)
+        .await
+        .map_err(PluginError::trap)?;
+    func.post_return_async(&mut *store)
+        .await
+        .map_err(PluginError::trap)?;
+
+    let contributions: Vec<UiContribution> = serde_json::from_value(dynamic::val_to_json(&results[0]))
+        .map_err(|e| PluginError::LinkError(format!("unexpected `contributions` result: {}", e)))?;
+
+    let mut valid = Vec::new();
+    for mut contribution in contributions {
+        if let Err(e) = dynamic::find_func(store, instance, &contribution.target_function) {
+            println!("⚠️ Ignoring UI contribution {} from {}: {}", contribution.id, id, e);
+            continue;
+        }
+        contribution.plugin = id.to_string();
+        valid.push(contribution);
+    }
+    Ok(valid)
+}
@@ -0,0 +1,74 @@
+use serde::Serialize;
+use wasmtime::{ResourceLimiter, StoreLimits};
+
+/// Wraps the store's limits to also keep track of how much the plugin has
+/// grown, so usage can be read without calling into the plugin.
+pub struct UsageLimiter {
+    pub limits: StoreLimits,
+    /// Linear memory across all of the plugin's memories
+    pub memory_bytes: usize,
+    /// Elements across all of the plugin's tables
+    pub table_elements: u64,
+}
+
+impl UsageLimiter {
+    pub fn new(limits: StoreLimits) -> Self {
+        UsageLimiter {
+            limits,
+            memory_bytes: 0,
+            table_elements: 0,
+        }
+    }
+}
+
+impl ResourceLimiter for UsageLimiter {
+    fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> wasmtime::Result<bool> {
+        let allowed = self.limits.memory_growing(current, desired, maximum)?;
+        if allowed {
+            self.memory_bytes += desired - current;
+        }
+        Ok(allowed)
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> wasmtime::Result<bool> {
+        let allowed = self.limits.table_growing(current, desired, maximum)?;
+        if allowed {
+            self.table_elements += u64::from(desired - current);
+        }
+        Ok(allowed)
+    }
+
+    fn memory_grow_failed(&mut self, error: wasmtime::Error) -> wasmtime::Result<()> {
+        self.limits.memory_grow_failed(error)
+    }
+
+    fn table_grow_failed(&mut self, error: wasmtime::Error) -> wasmtime::Result<()> {
+        self.limits.table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
+
+/// What `plugin_resource_usage` reports for a plugin.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceUsage {
+    pub id: String,
+    /// Current size of the plugin's linear memory
+    pub memory_bytes: usize,
+    /// The cap `memory_bytes` can grow to
+    pub memory_limit_bytes: usize,
+    pub table_elements: u64,
+    /// `handle`s the plugin holds in its resource table. WASI's own resources
+    /// (streams and the like) live there too but aren't counted.
+    pub open_handles: usize,
+}
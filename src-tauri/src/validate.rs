@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime};
+use wasmtime::component::Component;
+
+use crate::error::PluginError;
+use crate::introspect::describe_exports;
+use crate::package::read_plugin;
+use crate::registry::{millis_since_epoch, IdSource, PluginInfo};
+use crate::{derived_id, plugin_id, registry, shared_engine, taken_by_another_file, version};
+
+/// Exports every plugin must have for the host to call it.
+pub const REQUIRED_EXPORTS: &[&str] = &["test#get-number"];
+
+/// Exports the host calls when a plugin has them; lacking one is only a warning.
+pub const OPTIONAL_EXPORTS: &[&str] = &["test#transform", "test#get-stats"];
+
+/// What `validate_plugin` found out about a `.wasm` file.
+#[derive(Serialize)]
+pub struct PluginValidation {
+    #[serde(flatten)]
+    pub info: PluginInfo,
+    /// Required exports the component has
+    pub present: Vec<String>,
+    /// Required exports the component lacks; empty for a loadable plugin
+    pub missing: Vec<String>,
+    /// Things worth knowing that don't stop the plugin loading, like optional exports it lacks
+    pub warnings: Vec<String>,
+}
+
+/// Compiles the component at `path` and checks its manifest and exports, without
+/// instantiating it, running `init`, or changing the registry.
+pub async fn validate_plugin(path: PathBuf) -> Result<PluginValidation, PluginError> {
+    let stem = plugin_id(&path).ok_or_else(|| PluginError::Io(format!("{:?} has no file name", path)))?;
+    let (manifest, bytes) = read_plugin(&path)?;
+    let (id, id_source) = match &manifest {
+        Some(manifest) => (manifest.id.clone(), IdSource::Manifest),
+        None => {
+            // The id loading it would give it, next to what's already loaded
+            let registry = registry();
+            let id = derived_id(&path, &bytes, |id| taken_by_another_file(&registry, id, &path));
+            (id.unwrap_or(stem), IdSource::Derived)
+        }
+    };
+
+    // Compile off the async workers, with the engine a real load would use, so
+    // the compile time is what loading would take
+    let engine = shared_engine()?;
+    let size_bytes = bytes.len() as u64;
+    let compile_started = Instant::now();
+    let exports = tokio::task::spawn_blocking(move || {
+        let component = Component::new(engine, &bytes).map_err(|e| PluginError::compile(&bytes, e))?;
+        version::check(engine, &component)?;
+        let names: Vec<String> = describe_exports(engine, &component)
+            .into_iter()
+            .map(|export| export.name)
+            .collect();
+        Ok::<_, PluginError>(names)
+    })
+    .await
+    .map_err(|e| PluginError::CompileFailed(e.to_string()))??;
+    let compile_time = compile_started.elapsed();
+
+    let (present, missing) = REQUIRED_EXPORTS
+        .iter()
+        .map(|name| name.to_string())
+        .partition(|name| exports.contains(name));
+    let warnings = OPTIONAL_EXPORTS
+        .iter()
+        .filter(|name| !exports.iter().any(|export| export == *name))
+        .map(|name| format!("optional export {} is missing", name))
+        .collect();
+
+    let info = PluginInfo {
+        id,
+        path: path.clone(),
+        name: manifest.as_ref().map(|m| m.name.clone()),
+        version: manifest.as_ref().map(|m| m.version.clone()),
+        author: manifest.as_ref().and_then(|m| m.author.clone()),
+        loaded_at: millis_since_epoch(SystemTime::now()),
+        error: None,
+        health: None,
+        enabled: false,
+        priority: manifest.as_ref().map(|m| m.priority).unwrap_or(0),
+        dirty: false,
+        warm: false,
+        compile_ms: Some(compile_time.as_millis() as u64),
+        size_bytes: Some(size_bytes),
+        id_source: Some(id_source),
+    };
+
+    Ok(PluginValidation {
+        info,
+        present,
+        missing,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{component, engine, TestApp, ANSWER};
+
+    #[tokio::test]
+    async fn only_get_number_is_required() {
+        engine();
+        let app = TestApp::new();
+        let path = app.write_plugin("validate-answer.wasm", &component(ANSWER), None);
+        let validation = validate_plugin(path).await.unwrap();
+        assert_eq!(validation.present, ["test#get-number"]);
+        assert!(validation.missing.is_empty());
+        assert_eq!(
+            validation.warnings,
+            ["optional export test#transform is missing", "optional export test#get-stats is missing"]
+        );
+    }
+
+    #[tokio::test]
+    async fn plugins_without_get_number_are_missing_it() {
+        engine();
+        let app = TestApp::new();
+        let path = app.write_plugin("validate-empty.wasm", &component("(component)"), None);
+        let validation = validate_plugin(path).await.unwrap();
+        assert_eq!(validation.missing, ["test#get-number"]);
+    }
+}
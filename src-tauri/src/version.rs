@@ -0,0 +1,138 @@
+use std::fmt;
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::Component;
+use wasmtime::Engine;
+
+use crate::error::PluginError;
+
+/// The WIT package the host implements.
+pub const PACKAGE: &str = "pato:plugin-ui";
+
+/// The package version the host implements. Keep in step with `world.wit` and
+/// the versioned export names in `messaging` and `lifecycle`.
+pub const HOST_VERSION: Version = Version::new(0, 1, 0);
+
+/// The wasmtime release the host is built against, for error messages. Keep in
+/// step with `Cargo.toml`.
+pub const WASMTIME: &str = "25.0";
+
+/// A `major.minor.patch` package version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version { major, minor, patch }
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        // Pre-release and build suffixes don't affect compatibility here
+        let core = text.split(['-', '+']).next()?;
+        let mut parts = core.split('.').map(|part| part.parse().ok());
+        let version = Version::new(parts.next()??, parts.next()??, parts.next()??);
+        parts.next().is_none().then_some(version)
+    }
+
+    /// Follows semver (and wasmtime's linker): versions are compatible when they
+    /// share a major version, or, before 1.0, a minor version.
+    pub fn is_compatible_with(&self, other: &Version) -> bool {
+        match (self.major, other.major) {
+            (0, 0) => self.minor == other.minor,
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Describes the versions this host accepts, e.g. `0.1.x`.
+pub fn supported_range() -> String {
+    if HOST_VERSION.major == 0 {
+        format!("{}.{}.x", HOST_VERSION.major, HOST_VERSION.minor)
+    } else {
+        format!("{}.x", HOST_VERSION.major)
+    }
+}
+
+/// Checks the version of `pato:plugin-ui` the component was built against, read
+/// from the interfaces it imports, before the linker gets a chance to reject it
+/// with something less readable. Plugins importing none of them always pass.
+pub fn check(engine: &Engine, component: &Component) -> Result<(), PluginError> {
+    let prefix = format!("{}/", PACKAGE);
+
+    for (name, item) in component.component_type().imports(engine) {
+        let Some(interface) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        if !matches!(item, ComponentItem::ComponentInstance(_)) {
+            continue;
+        }
+
+        let mismatch = |actual: String| PluginError::VersionMismatch {
+            expected: supported_range(),
+            actual,
+        };
+        let version = match interface.split_once('@') {
+            Some((_, version)) => Version::parse(version).ok_or_else(|| mismatch(version.to_string()))?,
+            None => return Err(mismatch("unversioned".to_string())),
+        };
+        if !version.is_compatible_with(&HOST_VERSION) {
+            return Err(mismatch(version.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{component, engine};
+
+    fn importing(interface: &str) -> Result<(), PluginError> {
+        let wat = format!(r#"(component (import "{}" (instance)))"#, interface);
+        let component = Component::new(engine(), component(&wat)).unwrap();
+        check(engine(), &component)
+    }
+
+    #[test]
+    fn versions_parse_without_their_suffixes() {
+        assert_eq!(Version::parse("0.1.2"), Some(Version::new(0, 1, 2)));
+        assert_eq!(Version::parse("1.2.3-rc.1+build"), Some(Version::new(1, 2, 3)));
+        assert_eq!(Version::parse("1.2"), None);
+        assert_eq!(Version::parse("1.2.3.4"), None);
+        assert_eq!(Version::parse("one.2.3"), None);
+    }
+
+    #[test]
+    fn compatibility_follows_semver() {
+        assert!(Version::new(0, 1, 0).is_compatible_with(&Version::new(0, 1, 9)));
+        assert!(!Version::new(0, 1, 0).is_compatible_with(&Version::new(0, 2, 0)));
+        assert!(Version::new(1, 0, 0).is_compatible_with(&Version::new(1, 4, 0)));
+        assert!(!Version::new(1, 0, 0).is_compatible_with(&Version::new(2, 0, 0)));
+    }
+
+    #[test]
+    fn plugins_built_against_another_package_version_are_rejected() {
+        assert!(importing("pato:plugin-ui/host-info@0.1.5").is_ok());
+        // Other packages aren't ours to check
+        assert!(importing("wasi:cli/environment@0.2.0").is_ok());
+
+        let result = importing("pato:plugin-ui/host-info@0.2.0");
+        assert!(
+            matches!(&result, Err(PluginError::VersionMismatch { expected, actual }) if expected == "0.1.x" && actual == "0.2.0"),
+            "{:?}",
+            result
+        );
+        let result = importing("pato:plugin-ui/host-info");
+        assert!(matches!(&result, Err(PluginError::VersionMismatch { actual, .. }) if actual == "unversioned"));
+    }
+}
@@ -0,0 +1,118 @@
+use crate::error::PluginError;
+use crate::introspect::describe_exports;
+use crate::registry::PluginInstance;
+use crate::{dynamic, plugin_instance, recovery, registry, shared_engine, static_bindings};
+
+/// Gets plugin `id` ready to take its next call without setting anything up
+/// first. A plugin an interrupted call left dirty is re-instantiated, `init` and
+/// all, here rather than at the start of that call; then a no-op call goes
+/// through its store, resolving every export the way a real call would.
+pub async fn warmup(id: &str) -> Result<(), PluginError> {
+    let exports = {
+        let engine = shared_engine()?;
+        let registry = registry();
+        let loaded = registry.get(id).ok_or_else(|| PluginError::NotLoaded(id.to_string()))?;
+        describe_exports(engine, &loaded.component)
+    };
+    let names: Vec<String> = exports.into_iter().map(|export| export.name).collect();
+
+    // Through the recovery path, which respawns a dirty plugin and marks it warm
+    recovery::with_trap_retries(id, || touch(id, &names)).await?;
+    println!("🔥 Plugin {} is warm", id);
+    Ok(())
+}
+
+// The no-op call: takes the store like any call would, but runs no plugin code
+async fn touch(id: &str, names: &[String]) -> Result<(), PluginError> {
+    let (instance, _) = plugin_instance(id)?;
+    let mut guard = instance.lock().await;
+    let PluginInstance { store, instance } = &mut *guard;
+
+    let _ = static_bindings(store, instance);
+    for name in names {
+        dynamic::find_func(store, instance, name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+    use std::time::{Duration, Instant};
+
+    // `init` burns through a few million instructions, so instantiating is slow
+    // next to calling `get-number`
+    const SLOW_INIT: &str = r#"
+    (component
+      (core module $m
+        (memory (export "memory") 1)
+        (global $next (mut i32) (i32.const 1024))
+        (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+          (local $ptr i32)
+          (local.set $ptr (i32.and (i32.add (global.get $next) (i32.const 7)) (i32.const -8)))
+          (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+          (local.get $ptr))
+        (func (export "init") (param i32 i32)
+          (local $n i32)
+          (local.set $n (i32.const 5000000))
+          (loop $spin
+            (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+            (br_if $spin (local.get $n))))
+        (func (export "get-number") (result i32) i32.const 7))
+      (core instance $i (instantiate $m))
+      (func $init (param "params" (list (tuple string string)))
+        (canon lift (core func $i "init") (memory $i "memory") (realloc (func $i "realloc"))))
+      (func $get-number (result u32) (canon lift (core func $i "get-number")))
+      (instance $lifecycle (export "init" (func $init)))
+      (instance $test (export "get-number" (func $get-number)))
+      (export "pato:plugin-ui/lifecycle@0.1.0" (instance $lifecycle))
+      (export "test" (instance $test)))
+    "#;
+
+    async fn timed_call(id: &str) -> Duration {
+        let started = Instant::now();
+        let result = dynamic::invoke(id, "test#get-number", serde_json::Value::Null, None).await;
+        assert_eq!(result.unwrap(), serde_json::json!(7));
+        started.elapsed()
+    }
+
+    fn set_dirty(id: &str) {
+        registry().get_mut(id).unwrap().dirty = true;
+    }
+
+    #[tokio::test]
+    async fn first_call_after_warmup_skips_instantiation() {
+        let app = TestApp::new();
+        let id = app.load("warmup-slow-init", SLOW_INIT).await.unwrap();
+        assert!(!registry().list_info().iter().any(|info| info.id == id && info.warm));
+
+        // As if a call had been interrupted: the next one has to re-instantiate
+        set_dirty(&id);
+        let cold = timed_call(&id).await;
+
+        set_dirty(&id);
+        warmup(&id).await.unwrap();
+        let info = registry().list_info().into_iter().find(|info| info.id == id).unwrap();
+        assert!(info.warm && !info.dirty);
+        let warm = timed_call(&id).await;
+
+        assert!(warm < cold, "warm call took {:?}, cold call {:?}", warm, cold);
+    }
+
+    #[tokio::test]
+    async fn interrupted_calls_leave_the_plugin_cold() {
+        let app = TestApp::new();
+        let id = app.load("warmup-interrupted", SLOW_INIT).await.unwrap();
+        warmup(&id).await.unwrap();
+
+        let result = recovery::with_trap_retries(&id, || async {
+            Err::<(), _>(PluginError::Cancelled("test".to_string()))
+        })
+        .await;
+        assert!(result.is_err());
+        let plugins = registry();
+        let loaded = plugins.get(&id).unwrap();
+        assert!(loaded.dirty && !loaded.warm);
+    }
+}
@@ -0,0 +1,136 @@
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use wasmtime::Engine;
+
+use crate::embedder::AppHandle;
+use crate::introspect::describe_exports;
+use crate::{is_plugin_file, load_plugin, registry, reload_plugin, unload_plugin};
+
+/// How long a file has to stay quiet before we act on it. Compilers write
+/// components in several chunks, so acting on the first event would load a
+/// truncated file.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Kept alive for as long as the directory should be watched
+static WATCHER: Mutex<Option<Debouncer<RecommendedWatcher>>> = Mutex::new(None);
+
+/// Starts watching `plugins_dir`, loading, reloading, or unloading plugins as
+/// their `.wasm` or `.patopkg` files change.
+pub fn watch_plugins_dir(
+    app: AppHandle,
+    engine: Engine,
+    plugins_dir: PathBuf,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| match result {
+        Ok(events) => {
+            // Several events for the same file collapse into one change
+            let paths: HashSet<PathBuf> = events
+                .into_iter()
+                .map(|event| event.path)
+                .filter(|path| is_plugin_file(path))
+                .collect();
+
+            for path in paths {
+                crate::async_runtime::block_on(handle_change(&app, &engine, &path));
+            }
+        }
+        Err(e) => println!("⚠️ Plugin watcher error: {}", e),
+    })?;
+
+    debouncer
+        .watcher()
+        .watch(&plugins_dir, RecursiveMode::NonRecursive)?;
+    println!("👀 Watching plugins directory: {:?}", plugins_dir);
+
+    *WATCHER.lock().unwrap() = Some(debouncer);
+    Ok(())
+}
+
+/// Stops watching. Dropping the debouncer shuts down its background thread.
+pub fn stop() {
+    let debouncer = WATCHER.lock().unwrap().take();
+    if let Some(debouncer) = debouncer {
+        drop(debouncer);
+        println!("👀 Stopped watching the plugins directory");
+    }
+}
+
+async fn handle_change(app: &AppHandle, engine: &Engine, path: &Path) {
+    // The manifest may give the plugin a different id than its file name, so look it up by path
+    let (known_id, is_loaded, is_enabled, loaded_at) = {
+        let registry = registry();
+        let id = registry.id_for_path(path);
+        let loaded = id.as_ref().and_then(|id| registry.get(id));
+        let (is_loaded, is_enabled) = (loaded.is_some(), loaded.is_some_and(|loaded| loaded.enabled));
+        let loaded_at = loaded.map(|loaded| loaded.loaded_at);
+        (id, is_loaded, is_enabled, loaded_at)
+    };
+    // Something else (like an install) may have loaded the file since it was written
+    let modified = std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    let is_stale = match (modified, loaded_at) {
+        (Some(modified), Some(loaded_at)) => modified > loaded_at,
+        _ => true,
+    };
+
+    // The debouncer only reports that something happened, so work out what from the file and registry
+    match (path.exists(), known_id) {
+        (false, Some(id)) => {
+            if unload_plugin(&id).await {
+                let _ = app.emit("plugin-unloaded", &id);
+            }
+        }
+        (false, None) => {}
+        // A disabled plugin is left as it is; its `init` would run on reload otherwise
+        (true, Some(id)) if is_loaded && !is_enabled => {
+            println!("⏸️ Not reloading disabled plugin {}", id)
+        }
+        (true, Some(_)) if is_loaded && !is_stale => {}
+        (true, Some(id)) if is_loaded => {
+            let old_exports = export_names(engine, &id);
+            match reload_plugin(app, &id).await {
+                Ok(()) => {
+                    println!("✅ Reloaded plugin {}", id);
+                    print_export_diff(&id, &old_exports, &export_names(engine, &id));
+                    let _ = app.emit("plugin-reloaded", &id);
+                    let _ = app.emit("dev-toast", format!("Reloaded {}", id));
+                }
+                Err(e) => {
+                    println!("❌ Failed to reload plugin {}: {}", id, e);
+                    let _ = app.emit("dev-toast", format!("Failed to reload {}: {}", id, e));
+                }
+            }
+        }
+        (true, _) => match load_plugin(app, engine, path).await {
+            Ok(id) => {
+                println!("✅ Successfully loaded plugin: {:?}", path.file_name().unwrap());
+                let _ = app.emit("plugin-loaded", &id);
+            }
+            Err(e) => println!("❌ Failed to load plugin {:?}: {}", path.file_name().unwrap(), e),
+        },
+    }
+}
+
+fn export_names(engine: &Engine, id: &str) -> BTreeSet<String> {
+    let registry = registry();
+    let Some(loaded) = registry.get(id) else {
+        return BTreeSet::new();
+    };
+    describe_exports(engine, &loaded.component)
+        .into_iter()
+        .map(|export| export.name)
+        .collect()
+}
+
+/// Prints which exports a reload added or removed, so signature mistakes show up straight away.
+fn print_export_diff(id: &str, old: &BTreeSet<String>, new: &BTreeSet<String>) {
+    for added in new.difference(old) {
+        println!("  ➕ {} now exports {}", id, added);
+    }
+    for removed in old.difference(new) {
+        println!("  ➖ {} no longer exports {}", id, removed);
+    }
+}
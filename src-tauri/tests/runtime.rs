@@ -0,0 +1,105 @@
+//! Hosts plugins through the runtime's public API, with a plain embedder
+//! standing in for the Tauri app.
+
+use pato_lib::embedder::{AppHandle, Embedder};
+use pato_lib::error::PluginError;
+use pato_lib::runtime::{MissingDirMode, PluginRuntime, RuntimeLimits};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// Exports `test#get-number`, answering 42, and imports nothing from the host
+const ANSWER: &str = r#"
+(component
+  (core module $m
+    (func (export "get-number") (result i32) i32.const 42))
+  (core instance $i (instantiate $m))
+  (func $get-number (result u32) (canon lift (core func $i "get-number")))
+  (instance $test (export "get-number" (func $get-number)))
+  (export "test" (instance $test)))
+"#;
+
+/// Keeps everything under one directory and remembers what was emitted.
+#[derive(Clone)]
+struct TestEmbedder {
+    root: PathBuf,
+    events: Arc<Mutex<Vec<(String, Value)>>>,
+}
+
+impl TestEmbedder {
+    fn new(root: &Path) -> Self {
+        TestEmbedder {
+            root: root.to_path_buf(),
+            events: Arc::default(),
+        }
+    }
+}
+
+impl Embedder for TestEmbedder {
+    fn emit_value(&self, event: &str, payload: Value) -> Result<(), String> {
+        self.events.lock().unwrap().push((event.to_string(), payload));
+        Ok(())
+    }
+
+    fn app_data_dir(&self) -> Option<PathBuf> {
+        Some(self.root.join("data"))
+    }
+
+    fn app_config_dir(&self) -> Option<PathBuf> {
+        Some(self.root.join("config"))
+    }
+
+    fn app_cache_dir(&self) -> Option<PathBuf> {
+        Some(self.root.join("cache"))
+    }
+
+    fn version(&self) -> String {
+        "0.0.0-test".to_string()
+    }
+}
+
+#[tokio::test]
+async fn loads_calls_and_unloads_a_plugin_without_tauri() {
+    let root = tempfile::tempdir().unwrap();
+    let plugins_dir = root.path().join("plugins");
+    std::fs::create_dir_all(&plugins_dir).unwrap();
+    std::fs::write(plugins_dir.join("answer.wasm"), wat::parse_str(ANSWER).unwrap()).unwrap();
+
+    let embedder = TestEmbedder::new(root.path());
+    let app = AppHandle::new(embedder.clone());
+    let runtime = PluginRuntime::builder()
+        .plugins_dir(&plugins_dir)
+        .missing_dir(MissingDirMode::Error)
+        .build(&app)
+        .unwrap();
+
+    // There's one runtime per process, so others have to agree with it
+    let elsewhere = PluginRuntime::builder().plugins_dir(root.path().join("elsewhere")).build(&app);
+    assert!(matches!(elsewhere, Err(PluginError::RuntimeConflict(_))));
+    let limited = PluginRuntime::builder()
+        .limits(RuntimeLimits {
+            fuel: Some(1),
+            ..RuntimeLimits::default()
+        })
+        .build(&app);
+    assert!(matches!(limited, Err(PluginError::RuntimeConflict(_))));
+    let same = PluginRuntime::builder().plugins_dir(&plugins_dir).build(&app).unwrap();
+    assert_eq!(same.plugins_dir(), plugins_dir);
+
+    let summary = runtime.load_all().await.unwrap();
+    assert_eq!((summary.loaded, summary.failed), (1, 0));
+
+    let plugins = runtime.list();
+    assert_eq!(plugins.len(), 1);
+    let id = plugins[0].id.clone();
+    assert_eq!(id, "answer");
+    // The plugin's data directory comes from the embedder, not Tauri's path resolver
+    assert!(root.path().join("data").join("plugin-data").join(&id).is_dir());
+
+    let result = runtime.call(&id, "test#get-number", json!([]), None).await.unwrap();
+    assert_eq!(result, json!(42));
+
+    assert!(runtime.unload(&id).await);
+    assert!(runtime.list().is_empty());
+    assert!(!root.path().join("data").join("plugin-data").join(&id).exists());
+}
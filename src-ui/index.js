@@ -5,6 +5,29 @@ async function clickButton() {
   await invoke('handle_button_click');
 }
 
+// Ask every plugin for its number; the total arrives as a `numbers-summed` event
+async function sumAllNumbers() {
+  // @ts-ignore
+  const { invoke } = window.__TAURI__.core;
+  await invoke('sum_all_numbers');
+}
+
+/**
+ * @typedef {{ id: string, value: number | null, error: { kind: string, message?: unknown } | null }} Contribution
+ * @typedef {{ total: number, contributions: Contribution[] }} NumberTotal
+ */
+
+/**
+ * @param {NumberTotal} result
+ */
+function showTotal(result) {
+  const element = document.getElementById('result');
+  if (element) {
+    const parts = result.contributions.map((c) => c.error ? c.id + ': ❌ ' + c.error.kind : c.id + ': ' + c.value);
+    element.innerHTML = '<p>🧮 Total: ' + result.total + '</p><p>' + parts.join(', ') + '</p>';
+  }
+}
+
 /**
  * @param {string} message
  */
@@ -21,6 +44,88 @@ function showResult(message) {
   }
 }
 
+/**
+ * @typedef {{ id: string, path: string, loaded_at: number, error: string | null, enabled: boolean }} PluginInfo
+ */
+
+// Show a plugin's call metrics next to its memory and handle usage
+/**
+ * @param {string} id
+ */
+async function showStats(id) {
+  // @ts-ignore
+  const { invoke } = window.__TAURI__.core;
+  const [metrics, usage] = await Promise.all([
+    invoke('plugin_metrics', { id }),
+    invoke('plugin_resource_usage', { id }),
+  ]);
+
+  const element = document.getElementById('result');
+  if (element) {
+    const calls = Object.entries(metrics.functions).map(([name, m]) => name + ': ' + m.calls + ' call(s), ' + m.errors + ' error(s)');
+    const memory = Math.round(usage.memory_bytes / 1024) + ' KiB of ' + Math.round(usage.memory_limit_bytes / 1024) + ' KiB';
+    element.innerHTML = '<p>📊 ' + id + '</p><p>' + (calls.join('<br>') || 'No calls yet') + '</p>'
+      + '<p>Memory: ' + memory + ', table elements: ' + usage.table_elements + ', open handles: ' + usage.open_handles + '</p>';
+  }
+}
+
+// Render the list of loaded (and failed) plugins
+async function refreshPlugins() {
+  // @ts-ignore
+  const { invoke } = window.__TAURI__.core;
+  /** @type {PluginInfo[]} */
+  const plugins = await invoke('list_plugins');
+
+  const list = document.getElementById('plugins');
+  if (!list) {
+    return;
+  }
+
+  list.innerHTML = '';
+  /** @type {string | null} */
+  const unavailable = await invoke('plugins_unavailable');
+  if (unavailable) {
+    list.innerHTML = '<li style="color: red;">⚠️ Plugins are unavailable: ' + unavailable + '</li>';
+    return;
+  }
+  if (plugins.length === 0) {
+    list.innerHTML = '<li>No plugins loaded</li>';
+    return;
+  }
+
+  for (const plugin of plugins) {
+    const item = document.createElement('li');
+    const loadedAt = new Date(plugin.loaded_at).toLocaleTimeString();
+    if (plugin.error) {
+      item.innerHTML = '<span style="color: red;">❌ ' + plugin.id + '</span> - ' + plugin.error + ' ';
+
+      const retry = document.createElement('button');
+      retry.textContent = 'Retry';
+      retry.addEventListener('click', async () => {
+        await invoke('retry_failed', { path: plugin.path }).catch(() => refreshPlugins());
+      });
+      item.appendChild(retry);
+    } else {
+      const icon = plugin.enabled ? '✅ ' : '⏸️ ';
+      item.textContent = icon + plugin.id + ' (loaded ' + loadedAt + ') ';
+
+      const toggle = document.createElement('button');
+      toggle.textContent = plugin.enabled ? 'Disable' : 'Enable';
+      toggle.addEventListener('click', async () => {
+        await invoke('set_plugin_enabled', { id: plugin.id, enabled: !plugin.enabled });
+      });
+      item.appendChild(toggle);
+
+      const stats = document.createElement('button');
+      stats.textContent = 'Stats';
+      stats.addEventListener('click', () => showStats(plugin.id));
+      item.appendChild(stats);
+    }
+    item.title = plugin.path;
+    list.appendChild(item);
+  }
+}
+
 // Wait for DOM and Tauri to be ready
 document.addEventListener('DOMContentLoaded', async () => {
   // @ts-ignore
@@ -30,8 +135,31 @@ document.addEventListener('DOMContentLoaded', async () => {
   await listen('button-clicked', (/** @type {{ payload: string }} */ event) => {
     showResult(event.payload);
   });
+
+  // Only sent in dev mode, when the watcher reloads a plugin
+  await listen('dev-toast', (/** @type {{ payload: string }} */ event) => {
+    showResult(event.payload);
+  });
+
+  await listen('numbers-summed', (/** @type {{ payload: NumberTotal }} */ event) => {
+    showTotal(event.payload);
+  });
+
+  // Keep the plugin list in sync as plugins come and go
+  for (const name of ['plugins-ready', 'plugin-loaded', 'plugin-reloaded', 'plugin-unloaded', 'plugin-unhealthy', 'plugin-enabled-changed', 'plugin-load-failed', 'plugins-reloaded']) {
+    await listen(name, refreshPlugins);
+  }
+
+  // Loading may have finished before we started listening
+  // @ts-ignore
+  const { invoke } = window.__TAURI__.core;
+  if (await invoke('plugins_ready')) {
+    await refreshPlugins();
+  }
 });
 
 // Make functions available globally
 window.clickButton = clickButton;
-window.showResult = showResult;
\ No newline at end of file
+window.sumAllNumbers = sumAllNumbers;
+window.showResult = showResult;
+window.refreshPlugins = refreshPlugins;
\ No newline at end of file